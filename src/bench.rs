@@ -0,0 +1,97 @@
+/*
+   This file is part of term-video.
+
+   term-video is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   term-video is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with term-video.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Render throughput measurements, without decoding frames or waiting for the display.
+
+use crate::Renderer;
+use image::{imageops::FilterType, DynamicImage, GenericImageView};
+use std::time::{Duration, Instant};
+
+/// How long rendering single frames took.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Timings {
+    pub frames: usize,
+    pub min: Duration,
+    pub max: Duration,
+    pub mean: Duration,
+    pub total: Duration,
+}
+
+impl Timings {
+    /// Summarizes the render times of single frames, `None` without any.
+    pub fn from_durations(durations: &[Duration]) -> Option<Timings> {
+        let total: Duration = durations.iter().sum();
+        Some(Timings {
+            frames: durations.len(),
+            min: *durations.iter().min()?,
+            max: *durations.iter().max()?,
+            mean: total / durations.len() as u32,
+            total,
+        })
+    }
+
+    /// Frames rendered per second, one after the other.
+    pub fn frames_per_second(&self) -> f64 {
+        self.frames as f64 / self.total.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+/// Renders all `frames` `rounds` times with `renderer`, timing each frame on its own. The
+/// frames are first scaled to the renderer's pixel grid, which isn't part of the timings.
+pub fn measure(frames: &[DynamicImage], renderer: &Renderer, rounds: u32) -> Option<Timings> {
+    let (width, height) = renderer.options().frame_size();
+    let frames: Vec<DynamicImage> = frames
+        .iter()
+        .map(|frame| {
+            if frame.width() == width && frame.height() == height {
+                frame.clone()
+            } else {
+                frame.resize_exact(width, height, FilterType::Triangle)
+            }
+        })
+        .collect();
+
+    let mut durations = Vec::with_capacity(frames.len() * rounds as usize);
+    for _ in 0..rounds {
+        for frame in &frames {
+            let started = Instant::now();
+            let text = renderer.render_frame(frame);
+            durations.push(started.elapsed());
+            // keep the work from being optimized away
+            std::hint::black_box(text);
+        }
+    }
+    Timings::from_durations(&durations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timings_summarize_frame_durations() {
+        let millis = |ms: u64| Duration::from_millis(ms);
+        let timings = Timings::from_durations(&[millis(4), millis(1), millis(7)]).unwrap();
+        assert_eq!(timings.frames, 3);
+        assert_eq!(timings.min, millis(1));
+        assert_eq!(timings.max, millis(7));
+        assert_eq!(timings.mean, millis(4));
+        assert_eq!(timings.total, millis(12));
+        assert!((timings.frames_per_second() - 250.0).abs() < 1e-6);
+        assert_eq!(Timings::from_durations(&[]), None);
+    }
+}
@@ -0,0 +1,291 @@
+/*
+   This file is part of term-video.
+
+   term-video is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   term-video is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with term-video.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::ffmpeg::ExtractOptions;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File, TryLockError},
+    io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, UNIX_EPOCH},
+};
+use walkdir::WalkDir;
+
+pub const AUDIO_FILE: &str = "audio.wav";
+pub const METADATA_FILE: &str = "metadata.json";
+
+static CACHE_REMOVED: AtomicBool = AtomicBool::new(false);
+
+/// An exclusive lock on a cache directory, held on a `<name>.lock` file next to it for as
+/// long as this lives, so another run pointed at the same directory can't wipe the frames of
+/// this one while it extracts or plays them. The lock file stays behind afterwards: deleting
+/// it while another run may have just opened it would let two runs lock different files.
+#[derive(Debug)]
+pub struct CacheLock {
+    _file: File,
+}
+
+/// The error of a [`CacheLock`] another run holds. The cache directory is that run's, so
+/// this one must not clean it up.
+#[derive(Debug)]
+pub struct CacheInUse {
+    pub cache_dir: String,
+}
+
+impl std::fmt::Display for CacheInUse {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "cache directory {} is in use by another term-video, pick another one with --cache",
+            self.cache_dir
+        )
+    }
+}
+
+impl std::error::Error for CacheInUse {}
+
+impl CacheLock {
+    /// Locks `cache_dir`, failing right away with [`CacheInUse`] if another run holds it. A
+    /// lock file left behind by an earlier run isn't held by anyone and is taken over.
+    pub fn acquire(cache_dir: &str) -> Result<CacheLock> {
+        let context = || format!("cannot lock cache directory {}", cache_dir);
+        let dir = std::path::absolute(cache_dir).with_context(context)?;
+        let Some(name) = dir.file_name() else {
+            bail!("cannot lock cache directory {}: it has no name", cache_dir);
+        };
+        let path = dir.with_file_name(format!("{}.lock", name.to_string_lossy()));
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).with_context(context)?;
+        }
+        let file = File::create(&path).with_context(context)?;
+        match file.try_lock() {
+            Ok(()) => Ok(CacheLock { _file: file }),
+            Err(TryLockError::WouldBlock) => Err(CacheInUse {
+                cache_dir: cache_dir.to_owned(),
+            }
+            .into()),
+            Err(TryLockError::Error(e)) => Err(e).with_context(context),
+        }
+    }
+}
+
+/// Creates an empty directory at `name`, wiping the frames of an earlier run that were left
+/// there. Fails instead of deleting anything that doesn't look like it came from a cache
+/// directory, so a `--cache` pointing at the wrong folder can't take its files with it.
+pub fn make_dir(name: &str) -> Result<()> {
+    let context = || format!("cannot use cache directory {}", name);
+    match fs::create_dir(name) {
+        Ok(()) => return Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+        Err(e) => return Err(e).with_context(context),
+    }
+    if !Path::new(name).is_dir() {
+        bail!("cannot use cache directory {}: it is a file", name);
+    }
+    if let Some(unrelated) = unrelated_entry(Path::new(name)).with_context(context)? {
+        bail!(
+            "cannot use cache directory {}: it holds {}, which term-video didn't put there. Pick an empty or new directory with --cache, or clear it yourself",
+            name,
+            unrelated.display()
+        );
+    }
+    fs::remove_dir_all(name).with_context(context)?;
+    fs::create_dir(name).with_context(context)
+}
+
+/// The first file or folder below `dir` that isn't one of the frames, audio track, metadata
+/// or clip folders of a cache directory, if there is one.
+fn unrelated_entry(dir: &Path) -> Result<Option<PathBuf>> {
+    for entry in WalkDir::new(dir).min_depth(1) {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy();
+        let known = if entry.file_type().is_dir() {
+            name.starts_with("clip-")
+        } else {
+            name.starts_with("frame-") || name == AUDIO_FILE || name == METADATA_FILE
+        };
+        if !known {
+            return Ok(Some(entry.into_path()));
+        }
+    }
+    Ok(None)
+}
+
+/// Deletes the temporary frame directory, at most once per run. A directory holding anything
+/// besides cache files is left alone, as [`make_dir`] never took it over.
+pub fn remove_cache(cache_dir: &str) -> Result<()> {
+    if !CACHE_REMOVED.swap(true, Ordering::SeqCst) {
+        if let Some(unrelated) = unrelated_entry(Path::new(cache_dir))? {
+            bail!(
+                "not deleting cache directory {}: it holds {}, which term-video didn't put there",
+                cache_dir,
+                unrelated.display()
+            );
+        }
+        fs::remove_dir_all(cache_dir)
+            .with_context(|| format!("could not delete temporary directory {}", cache_dir))?;
+    }
+    Ok(())
+}
+
+/// Collects the extracted frame images in a cache directory, in playback order.
+pub fn list_frames(cache_dir: &str) -> Result<Vec<PathBuf>> {
+    let mut frame_files = Vec::new();
+    for entry in WalkDir::new(cache_dir).into_iter().skip(1) {
+        let path = entry
+            .with_context(|| format!("could not read cache directory {}", cache_dir))?
+            .into_path();
+        let is_frame = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with("frame-"));
+        if is_frame {
+            frame_files.push(path);
+        }
+    }
+    frame_files.sort();
+    Ok(frame_files)
+}
+
+/// Describes what a cache directory was extracted from, so a later run with identical
+/// settings can skip ffmpeg entirely.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct CacheMetadata {
+    source: String,
+    mtime: u64,
+    width: u32,
+    height: u32,
+    fps: f64,
+    filter: String,
+    #[serde(default)]
+    trim: Vec<String>,
+    frame_count: usize,
+    /// Per-frame display durations in microseconds, for sources carrying their own timing
+    #[serde(default)]
+    frame_delays: Option<Vec<u64>>,
+}
+
+impl CacheMetadata {
+    pub fn new(source: &str, width: u32, height: u32, fps: f64, opts: &ExtractOptions) -> Self {
+        let mtime = fs::metadata(source)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_secs());
+
+        CacheMetadata {
+            source: source.to_owned(),
+            mtime,
+            width,
+            height,
+            fps,
+            filter: opts.filter(),
+            trim: opts.trim_args(),
+            frame_count: 0,
+            frame_delays: None,
+        }
+    }
+
+    /// Records what extraction produced: the number of frames and, if the source has its own
+    /// timing, how long each frame is shown.
+    pub fn with_frames(self, frame_count: usize, frame_delays: Option<&[Duration]>) -> Self {
+        CacheMetadata {
+            frame_count,
+            frame_delays: frame_delays
+                .map(|delays| delays.iter().map(|d| d.as_micros() as u64).collect()),
+            ..self
+        }
+    }
+
+    pub fn frame_delays(&self) -> Option<Vec<Duration>> {
+        self.frame_delays
+            .as_ref()
+            .map(|delays| delays.iter().map(|&d| Duration::from_micros(d)).collect())
+    }
+
+    /// Loads the metadata stored in `cache_dir`, provided it describes the same extraction as
+    /// this one and every frame it recorded is still present.
+    pub fn load_matching(&self, cache_dir: &str) -> Option<CacheMetadata> {
+        let stored = fs::read_to_string(Path::new(cache_dir).join(METADATA_FILE))
+            .ok()
+            .and_then(|json| serde_json::from_str::<CacheMetadata>(&json).ok())?;
+
+        let expected = CacheMetadata {
+            frame_count: stored.frame_count,
+            frame_delays: stored.frame_delays.clone(),
+            ..self.clone()
+        };
+        let complete = stored.frame_count > 0
+            && list_frames(cache_dir).is_ok_and(|frames| frames.len() == stored.frame_count);
+
+        if complete && stored == expected {
+            Some(stored)
+        } else {
+            None
+        }
+    }
+
+    pub fn write(&self, cache_dir: &str) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(Path::new(cache_dir).join(METADATA_FILE), json)
+            .context("could not write cache metadata")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    #[test]
+    fn cache_lock_keeps_other_runs_out() {
+        let dir = env::temp_dir().join(format!("term-video-test-{}-lock", process::id()));
+        let name = dir.to_str().unwrap();
+        let lock = CacheLock::acquire(name).unwrap();
+        let refused = CacheLock::acquire(name).unwrap_err();
+        drop(lock);
+        // released, so the next run gets it
+        let next = CacheLock::acquire(name);
+        let _ = fs::remove_file(dir.with_extension("lock"));
+        assert!(refused.is::<CacheInUse>());
+        assert!(next.is_ok());
+    }
+
+    #[test]
+    fn make_dir_only_wipes_cache_files() {
+        let dir = env::temp_dir().join(format!("term-video-test-{}-make-dir", process::id()));
+        let name = dir.to_str().unwrap();
+        make_dir(name).unwrap();
+        fs::create_dir(dir.join("clip-001")).unwrap();
+        fs::write(dir.join("clip-001").join("frame-0000001.png"), "").unwrap();
+        fs::write(dir.join(METADATA_FILE), "{}").unwrap();
+        // the frames of an earlier run are cleared away
+        make_dir(name).unwrap();
+        let emptied = fs::read_dir(&dir).unwrap().count();
+
+        fs::write(dir.join("notes.txt"), "keep me").unwrap();
+        let refused = make_dir(name);
+        let kept = dir.join("notes.txt").exists();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(emptied, 0);
+        assert!(format!("{:#}", refused.unwrap_err()).contains("notes.txt"));
+        assert!(kept);
+    }
+}
@@ -0,0 +1,268 @@
+/*
+   This file is part of term-video.
+
+   term-video is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   term-video is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with term-video.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use clap::ValueEnum;
+use std::fmt::Write as _;
+
+/// Resets all colors and text attributes.
+pub const RESET: &str = "\x1B[0m";
+
+// channel levels of the xterm-256 6x6x6 color cube (indices 16..=231)
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ColorMode {
+    /// Plain characters, no color escapes
+    Mono,
+    /// xterm-256 indexed palette
+    #[value(name = "256")]
+    Indexed,
+    /// 24-bit RGB
+    Truecolor,
+}
+
+impl ColorMode {
+    /// Length of the longest escape sequence emitted in front of a single cell.
+    pub fn escape_len(self) -> u32 {
+        match self {
+            ColorMode::Mono => 0,
+            // \x1B[38;5;255m
+            ColorMode::Indexed => 11,
+            // \x1B[38;2;255;255;255m
+            ColorMode::Truecolor => 19,
+        }
+    }
+
+    /// Appends the escape selecting `rgb` as foreground color, nothing in mono mode.
+    pub fn write_fg(self, buf: &mut String, rgb: [u8; 3]) {
+        self.write_color(buf, 38, rgb)
+    }
+
+    /// Appends the escape selecting `rgb` as background color, nothing in mono mode.
+    pub fn write_bg(self, buf: &mut String, rgb: [u8; 3]) {
+        self.write_color(buf, 48, rgb)
+    }
+
+    fn write_color(self, buf: &mut String, layer: u8, [r, g, b]: [u8; 3]) {
+        match self {
+            ColorMode::Mono => {}
+            ColorMode::Indexed => write!(buf, "\x1B[{};5;{}m", layer, rgb_to_256(r, g, b)).unwrap(),
+            ColorMode::Truecolor => write!(buf, "\x1B[{};2;{};{};{}m", layer, r, g, b).unwrap(),
+        }
+    }
+}
+
+/// How the color channels are weighted into a pixel's luminosity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum LumaMode {
+    /// ITU-R BT.709 weights, the perceptual luminance of sRGB and HD video
+    Rec709,
+    /// ITU-R BT.601 weights of SD video, giving red more and green less weight
+    Rec601,
+    /// The mean of the three channels
+    Average,
+}
+
+impl LumaMode {
+    fn weights(self) -> [f32; 3] {
+        match self {
+            LumaMode::Rec709 => [0.2126, 0.7152, 0.0722],
+            LumaMode::Rec601 => [0.299, 0.587, 0.114],
+            LumaMode::Average => [1.0 / 3.0; 3],
+        }
+    }
+}
+
+/// The luminosity of `rgb`, weighting the channels according to `mode`.
+pub fn luminance(rgb: [u8; 3], mode: LumaMode) -> u8 {
+    let weights = mode.weights();
+    let luma: f32 = rgb
+        .iter()
+        .zip(&weights)
+        .map(|(&channel, weight)| channel as f32 * weight)
+        .sum();
+    luma.round().min(255.0) as u8
+}
+
+/// The property of a pixel that picks its character.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum MapChannel {
+    /// Luminosity, weighted by the luma mode
+    Luma,
+    /// Saturation, dense characters for vivid colors and sparse ones for grays
+    Saturation,
+    /// Hue, going around the color wheel from red over green and blue back to red
+    Hue,
+}
+
+impl MapChannel {
+    /// The value of this channel of `rgb`, scaled to 0..=255, luminosity weighted by `mode`.
+    pub fn value(self, rgb: [u8; 3], mode: LumaMode) -> u8 {
+        match self {
+            MapChannel::Luma => luminance(rgb, mode),
+            MapChannel::Saturation => hsv(rgb)[1],
+            MapChannel::Hue => hsv(rgb)[0],
+        }
+    }
+}
+
+/// Hue, saturation and value of `rgb`, each scaled to 0..=255. Hue runs from red at 0 over
+/// green and blue back towards red, and is 0 for grays, which have no saturation.
+pub fn hsv([r, g, b]: [u8; 3]) -> [u8; 3] {
+    let max = r.max(g).max(b);
+    let chroma = (max - r.min(g).min(b)) as f32;
+    if chroma == 0.0 {
+        return [0, 0, max];
+    }
+    let (r, g, b) = (r as f32, g as f32, b as f32);
+    // sixths of the way around the color wheel
+    let sector = if max as f32 == r {
+        ((g - b) / chroma).rem_euclid(6.0)
+    } else if max as f32 == g {
+        (b - r) / chroma + 2.0
+    } else {
+        (r - g) / chroma + 4.0
+    };
+    let hue = (sector / 6.0 * 256.0).min(255.0) as u8;
+    let saturation = (chroma / max as f32 * 255.0).round() as u8;
+    [hue, saturation, max]
+}
+
+/// Maps an RGB color to the closest xterm-256 palette index, considering both the
+/// 6x6x6 color cube (16..=231) and the 24-step grayscale ramp (232..=255).
+pub fn rgb_to_256(r: u8, g: u8, b: u8) -> u8 {
+    fn nearest_level(c: u8) -> usize {
+        (0..CUBE_LEVELS.len())
+            .min_by_key(|&i| (CUBE_LEVELS[i] as i32 - c as i32).abs())
+            .unwrap()
+    }
+    fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> i32 {
+        let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2);
+        d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+    }
+
+    let (ri, gi, bi) = (nearest_level(r), nearest_level(g), nearest_level(b));
+    let cube = (CUBE_LEVELS[ri], CUBE_LEVELS[gi], CUBE_LEVELS[bi]);
+    let cube_index = 16 + 36 * ri + 6 * gi + bi;
+
+    // grayscale ramp runs from 8 to 238 in steps of 10
+    let avg = (r as u32 + g as u32 + b as u32) / 3;
+    let gray_step = (avg.saturating_sub(3) / 10).min(23);
+    let gray_level = (8 + gray_step * 10) as u8;
+    let gray_index = 232 + gray_step as usize;
+
+    if distance((r, g, b), (gray_level, gray_level, gray_level)) < distance((r, g, b), cube) {
+        gray_index as u8
+    } else {
+        cube_index as u8
+    }
+}
+
+/// The color of an xterm-256 palette index, the first 16 as xterm shows them by default.
+pub fn rgb_from_256(index: u8) -> [u8; 3] {
+    const SYSTEM: [[u8; 3]; 16] = [
+        [0, 0, 0],
+        [205, 0, 0],
+        [0, 205, 0],
+        [205, 205, 0],
+        [0, 0, 238],
+        [205, 0, 205],
+        [0, 205, 205],
+        [229, 229, 229],
+        [127, 127, 127],
+        [255, 0, 0],
+        [0, 255, 0],
+        [255, 255, 0],
+        [92, 92, 255],
+        [255, 0, 255],
+        [0, 255, 255],
+        [255, 255, 255],
+    ];
+    match index {
+        0..=15 => SYSTEM[index as usize],
+        16..=231 => {
+            let cube = index as usize - 16;
+            [cube / 36, cube / 6 % 6, cube % 6].map(|level| CUBE_LEVELS[level])
+        }
+        _ => [8 + (index - 232) * 10; 3],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn luminance_weights_primaries_per_mode() {
+        let primaries =
+            |mode| [[255, 0, 0], [0, 255, 0], [0, 0, 255]].map(|rgb| luminance(rgb, mode));
+        assert_eq!(primaries(LumaMode::Rec709), [54, 182, 18]);
+        assert_eq!(primaries(LumaMode::Rec601), [76, 150, 29]);
+        assert_eq!(primaries(LumaMode::Average), [85, 85, 85]);
+        for mode in [LumaMode::Rec709, LumaMode::Rec601, LumaMode::Average] {
+            assert_eq!(luminance([255, 255, 255], mode), 255);
+            assert_eq!(luminance([0, 0, 0], mode), 0);
+        }
+    }
+
+    #[test]
+    fn hsv_of_known_colors() {
+        assert_eq!(hsv([255, 0, 0]), [0, 255, 255]);
+        assert_eq!(hsv([0, 255, 0]), [85, 255, 255]);
+        assert_eq!(hsv([0, 0, 255]), [170, 255, 255]);
+        assert_eq!(hsv([255, 0, 255]), [213, 255, 255]);
+        assert_eq!(hsv([128, 128, 128]), [0, 0, 128]);
+        assert_eq!(hsv([0, 0, 0]), [0, 0, 0]);
+        // half as vivid
+        assert_eq!(hsv([200, 100, 100])[1], 128);
+    }
+
+    #[test]
+    fn map_channel_picks_the_property() {
+        let mode = LumaMode::Rec709;
+        assert_eq!(MapChannel::Luma.value([255, 0, 0], mode), 54);
+        assert_eq!(MapChannel::Saturation.value([255, 0, 0], mode), 255);
+        assert_eq!(MapChannel::Saturation.value([90, 90, 90], mode), 0);
+        assert_eq!(MapChannel::Hue.value([0, 0, 255], mode), 170);
+    }
+
+    #[test]
+    fn rgb_to_256_black() {
+        assert_eq!(rgb_to_256(0, 0, 0), 16);
+    }
+
+    #[test]
+    fn rgb_to_256_white() {
+        assert_eq!(rgb_to_256(255, 255, 255), 231);
+    }
+
+    #[test]
+    fn rgb_from_256_inverts_the_palette() {
+        for rgb in [[0, 0, 0], [255, 255, 255], [128, 128, 128], [95, 135, 215]] {
+            let index = rgb_to_256(rgb[0], rgb[1], rgb[2]);
+            let [r, g, b] = rgb_from_256(index);
+            assert_eq!(rgb_to_256(r, g, b), index);
+        }
+        assert_eq!(rgb_from_256(68), [95, 135, 215]);
+        assert_eq!(rgb_from_256(244), [128, 128, 128]);
+    }
+
+    #[test]
+    fn rgb_to_256_mid_gray_uses_grayscale_ramp() {
+        assert_eq!(rgb_to_256(128, 128, 128), 244);
+    }
+}
@@ -0,0 +1,249 @@
+/*
+   This file is part of term-video.
+
+   term-video is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   term-video is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with term-video.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Defaults for the command line options, read from a TOML file.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+/// Options that can be set in the config file, named like their command line counterparts,
+/// e.g. `charset-preset = "blocks"` for `--charset-preset blocks`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    pub ffmpeg_path: Option<PathBuf>,
+    pub ffprobe_path: Option<PathBuf>,
+    pub ffmpeg_args: Option<String>,
+    pub cache: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub force: Option<bool>,
+    pub fit: Option<bool>,
+    pub fps: Option<f64>,
+    pub sample_rate: Option<u32>,
+    pub interpolate: Option<String>,
+    pub tonemap: Option<String>,
+    pub no_tonemap: Option<bool>,
+    pub scale_filter: Option<String>,
+    pub supersample: Option<u32>,
+    pub color_mode: Option<String>,
+    pub color: Option<bool>,
+    pub luma_mode: Option<String>,
+    pub map_channel: Option<String>,
+    pub background: Option<String>,
+    pub tint: Option<String>,
+    pub alpha_threshold: Option<u8>,
+    pub charset: Option<String>,
+    pub charset_preset: Option<String>,
+    pub ramp: Option<String>,
+    pub brightness: Option<f32>,
+    pub contrast: Option<f32>,
+    pub gamma: Option<f32>,
+    pub invert: Option<bool>,
+    pub fade: Option<u32>,
+    pub levels: Option<u16>,
+    pub palette_size: Option<u16>,
+    pub auto_levels: Option<bool>,
+    pub auto_levels_mode: Option<String>,
+    pub dither: Option<bool>,
+    pub edges: Option<bool>,
+    pub edge_threshold: Option<u8>,
+    pub smooth: Option<bool>,
+    pub smooth_threshold: Option<u8>,
+    pub lazy: Option<bool>,
+    pub prebuffer: Option<usize>,
+    pub max_memory: Option<u64>,
+    pub jobs: Option<u32>,
+    pub keep_aspect: Option<bool>,
+    pub cell_aspect: Option<f32>,
+    pub no_cell_correct: Option<bool>,
+    pub audio: Option<bool>,
+    pub reuse_cache: Option<bool>,
+    pub render_cache: Option<bool>,
+    pub half_block: Option<bool>,
+    pub blocks: Option<bool>,
+    pub braille: Option<bool>,
+    pub braille_threshold: Option<u8>,
+    pub sixel: Option<bool>,
+    pub kitty: Option<bool>,
+    #[serde(rename = "loop")]
+    pub plays: Option<u32>,
+    pub loop_delay: Option<u64>,
+    pub diff: Option<bool>,
+    pub scroll: Option<bool>,
+    pub speed: Option<f64>,
+    pub reverse: Option<bool>,
+    pub bounce: Option<bool>,
+    pub quiet: Option<bool>,
+    pub stats: Option<bool>,
+    pub timecode: Option<String>,
+    pub font: Option<PathBuf>,
+    pub font_size: Option<f32>,
+    pub theme: Option<String>,
+}
+
+impl Config {
+    /// Where the config file is looked for when `--config` isn't given.
+    pub fn default_path() -> Option<PathBuf> {
+        let config_home = env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+        Some(config_home.join("term-video").join("config.toml"))
+    }
+
+    /// Reads the config file at `path`, or at the [default path](Self::default_path) if it
+    /// is None. Only a missing default file is fine and gives an empty config.
+    pub fn load(path: Option<&Path>) -> Result<Config> {
+        let (path, required) = match path {
+            Some(path) => (path.to_owned(), true),
+            None => match Self::default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Config::default()),
+            },
+        };
+        let toml = match fs::read_to_string(&path) {
+            Ok(toml) => toml,
+            Err(_) if !required && !path.exists() => return Ok(Config::default()),
+            Err(e) => return Err(e).with_context(|| format!("could not read {}", path.display())),
+        };
+        toml::from_str(&toml).with_context(|| format!("invalid config file {}", path.display()))
+    }
+
+    /// The configured options as default values for the command line arguments, keyed by
+    /// their argument id. Flags only show up when they are turned on.
+    pub fn defaults(&self) -> Vec<(&'static str, String)> {
+        fn value<T: ToString>(
+            id: &'static str,
+            value: &Option<T>,
+        ) -> Option<(&'static str, String)> {
+            value.as_ref().map(|value| (id, value.to_string()))
+        }
+        fn flag(id: &'static str, value: Option<bool>) -> Option<(&'static str, String)> {
+            (value == Some(true)).then(|| (id, String::from("true")))
+        }
+        let path = |id, path: &Option<PathBuf>| {
+            path.as_ref()
+                .map(|path| (id, path.to_string_lossy().into_owned()))
+        };
+
+        vec![
+            path("ffmpeg_path", &self.ffmpeg_path),
+            path("ffprobe_path", &self.ffprobe_path),
+            value("ffmpeg_args", &self.ffmpeg_args),
+            value("cache", &self.cache),
+            value("width", &self.width),
+            value("height", &self.height),
+            flag("force", self.force),
+            flag("fit", self.fit),
+            value("fps", &self.fps),
+            value("sample_rate", &self.sample_rate),
+            value("interpolate", &self.interpolate),
+            value("tonemap", &self.tonemap),
+            flag("no_tonemap", self.no_tonemap),
+            value("scale_filter", &self.scale_filter),
+            value("supersample", &self.supersample),
+            value("color_mode", &self.color_mode),
+            flag("color", self.color),
+            value("luma_mode", &self.luma_mode),
+            value("map_channel", &self.map_channel),
+            value("background", &self.background),
+            value("tint", &self.tint),
+            value("alpha_threshold", &self.alpha_threshold),
+            value("charset", &self.charset),
+            value("charset_preset", &self.charset_preset),
+            value("ramp", &self.ramp),
+            value("brightness", &self.brightness),
+            value("contrast", &self.contrast),
+            value("gamma", &self.gamma),
+            flag("invert", self.invert),
+            value("fade", &self.fade),
+            value("levels", &self.levels),
+            value("palette_size", &self.palette_size),
+            flag("auto_levels", self.auto_levels),
+            value("auto_levels_mode", &self.auto_levels_mode),
+            flag("dither", self.dither),
+            flag("edges", self.edges),
+            value("edge_threshold", &self.edge_threshold),
+            flag("smooth", self.smooth),
+            value("smooth_threshold", &self.smooth_threshold),
+            flag("lazy", self.lazy),
+            value("prebuffer", &self.prebuffer),
+            value("max_memory", &self.max_memory),
+            value("jobs", &self.jobs),
+            flag("keep_aspect", self.keep_aspect),
+            value("cell_aspect", &self.cell_aspect),
+            flag("no_cell_correct", self.no_cell_correct),
+            flag("audio", self.audio),
+            flag("reuse_cache", self.reuse_cache),
+            flag("render_cache", self.render_cache),
+            flag("half_block", self.half_block),
+            flag("blocks", self.blocks),
+            flag("braille", self.braille),
+            value("braille_threshold", &self.braille_threshold),
+            flag("sixel", self.sixel),
+            flag("kitty", self.kitty),
+            value("plays", &self.plays),
+            value("loop_delay", &self.loop_delay),
+            flag("diff", self.diff),
+            flag("scroll", self.scroll),
+            value("speed", &self.speed),
+            flag("reverse", self.reverse),
+            flag("bounce", self.bounce),
+            flag("quiet", self.quiet),
+            flag("stats", self.stats),
+            value("timecode", &self.timecode),
+            path("font", &self.font),
+            value("font_size", &self.font_size),
+            value("theme", &self.theme),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_maps_onto_argument_defaults() {
+        let config: Config = toml::from_str(
+            "charset-preset = \"blocks\"\nfps = 23.976\nloop = 0\ndiff = true\nquiet = false\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config.defaults(),
+            [
+                ("fps", String::from("23.976")),
+                ("charset_preset", String::from("blocks")),
+                ("plays", String::from("0")),
+                ("diff", String::from("true")),
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_options_are_rejected() {
+        assert!(toml::from_str::<Config>("colour = true").is_err());
+    }
+}
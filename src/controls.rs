@@ -0,0 +1,92 @@
+/*
+   This file is part of term-video.
+
+   term-video is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   term-video is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with term-video.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Keyboard controls during playback, read on a background thread in raw mode.
+
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    terminal,
+};
+use std::{
+    io::{self, IsTerminal},
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::Duration,
+};
+
+/// What a key press asks the player to do.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    TogglePause,
+    /// Jump back (-1) or ahead (+1) by one seek step
+    Seek(i8),
+    Quit,
+}
+
+pub struct Controls {
+    actions: Receiver<Action>,
+}
+
+impl Controls {
+    /// Puts the terminal into raw mode and starts listening for keys: space pauses, the arrow
+    /// keys seek and q, Esc or Ctrl-C quit. Returns None if there is no terminal to read from.
+    /// Raw mode is left again by [`restore_terminal`](crate::player::restore_terminal).
+    pub fn start() -> Option<Controls> {
+        if !io::stdin().is_terminal() || terminal::enable_raw_mode().is_err() {
+            return None;
+        }
+
+        let (tx, actions) = mpsc::channel();
+        thread::spawn(move || {
+            while let Ok(event) = event::read() {
+                let Event::Key(key) = event else { continue };
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                let action = match key.code {
+                    KeyCode::Char(' ') => Action::TogglePause,
+                    KeyCode::Left => Action::Seek(-1),
+                    KeyCode::Right => Action::Seek(1),
+                    KeyCode::Char('q') | KeyCode::Esc => Action::Quit,
+                    // raw mode swallows SIGINT, so Ctrl-C arrives as a key
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        Action::Quit
+                    }
+                    _ => continue,
+                };
+                if tx.send(action).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Some(Controls { actions })
+    }
+
+    /// The next pending action, if any.
+    pub fn poll(&self) -> Option<Action> {
+        self.actions.try_recv().ok()
+    }
+
+    /// Waits up to `timeout` for an action, forever if it is None.
+    pub fn wait(&self, timeout: Option<Duration>) -> Option<Action> {
+        match timeout {
+            Some(timeout) => self.actions.recv_timeout(timeout).ok(),
+            None => self.actions.recv().ok(),
+        }
+    }
+}
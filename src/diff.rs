@@ -0,0 +1,201 @@
+/*
+   This file is part of term-video.
+
+   term-video is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   term-video is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with term-video.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Incremental redraws: only the cells that changed since the previous frame get rewritten.
+
+use crate::color::RESET;
+use std::{fmt::Write, ops::Range};
+
+/// A character cell of a rendered frame: the color escapes in effect for it, as a byte range
+/// of the frame it was parsed from, and the character itself.
+#[derive(Clone, Debug)]
+struct Cell {
+    style: Range<usize>,
+    ch: char,
+}
+
+/// Remembers the frame currently on screen and turns the next one into a minimal update of
+/// cursor-positioned writes.
+#[derive(Default)]
+pub struct FrameDiff {
+    previous: String,
+    cells: Vec<Vec<Cell>>,
+}
+
+impl FrameDiff {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forgets the frame on screen, so the next one is drawn in full.
+    pub fn reset(&mut self) {
+        self.previous.clear();
+        self.cells.clear();
+    }
+
+    /// Computes the update turning the previously shown frame into `frame`, to be printed
+    /// after moving the cursor home. Returns None when the whole frame should be printed
+    /// instead: for the very first frame, when the frame size changed, or when so much
+    /// changed that the update would be larger than a full redraw.
+    pub fn update(&mut self, frame: &str) -> Option<String> {
+        let cells = parse_cells(frame);
+        let same_shape = !self.previous.is_empty()
+            && self.cells.len() == cells.len()
+            && self
+                .cells
+                .iter()
+                .zip(&cells)
+                .all(|(a, b)| a.len() == b.len());
+        let update = same_shape.then(|| self.changes(frame, &cells));
+
+        self.previous.clear();
+        self.previous.push_str(frame);
+        self.cells = cells;
+
+        update.filter(|update| update.len() < frame.len())
+    }
+
+    fn changes(&self, frame: &str, cells: &[Vec<Cell>]) -> String {
+        let mut update = String::new();
+        // where printing the next character would put it, the cursor starts at home
+        let mut cursor = (0, 0);
+        let mut active = "";
+
+        for (row, (old_line, new_line)) in self.cells.iter().zip(cells).enumerate() {
+            for (col, (old, new)) in old_line.iter().zip(new_line).enumerate() {
+                let style = &frame[new.style.clone()];
+                if old.ch == new.ch && self.previous[old.style.clone()] == *style {
+                    continue;
+                }
+                if cursor != (row, col) {
+                    let _ = write!(update, "\x1B[{};{}H", row + 1, col + 1);
+                }
+                if style != active {
+                    if !active.is_empty() {
+                        update.push_str(RESET);
+                    }
+                    update.push_str(style);
+                    active = style;
+                }
+                update.push(new.ch);
+                cursor = (row, col + 1);
+            }
+        }
+        if !active.is_empty() {
+            update.push_str(RESET);
+        }
+
+        update
+    }
+}
+
+/// Splits a rendered frame into lines of cells. Every cell inherits the escapes preceding it,
+/// or those of the previous cell on the same line if it has none, a reset clears them.
+fn parse_cells(frame: &str) -> Vec<Vec<Cell>> {
+    let mut lines = Vec::new();
+    let mut offset = 0;
+
+    for line in frame.split_terminator('\n') {
+        let mut cells = Vec::new();
+        let mut style = offset..offset;
+        // escapes seen since the last character
+        let mut pending: Option<Range<usize>> = None;
+        let mut chars = line.char_indices();
+
+        while let Some((i, ch)) = chars.next() {
+            if ch == '\x1B' {
+                // CSI sequences end in a byte from '@' to '~'
+                let mut end = i + 1;
+                for (j, c) in chars.by_ref() {
+                    end = j + c.len_utf8();
+                    if j > i + 1 && ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+                let (start, end) = (offset + i, offset + end);
+                pending = if &frame[start..end] == RESET {
+                    Some(end..end)
+                } else {
+                    Some(pending.map_or(start, |p| p.start)..end)
+                };
+                continue;
+            }
+            if let Some(p) = pending.take() {
+                style = p;
+            }
+            cells.push(Cell {
+                style: style.clone(),
+                ch,
+            });
+        }
+
+        lines.push(cells);
+        offset += line.len() + 1;
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_diff_draws_the_first_frame_in_full() {
+        let mut diff = FrameDiff::new();
+        assert_eq!(diff.update("abc\ndef\n"), None);
+    }
+
+    #[test]
+    fn frame_diff_rewrites_only_changed_cells() {
+        let mut diff = FrameDiff::new();
+        diff.update("    \n    \n    \n");
+        assert_eq!(
+            diff.update("    \n  @ \n    \n").as_deref(),
+            Some("\x1B[2;3H@")
+        );
+        // reverting the cell is a change as well, identical frames need no output at all
+        assert_eq!(
+            diff.update("    \n    \n    \n").as_deref(),
+            Some("\x1B[2;3H ")
+        );
+        assert_eq!(diff.update("    \n    \n    \n").as_deref(), Some(""));
+    }
+
+    #[test]
+    fn frame_diff_keeps_cell_colors() {
+        let red = "\x1B[38;5;196m";
+        let blue = "\x1B[38;5;21m";
+        let mut diff = FrameDiff::new();
+        diff.update(&format!("{red}ab {RESET}   \n"));
+        // only the color of the second cell changed
+        assert_eq!(
+            diff.update(&format!("{red}a{blue}b {RESET}   \n"))
+                .as_deref(),
+            Some(&*format!("\x1B[1;2H{blue}b {RESET}"))
+        );
+    }
+
+    #[test]
+    fn frame_diff_falls_back_to_full_redraws() {
+        let mut diff = FrameDiff::new();
+        diff.update("abcd\nefgh\n");
+        assert_eq!(diff.update("ijkl\nmnop\n"), None);
+        // a different size can't be diffed either
+        assert_eq!(diff.update("ijkl\n"), None);
+    }
+}
@@ -0,0 +1,85 @@
+/*
+   This file is part of term-video.
+
+   term-video is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   term-video is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with term-video.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Floyd–Steinberg error diffusion, trading banding in smooth gradients for fine noise.
+
+use crate::ramp::Ramp;
+
+/// Quantizes a `width`x`height` grid of luminosity values onto `steps` evenly spaced levels,
+/// returning the level index of every pixel in row-major order.
+pub fn dither(luma: &[u8], width: usize, height: usize, steps: usize) -> Vec<usize> {
+    dither_to(luma, width, height, Ramp::linear(steps).levels())
+}
+
+/// Like [`dither`], onto the given ascending `levels` rather than evenly spaced ones, every
+/// pixel getting the level closest to it.
+///
+/// Pixels are visited left to right, top to bottom, and the difference between a pixel and
+/// the level it was given is pushed onto the neighbours not visited yet: 7/16 to the right,
+/// 3/16 below left, 5/16 below and 1/16 below right.
+pub fn dither_to(luma: &[u8], width: usize, height: usize, levels: &[f32]) -> Vec<usize> {
+    // halfway between neighbouring levels, values from there on round up
+    let midpoints: Vec<f32> = levels
+        .windows(2)
+        .map(|pair| (pair[0] + pair[1]) / 2.0)
+        .collect();
+    // luminosity plus the error diffused into each pixel so far
+    let mut values: Vec<f32> = luma.iter().map(|&v| v as f32).collect();
+    let mut indices = Vec::with_capacity(values.len());
+
+    for y in 0..height {
+        for x in 0..width {
+            let i = y * width + x;
+            let value = values[i].clamp(0.0, 255.0);
+            let index = midpoints.partition_point(|&midpoint| midpoint <= value);
+            let error = value - levels.get(index).copied().unwrap_or(value);
+            indices.push(index);
+
+            if x + 1 < width {
+                values[i + 1] += error * 7.0 / 16.0;
+            }
+            if y + 1 < height {
+                if x > 0 {
+                    values[i + width - 1] += error * 3.0 / 16.0;
+                }
+                values[i + width] += error * 5.0 / 16.0;
+                if x + 1 < width {
+                    values[i + width + 1] += error / 16.0;
+                }
+            }
+        }
+    }
+    indices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flat_gray_averages_out() {
+        let levels = dither(&[128; 64 * 64], 64, 64, 2);
+        let lit = levels.iter().filter(|&&level| level == 1).count();
+        // half of the pixels are lit, give or take the edges
+        assert!((lit as i32 - 2048).abs() < 64, "{} pixels lit", lit);
+    }
+
+    #[test]
+    fn ramp_ends_are_kept() {
+        assert_eq!(dither(&[0, 255, 0, 255], 2, 2, 13), vec![0, 12, 0, 12]);
+    }
+}
@@ -0,0 +1,68 @@
+/*
+   This file is part of term-video.
+
+   term-video is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   term-video is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with term-video.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Sobel edge detection, for rendering outlines instead of brightness.
+
+/// Gradient magnitude of a `width`x`height` luminosity grid in row-major order, scaled back
+/// to `0..=255`. Magnitudes below `threshold` are dropped to zero to keep noise out of flat
+/// areas. Pixels past the border repeat the nearest edge pixel, so the outermost rows and
+/// columns only pick up gradients actually present in the frame.
+pub fn sobel(luma: &[u8], width: usize, height: usize, threshold: u8) -> Vec<u8> {
+    let at = |x: isize, y: isize| {
+        let x = x.clamp(0, width as isize - 1) as usize;
+        let y = y.clamp(0, height as isize - 1) as usize;
+        luma[y * width + x] as i32
+    };
+
+    let mut magnitudes = Vec::with_capacity(luma.len());
+    for y in 0..height as isize {
+        for x in 0..width as isize {
+            let gx = at(x + 1, y - 1) + 2 * at(x + 1, y) + at(x + 1, y + 1)
+                - at(x - 1, y - 1)
+                - 2 * at(x - 1, y)
+                - at(x - 1, y + 1);
+            let gy = at(x - 1, y + 1) + 2 * at(x, y + 1) + at(x + 1, y + 1)
+                - at(x - 1, y - 1)
+                - 2 * at(x, y - 1)
+                - at(x + 1, y - 1);
+            // a full-range step gives a magnitude of 4 * 255 along one axis
+            let magnitude = ((gx * gx + gy * gy) as f32).sqrt() / 4.0;
+            let magnitude = magnitude.round().min(255.0) as u8;
+            magnitudes.push(if magnitude < threshold { 0 } else { magnitude });
+        }
+    }
+    magnitudes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flat_areas_have_no_edges() {
+        assert!(sobel(&[200; 16], 4, 4, 0).iter().all(|&m| m == 0));
+    }
+
+    #[test]
+    fn step_is_an_edge_on_both_sides() {
+        let luma = [0, 0, 255, 255].repeat(3);
+        assert_eq!(sobel(&luma, 4, 3, 0), [0, 255, 255, 0].repeat(3));
+        // weaker edges than the threshold are suppressed
+        let luma = [0, 0, 40, 40].repeat(3);
+        assert_eq!(sobel(&luma, 4, 3, 50), [0; 12]);
+    }
+}
@@ -0,0 +1,498 @@
+/*
+   This file is part of term-video.
+
+   term-video is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   term-video is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with term-video.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Rendered frames drawn as pictures, for sharing an animation outside the terminal.
+//!
+//! Every character cell is drawn with a monospace font in the colors its escape sequences
+//! select, then the pictures are encoded into an animated GIF or, by piping them to ffmpeg,
+//! into any video format it can write.
+
+use crate::{color::rgb_from_256, images::is_gif, recording::Recording};
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use image::{
+    codecs::gif::{GifEncoder, Repeat},
+    Delay, DynamicImage, Frame, Rgb, RgbImage,
+};
+use rayon::prelude::*;
+use rusttype::{point, Font, Scale};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::Duration,
+};
+
+/// Monospace fonts looked for when none is given, in this order.
+const FONTS: &[&str] = &[
+    "/usr/share/fonts/truetype/dejavu/DejaVuSansMono.ttf",
+    "/usr/share/fonts/TTF/DejaVuSansMono.ttf",
+    "/usr/share/fonts/dejavu/DejaVuSansMono.ttf",
+    "/usr/share/fonts/dejavu-sans-mono-fonts/DejaVuSansMono.ttf",
+    "/usr/share/fonts/truetype/liberation/LiberationMono-Regular.ttf",
+    "/usr/share/fonts/liberation-mono/LiberationMono-Regular.ttf",
+    "/System/Library/Fonts/Menlo.ttc",
+    "/Library/Fonts/Courier New.ttf",
+    "C:\\Windows\\Fonts\\consola.ttf",
+];
+
+/// Colors of the cells whose escapes don't select any.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Theme {
+    /// Light gray characters on black, like most terminals
+    Dark,
+    /// Black characters on white
+    Light,
+}
+
+impl Theme {
+    fn foreground(self) -> [u8; 3] {
+        match self {
+            Theme::Dark => [229, 229, 229],
+            Theme::Light => [0, 0, 0],
+        }
+    }
+
+    fn background(self) -> [u8; 3] {
+        match self {
+            Theme::Dark => [0, 0, 0],
+            Theme::Light => [255, 255, 255],
+        }
+    }
+}
+
+pub struct ExportOptions {
+    pub font: Font<'static>,
+    /// Height of a line of characters in pixels
+    pub font_size: f32,
+    pub theme: Theme,
+    /// ffmpeg binary encoding everything but GIFs
+    pub ffmpeg: PathBuf,
+}
+
+/// The first of the usual monospace fonts installed on this system.
+pub fn find_font() -> Option<PathBuf> {
+    FONTS.iter().map(PathBuf::from).find(|path| path.is_file())
+}
+
+pub fn load_font(path: &Path) -> Result<Font<'static>> {
+    let data = fs::read(path).with_context(|| format!("could not read font {}", path.display()))?;
+    Font::try_from_vec(data)
+        .with_context(|| format!("{} is not a TrueType or OpenType font", path.display()))
+}
+
+/// Draws every frame of `recording` and encodes them into `path`: an animated GIF looping
+/// forever for a `.gif` file, anything else is left to ffmpeg to encode by its extension.
+/// Videos have a constant frame rate, so frames with their own delays are repeated or
+/// dropped to show for about as long as they should.
+pub fn export(recording: &Recording, path: &Path, opts: &ExportOptions) -> Result<()> {
+    if recording
+        .frames
+        .iter()
+        .any(|frame| frame.contains("\x1BPq") || frame.contains("\x1B_G"))
+    {
+        bail!("Sixel and Kitty frames are already pictures and can't be drawn as text");
+    }
+    let frames: Vec<Vec<Vec<Cell>>> = recording.frames.par_iter().map(|f| cells(f)).collect();
+    let columns = frames.iter().flatten().map(Vec::len).max().unwrap_or(0) as u32;
+    let lines = frames.iter().map(Vec::len).max().unwrap_or(0) as u32;
+    if columns == 0 || lines == 0 {
+        bail!("there are no characters to draw");
+    }
+    let chars: HashSet<char> = frames
+        .iter()
+        .flatten()
+        .flatten()
+        .map(|cell| cell.ch)
+        .collect();
+    let glyphs = Glyphs::new(&opts.font, opts.font_size, chars);
+    let canvas = Canvas {
+        glyphs,
+        columns,
+        lines,
+        theme: opts.theme,
+    };
+
+    let write = || -> Result<()> {
+        if is_gif(&path.to_string_lossy()) {
+            write_gif(recording, &frames, &canvas, path)
+        } else {
+            write_video(recording, &frames, &canvas, path, &opts.ffmpeg)
+        }
+    };
+    write().with_context(|| format!("could not write {}", path.display()))
+}
+
+fn write_gif(
+    recording: &Recording,
+    frames: &[Vec<Vec<Cell>>],
+    canvas: &Canvas,
+    path: &Path,
+) -> Result<()> {
+    let period = Duration::from_secs_f64(1.0 / recording.frame_rate);
+    let mut encoder = GifEncoder::new(BufWriter::new(File::create(path)?));
+    encoder.set_repeat(Repeat::Infinite)?;
+    let mut index = 0;
+    drawn_in_batches(frames, canvas, |image| {
+        let delay = recording
+            .frame_delays
+            .as_ref()
+            .and_then(|delays| delays.get(index))
+            .copied()
+            .unwrap_or(period);
+        index += 1;
+        let image = DynamicImage::ImageRgb8(image).into_rgba8();
+        encoder.encode_frame(Frame::from_parts(
+            image,
+            0,
+            0,
+            Delay::from_saturating_duration(delay),
+        ))?;
+        Ok(())
+    })
+}
+
+fn write_video(
+    recording: &Recording,
+    frames: &[Vec<Vec<Cell>>],
+    canvas: &Canvas,
+    path: &Path,
+    ffmpeg_path: &Path,
+) -> Result<()> {
+    let (width, height) = canvas.size();
+    // ffmpeg -y -loglevel error -f rawvideo -pix_fmt rgb24 -s <W>x<H> -framerate <fps> -i pipe:0
+    //        -vf pad=<even size> -pix_fmt yuv420p <path>
+    let mut ffmpeg = Command::new(ffmpeg_path)
+        .args(vec![
+            "-y",
+            "-loglevel",
+            "error",
+            "-f",
+            "rawvideo",
+            "-pix_fmt",
+            "rgb24",
+            "-s",
+            &format!("{}x{}", width, height),
+            "-framerate",
+            &recording.frame_rate.to_string(),
+            "-i",
+            "pipe:0",
+            // most players only cope with 4:2:0 chroma, which needs an even size
+            "-vf",
+            "pad=ceil(iw/2)*2:ceil(ih/2)*2",
+            "-pix_fmt",
+            "yuv420p",
+        ])
+        .arg(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .with_context(|| {
+            format!(
+                "failed to execute {} - do you have ffmpeg installed?",
+                ffmpeg_path.display()
+            )
+        })?;
+    let mut stdin = ffmpeg.stdin.take().context("ffmpeg has no input pipe")?;
+
+    let repeats = repeats(recording);
+    let mut index = 0;
+    let written = drawn_in_batches(frames, canvas, |image| {
+        for _ in 0..repeats[index] {
+            stdin.write_all(image.as_raw())?;
+        }
+        index += 1;
+        Ok(())
+    });
+    drop(stdin);
+    let status = ffmpeg.wait()?;
+    if !status.success() {
+        bail!("ffmpeg failed to encode the frames ({})", status);
+    }
+    written
+}
+
+/// How many times each frame is written at the constant frame rate of the recording, so
+/// frames with their own delays are shown for as long as they should be, give or take a
+/// frame.
+fn repeats(recording: &Recording) -> Vec<usize> {
+    let delays = match &recording.frame_delays {
+        Some(delays) => delays,
+        None => return vec![1; recording.frames.len()],
+    };
+    let mut shown = Duration::ZERO;
+    let mut written = 0;
+    (0..recording.frames.len())
+        .map(|index| {
+            shown += delays.get(index).copied().unwrap_or_default();
+            let due = (shown.as_secs_f64() * recording.frame_rate).round() as usize;
+            let repeats = due.saturating_sub(written);
+            written += repeats;
+            repeats
+        })
+        .collect()
+}
+
+/// Draws the frames in parallel, a batch at a time so they don't all sit in memory, and
+/// hands the pictures to `write` in order.
+fn drawn_in_batches(
+    frames: &[Vec<Vec<Cell>>],
+    canvas: &Canvas,
+    mut write: impl FnMut(RgbImage) -> Result<()>,
+) -> Result<()> {
+    let batch = rayon::current_num_threads() * 2;
+    for frames in frames.chunks(batch) {
+        let images: Vec<RgbImage> = frames.par_iter().map(|rows| canvas.draw(rows)).collect();
+        for image in images {
+            write(image)?;
+        }
+    }
+    Ok(())
+}
+
+/// A character cell of a rendered frame, with the colors its escapes selected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Cell {
+    ch: char,
+    fg: Option<[u8; 3]>,
+    bg: Option<[u8; 3]>,
+}
+
+/// The rows of cells of a rendered text frame. Escape sequences other than colors, like
+/// cursor moves, are skipped.
+fn cells(frame: &str) -> Vec<Vec<Cell>> {
+    let mut rows = vec![Vec::new()];
+    let (mut fg, mut bg) = (None, None);
+    let mut chars = frame.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\x1B' => {
+                if chars.next() != Some('[') {
+                    continue;
+                }
+                // parameters up to the final byte of the control sequence
+                let mut params = String::new();
+                let end = chars.by_ref().find(|&c| {
+                    let end = ('\x40'..='\x7E').contains(&c);
+                    if !end {
+                        params.push(c);
+                    }
+                    end
+                });
+                if end == Some('m') {
+                    select_colors(&params, &mut fg, &mut bg);
+                }
+            }
+            '\n' => rows.push(Vec::new()),
+            '\r' => {}
+            _ => rows
+                .last_mut()
+                .expect("there is always a row")
+                .push(Cell { ch, fg, bg }),
+        }
+    }
+    if rows.last().is_some_and(Vec::is_empty) {
+        rows.pop();
+    }
+    rows
+}
+
+/// Applies the parameters of an SGR escape sequence to the current colors.
+fn select_colors(params: &str, fg: &mut Option<[u8; 3]>, bg: &mut Option<[u8; 3]>) {
+    let mut params = params
+        .split(';')
+        .map(|param| param.parse::<u8>().unwrap_or(0));
+    while let Some(param) = params.next() {
+        match param {
+            0 => (*fg, *bg) = (None, None),
+            30..=37 => *fg = Some(rgb_from_256(param - 30)),
+            90..=97 => *fg = Some(rgb_from_256(param - 90 + 8)),
+            40..=47 => *bg = Some(rgb_from_256(param - 40)),
+            100..=107 => *bg = Some(rgb_from_256(param - 100 + 8)),
+            38 | 48 => {
+                let color = match params.next() {
+                    Some(5) => params.next().map(rgb_from_256),
+                    Some(2) => match (params.next(), params.next(), params.next()) {
+                        (Some(r), Some(g), Some(b)) => Some([r, g, b]),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                if param == 38 {
+                    *fg = color;
+                } else {
+                    *bg = color;
+                }
+            }
+            39 => *fg = None,
+            49 => *bg = None,
+            _ => {}
+        }
+    }
+}
+
+/// How much of a cell every character covers, as one byte per pixel of the cell.
+struct Glyphs {
+    width: u32,
+    height: u32,
+    coverage: HashMap<char, Vec<u8>>,
+}
+
+impl Glyphs {
+    fn new(font: &Font, size: f32, chars: HashSet<char>) -> Glyphs {
+        let scale = Scale::uniform(size);
+        let metrics = font.v_metrics(scale);
+        let advance = font.glyph('M').scaled(scale).h_metrics().advance_width;
+        let width = advance.ceil().max(1.0) as u32;
+        let height = (metrics.ascent - metrics.descent + metrics.line_gap)
+            .ceil()
+            .max(1.0) as u32;
+
+        let coverage = chars
+            .into_iter()
+            .map(|ch| {
+                let mut coverage = vec![0; (width * height) as usize];
+                // block elements fill the cell exactly, without the font's own margins, so
+                // half blocks tile into a seamless picture
+                let block = match ch {
+                    '█' => Some(0..height),
+                    '▀' => Some(0..height / 2),
+                    '▄' => Some(height / 2..height),
+                    _ => None,
+                };
+                if let Some(rows) = block {
+                    let pixels = rows.start * width..rows.end * width;
+                    coverage[pixels.start as usize..pixels.end as usize].fill(u8::MAX);
+                } else {
+                    let glyph = font
+                        .glyph(ch)
+                        .scaled(scale)
+                        .positioned(point(0.0, metrics.ascent));
+                    if let Some(bounds) = glyph.pixel_bounding_box() {
+                        glyph.draw(|x, y, value| {
+                            let x = x as i32 + bounds.min.x;
+                            let y = y as i32 + bounds.min.y;
+                            if (0..width as i32).contains(&x) && (0..height as i32).contains(&y) {
+                                coverage[(y as u32 * width + x as u32) as usize] =
+                                    (value * 255.0).round() as u8;
+                            }
+                        });
+                    }
+                }
+                (ch, coverage)
+            })
+            .collect();
+        Glyphs {
+            width,
+            height,
+            coverage,
+        }
+    }
+}
+
+/// The grid every frame is drawn on, as large as the largest frame.
+struct Canvas {
+    glyphs: Glyphs,
+    columns: u32,
+    lines: u32,
+    theme: Theme,
+}
+
+impl Canvas {
+    fn size(&self) -> (u32, u32) {
+        (
+            self.columns * self.glyphs.width,
+            self.lines * self.glyphs.height,
+        )
+    }
+
+    fn draw(&self, rows: &[Vec<Cell>]) -> RgbImage {
+        let (width, height) = self.size();
+        let Glyphs {
+            width: cell_width,
+            height: cell_height,
+            coverage,
+        } = &self.glyphs;
+        let mut image = RgbImage::from_pixel(width, height, Rgb(self.theme.background()));
+        for (row, cells) in rows.iter().enumerate() {
+            for (column, cell) in cells.iter().enumerate() {
+                let fg = cell.fg.unwrap_or_else(|| self.theme.foreground());
+                let bg = cell.bg.unwrap_or_else(|| self.theme.background());
+                let coverage = coverage.get(&cell.ch);
+                let (left, top) = (column as u32 * cell_width, row as u32 * cell_height);
+                for y in 0..*cell_height {
+                    for x in 0..*cell_width {
+                        let alpha = coverage.map_or(0, |c| c[(y * cell_width + x) as usize]);
+                        image.put_pixel(left + x, top + y, Rgb(blend(bg, fg, alpha)));
+                    }
+                }
+            }
+        }
+        image
+    }
+}
+
+/// `fg` over `bg` with `alpha` out of 255.
+fn blend(bg: [u8; 3], fg: [u8; 3], alpha: u8) -> [u8; 3] {
+    let alpha = alpha as u32;
+    let mut mixed = [0; 3];
+    for ((mixed, bg), fg) in mixed.iter_mut().zip(bg).zip(fg) {
+        *mixed = ((bg as u32 * (255 - alpha) + fg as u32 * alpha + 127) / 255) as u8;
+    }
+    mixed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cells_follow_color_escapes() {
+        let frame = "\x1B[38;2;1;2;3ma\x1B[48;5;16mb\x1B[0mc\n\x1B[31md\x1B[2J\n";
+        let cells = cells(frame);
+        let cell = |ch, fg, bg| Cell { ch, fg, bg };
+        assert_eq!(
+            cells,
+            vec![
+                vec![
+                    cell('a', Some([1, 2, 3]), None),
+                    cell('b', Some([1, 2, 3]), Some([0, 0, 0])),
+                    cell('c', None, None),
+                ],
+                vec![cell('d', Some([205, 0, 0]), None)],
+            ]
+        );
+    }
+
+    #[test]
+    fn delayed_frames_repeat_at_the_frame_rate() {
+        let recording = Recording {
+            frame_rate: 10.0,
+            size: None,
+            frames: vec![String::new(); 4],
+            frame_delays: Some(
+                [300, 100, 20, 80]
+                    .iter()
+                    .map(|&ms| Duration::from_millis(ms))
+                    .collect(),
+            ),
+        };
+        assert_eq!(repeats(&recording), vec![3, 1, 0, 1]);
+    }
+}
@@ -0,0 +1,1501 @@
+/*
+   This file is part of term-video.
+
+   term-video is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   term-video is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with term-video.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::{
+    cache::{list_frames, AUDIO_FILE},
+    color::LumaMode,
+    parse::parse_frame_rate,
+};
+use anyhow::{anyhow, bail, Context, Result};
+use clap::ValueEnum;
+use image::{imageops::FilterType, DynamicImage, GrayImage, RgbImage};
+use log::{debug, trace, warn};
+use serde::Deserialize;
+use std::{
+    io::{BufRead, BufReader, Read},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdout, Command, Stdio},
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    thread,
+    time::Duration,
+};
+
+/// What ffmpeg is given as input to read the video from its own stdin.
+pub const STDIN: &str = "pipe:0";
+
+// lines of ffmpeg's log kept to explain a failed extraction
+const LOG_TAIL: usize = 5;
+
+// set once a failure to run ffprobe has been reported
+static PROBE_FAILED: AtomicBool = AtomicBool::new(false);
+
+/// How ffmpeg should resize the extracted frames.
+#[derive(Clone, Debug)]
+pub struct ExtractOptions {
+    /// ffmpeg binary to run, looked up in PATH unless it contains a directory
+    pub ffmpeg: PathBuf,
+    /// Size of the extracted frames in pixels
+    pub width: u32,
+    pub height: u32,
+    /// Height to width ratio of the area a single pixel covers in the terminal, set to
+    /// preserve the source aspect ratio
+    pub cell_aspect: Option<f32>,
+    /// Only extract the part of the video between these positions
+    pub start: Option<Duration>,
+    pub end: Option<Duration>,
+    /// Only extract this region of the frames, which is then scaled to the playback area
+    pub crop: Option<Crop>,
+    /// Only extract every this many frames, 1 for all of them
+    pub sample_rate: u32,
+    /// How to turn and mirror the frames, after cropping and before they are scaled
+    pub transform: Transform,
+    /// How pixels are interpolated when scaling the frames down
+    pub scale_filter: ScaleFilter,
+    /// Options passed through to ffmpeg as they are
+    pub ffmpeg_args: FfmpegArgs,
+    /// Synthesize frames in between the source's to reach a higher frame rate
+    pub interpolation: Option<Interpolation>,
+    /// Map HDR frames down to SDR with this curve before anything else is done to them
+    pub tonemap: Option<Tonemap>,
+}
+
+/// Arguments given to ffmpeg on top of the ones term-video passes, for what it has no option
+/// of its own for.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FfmpegArgs {
+    /// Input and global options, given in front of the input, like `-hwaccel auto`
+    pub input: Vec<String>,
+    /// Video filters the decoded frames pass through before they are cropped and resized
+    pub filter: Option<String>,
+}
+
+impl FfmpegArgs {
+    /// Parses arguments given as a single string, split like a shell would split them.
+    /// Filters given with `-vf` or `-filter:v` are taken out, to be joined to the filter
+    /// graph resizing the frames rather than replace it.
+    pub fn parse(s: &str) -> Option<FfmpegArgs> {
+        let mut args = split_args(s)?.into_iter();
+        let mut input = Vec::new();
+        let mut filters = Vec::new();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-vf" | "-filter:v" => filters.push(args.next()?),
+                _ => input.push(arg),
+            }
+        }
+        Some(FfmpegArgs {
+            input,
+            filter: (!filters.is_empty()).then(|| filters.join(",")),
+        })
+    }
+}
+
+/// Splits `s` into words at whitespace, keeping what is in single or double quotes together
+/// and taking characters after a backslash as they are. Fails on an unterminated quote.
+fn split_args(s: &str) -> Option<Vec<String>> {
+    let mut args = Vec::new();
+    let mut arg = None;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => args.extend(arg.take()),
+            '\\' => arg.get_or_insert_with(String::new).push(chars.next()?),
+            '\'' | '"' => {
+                let arg = arg.get_or_insert_with(String::new);
+                loop {
+                    match chars.next()? {
+                        end if end == c => break,
+                        // only double quotes escape within them
+                        '\\' if c == '"' => arg.push(chars.next()?),
+                        inner => arg.push(inner),
+                    }
+                }
+            }
+            c => arg.get_or_insert_with(String::new).push(c),
+        }
+    }
+    args.extend(arg);
+    Some(args)
+}
+
+/// Interpolation used to resize frames, by ffmpeg and in-process alike.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ScaleFilter {
+    /// Sharp without much ringing, keeps fine detail
+    Lanczos,
+    /// Softest, averages neighboring pixels
+    Bilinear,
+    /// No interpolation, every pixel is taken from a single source pixel
+    Neighbor,
+    /// ffmpeg's default, somewhat soft
+    #[default]
+    Bicubic,
+}
+
+impl ScaleFilter {
+    /// Options of ffmpeg's `scale` filter selecting the filter, none for ffmpeg's default.
+    fn flags(self) -> &'static str {
+        match self {
+            ScaleFilter::Lanczos => ":flags=lanczos",
+            ScaleFilter::Bilinear => ":flags=bilinear",
+            ScaleFilter::Neighbor => ":flags=neighbor",
+            ScaleFilter::Bicubic => "",
+        }
+    }
+
+    /// The `image` crate's counterpart of the filter.
+    pub fn filter_type(self) -> FilterType {
+        match self {
+            ScaleFilter::Lanczos => FilterType::Lanczos3,
+            ScaleFilter::Bilinear => FilterType::Triangle,
+            ScaleFilter::Neighbor => FilterType::Nearest,
+            // Catmull-Rom is a bicubic filter, like ffmpeg's default scaler
+            ScaleFilter::Bicubic => FilterType::CatmullRom,
+        }
+    }
+}
+
+/// How in-between frames are made up by [`Interpolation`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum InterpolationMode {
+    /// Motion compensated, objects move smoothly, but slow to compute
+    Motion,
+    /// Cross-fades neighboring frames, fast but motion looks doubled
+    Blend,
+}
+
+/// Frames made up with ffmpeg's `minterpolate` filter to play at a higher frame rate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Interpolation {
+    pub mode: InterpolationMode,
+    /// Frames per second to interpolate up to
+    pub frame_rate: f64,
+}
+
+impl Interpolation {
+    fn filter(&self) -> String {
+        let mode = match self.mode {
+            InterpolationMode::Motion => "mci",
+            InterpolationMode::Blend => "blend",
+        };
+        format!(",minterpolate=fps={}:mi_mode={}", self.frame_rate, mode)
+    }
+}
+
+/// Curve compressing the brightness range of HDR video into SDR's, by ffmpeg's `tonemap` filter.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Tonemap {
+    /// Filmic, keeps contrast in the highlights and darkens the midtones a little
+    #[default]
+    Hable,
+    /// Simple and bright, flattens the highlights
+    Reinhard,
+    /// Leaves the darker tones as they are and only rolls off the highlights
+    Mobius,
+}
+
+impl Tonemap {
+    /// The filters converting PQ or HLG frames to BT.709, each followed by a comma. `zscale`
+    /// makes the light linear so `tonemap` can compress it, in floating point to keep the
+    /// highlights, and then encodes the result for SDR again.
+    fn filter(self) -> String {
+        let curve = match self {
+            Tonemap::Hable => "hable",
+            Tonemap::Reinhard => "reinhard",
+            Tonemap::Mobius => "mobius",
+        };
+        format!(
+            "zscale=t=linear:npl=100,format=gbrpf32le,zscale=p=bt709,\
+             tonemap=tonemap={}:desat=0,zscale=t=bt709:m=bt709:r=tv,format=yuv420p,",
+            curve
+        )
+    }
+}
+
+/// A rectangle of the source frames in pixels, from its top left corner at `x`, `y`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Crop {
+    pub width: u32,
+    pub height: u32,
+    pub x: u32,
+    pub y: u32,
+}
+
+impl Crop {
+    /// Parses a rectangle given as `W:H:X:Y`, like ffmpeg's `crop` filter takes it.
+    pub fn parse(s: &str) -> Option<Crop> {
+        let fields: Vec<u32> = s
+            .split(':')
+            .map(|field| field.trim().parse().ok())
+            .collect::<Option<_>>()?;
+        match fields[..] {
+            [width, height, x, y] if width > 0 && height > 0 => Some(Crop {
+                width,
+                height,
+                x,
+                y,
+            }),
+            _ => None,
+        }
+    }
+
+    /// The size of the part of the rectangle that lies within a `width`x`height` frame.
+    pub fn size_within(&self, width: u32, height: u32) -> (u32, u32) {
+        (
+            self.width.min(width.saturating_sub(self.x)).max(1),
+            self.height.min(height.saturating_sub(self.y)).max(1),
+        )
+    }
+}
+
+/// A clockwise rotation of the frames by a multiple of 90 degrees.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Rotation {
+    #[default]
+    None,
+    Quarter,
+    Half,
+    ThreeQuarters,
+}
+
+impl Rotation {
+    /// The rotation by `degrees`, which has to be 0, 90, 180 or 270.
+    pub fn from_degrees(degrees: u32) -> Option<Rotation> {
+        match degrees {
+            0 => Some(Rotation::None),
+            90 => Some(Rotation::Quarter),
+            180 => Some(Rotation::Half),
+            270 => Some(Rotation::ThreeQuarters),
+            _ => None,
+        }
+    }
+}
+
+/// Turns frames shot sideways upright and mirrors them. The rotation comes first, so the
+/// flips are along the axes of the upright frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Transform {
+    pub rotate: Rotation,
+    /// Mirror left to right
+    pub flip_h: bool,
+    /// Mirror top to bottom
+    pub flip_v: bool,
+}
+
+impl Transform {
+    /// The ffmpeg filters applying the transform, each followed by a comma.
+    pub fn filter(&self) -> String {
+        let mut filter = String::from(match self.rotate {
+            Rotation::None => "",
+            Rotation::Quarter => "transpose=clock,",
+            Rotation::Half => "hflip,vflip,",
+            Rotation::ThreeQuarters => "transpose=cclock,",
+        });
+        if self.flip_h {
+            filter += "hflip,";
+        }
+        if self.flip_v {
+            filter += "vflip,";
+        }
+        filter
+    }
+
+    /// The size a `width`x`height` frame has once transformed, quarter turns swap the axes.
+    pub fn size(&self, width: u32, height: u32) -> (u32, u32) {
+        match self.rotate {
+            Rotation::Quarter | Rotation::ThreeQuarters => (height, width),
+            Rotation::None | Rotation::Half => (width, height),
+        }
+    }
+
+    /// Transforms a decoded frame the same way [`filter`](Self::filter) makes ffmpeg do it.
+    pub fn apply(&self, image: DynamicImage) -> DynamicImage {
+        let image = match self.rotate {
+            Rotation::None => image,
+            Rotation::Quarter => image.rotate90(),
+            Rotation::Half => image.rotate180(),
+            Rotation::ThreeQuarters => image.rotate270(),
+        };
+        let image = if self.flip_h { image.fliph() } else { image };
+        if self.flip_v {
+            image.flipv()
+        } else {
+            image
+        }
+    }
+}
+
+impl ExtractOptions {
+    /// Builds the ffmpeg filter graph resizing frames to the playback area, and with
+    /// `interpolation` making up frames in between after that, which is much cheaper at the
+    /// size of the playback area than at the source's.
+    ///
+    /// Character cells are roughly twice as tall as they are wide (`cell_aspect` ≈ 2.0), so
+    /// when keeping the aspect ratio the source is first squashed vertically by that factor
+    /// and then fit inside `width`x`height`. The resulting frame may be smaller than the
+    /// playback area on one axis; the renderer centers it and pads the remainder with spaces.
+    /// A crop comes first, so it is the cropped region that gets fit, then the frames are
+    /// turned and mirrored. Before all that frames are sampled with `select` so the dropped
+    /// ones aren't scaled for nothing, and tone mapped with `tonemap`, so cropping and scaling
+    /// work on SDR frames. First of all come the filters of `ffmpeg_args`, which see the
+    /// frames as they are decoded.
+    pub fn filter(&self) -> String {
+        let select = if self.sample_rate > 1 {
+            format!("select=not(mod(n\\,{})),", self.sample_rate)
+        } else {
+            String::new()
+        };
+        let crop = self.crop.map_or_else(String::new, |crop| {
+            format!("crop={}:{}:{}:{},", crop.width, crop.height, crop.x, crop.y)
+        });
+        let flags = self.scale_filter.flags();
+        let scale = match self.cell_aspect {
+            Some(aspect) => format!(
+                "scale=iw:ih/{}{},scale={}:{}:force_original_aspect_ratio=decrease{}",
+                aspect, flags, self.width, self.height, flags
+            ),
+            None => format!("scale={}:{}{}", self.width, self.height, flags),
+        };
+        let custom = self
+            .ffmpeg_args
+            .filter
+            .as_ref()
+            .map_or_else(String::new, |filter| format!("{},", filter));
+        let interpolation = self
+            .interpolation
+            .map_or_else(String::new, |interpolation| interpolation.filter());
+        let tonemap = self.tonemap.map_or_else(String::new, Tonemap::filter);
+        custom + &select + &tonemap + &crop + &self.transform.filter() + &scale + &interpolation
+    }
+
+    /// Output options keeping the frames left by sampling as they are, which ffmpeg would
+    /// otherwise duplicate again to fill the gaps at the source's frame rate.
+    pub fn sampling_args(&self) -> &'static [&'static str] {
+        if self.sample_rate > 1 {
+            &["-fps_mode", "passthrough"]
+        } else {
+            &[]
+        }
+    }
+
+    /// How many of `count` source frames are left after sampling.
+    pub fn sampled(&self, count: u64) -> u64 {
+        count.div_ceil(self.sample_rate.max(1) as u64)
+    }
+
+    /// Input options limiting ffmpeg to the trimmed range. Seeking with `-ss` in front of the
+    /// input is fast, and since output timestamps then start at zero the end is given as a
+    /// duration with `-t`.
+    pub fn trim_args(&self) -> Vec<String> {
+        let start = self.start.unwrap_or_default();
+        let mut args = Vec::new();
+        if !start.is_zero() {
+            args.extend([String::from("-ss"), format!("{}", start.as_secs_f64())]);
+        }
+        if let Some(end) = self.end {
+            args.extend([
+                String::from("-t"),
+                format!("{}", end.saturating_sub(start).as_secs_f64()),
+            ]);
+        }
+        args
+    }
+
+    /// Length of the trimmed range of a video `duration` long.
+    pub fn trimmed_duration(&self, duration: Duration) -> Duration {
+        self.end
+            .map_or(duration, |end| end.min(duration))
+            .saturating_sub(self.start.unwrap_or_default())
+    }
+
+    /// The same options with the aspect ratio preserving scale resolved into an exact size
+    /// for a `src_width`x`src_height` source, or stretching to the whole area if the source
+    /// size is unknown.
+    pub fn fitted(&self, source_size: Option<(u32, u32)>) -> ExtractOptions {
+        let (width, height) = match source_size {
+            Some((src_width, src_height)) => self.fit(src_width, src_height),
+            None => (self.width, self.height),
+        };
+        ExtractOptions {
+            width,
+            height,
+            cell_aspect: None,
+            ..self.clone()
+        }
+    }
+
+    /// Size of the part of a `src_width`x`src_height` frame that gets scaled, once cropped
+    /// and turned.
+    pub fn region_size(&self, src_width: u32, src_height: u32) -> (u32, u32) {
+        let (width, height) = match self.crop {
+            Some(crop) => crop.size_within(src_width, src_height),
+            None => (src_width, src_height),
+        };
+        self.transform.size(width, height)
+    }
+
+    /// Size a `src_width`x`src_height` frame ends up with, for resizing frames in-process the
+    /// same way [`filter`](Self::filter) makes ffmpeg resize them.
+    pub fn fit(&self, src_width: u32, src_height: u32) -> (u32, u32) {
+        let (src_width, src_height) = self.region_size(src_width, src_height);
+        match self.cell_aspect {
+            Some(aspect) => {
+                let src_width = src_width.max(1) as f32;
+                let src_height = src_height.max(1) as f32 / aspect;
+                let scale = (self.width as f32 / src_width).min(self.height as f32 / src_height);
+                (
+                    ((src_width * scale).round() as u32).clamp(1, self.width),
+                    ((src_height * scale).round() as u32).clamp(1, self.height),
+                )
+            }
+            None => (self.width, self.height),
+        }
+    }
+}
+
+/// Splits `file_name` into resized PNG frames inside `cache_dir`, returning the frame files
+/// in playback order. Fails if ffmpeg can't be executed.
+pub fn extract_frames(
+    file_name: &str,
+    cache_dir: &str,
+    opts: &ExtractOptions,
+) -> Result<Vec<PathBuf>> {
+    extract_frames_with_progress(file_name, cache_dir, opts, |_| ())
+}
+
+/// Like [`extract_frames`], calling `progress` with the number of frames written so far
+/// whenever ffmpeg reports its progress.
+pub fn extract_frames_with_progress(
+    file_name: &str,
+    cache_dir: &str,
+    opts: &ExtractOptions,
+    progress: impl FnMut(u64),
+) -> Result<Vec<PathBuf>> {
+    let log = write_frames(
+        file_name,
+        cache_dir,
+        opts,
+        &opts.trim_args(),
+        &[],
+        false,
+        progress,
+    )?;
+    let frame_files = list_frames(cache_dir)?;
+    if frame_files.is_empty() {
+        return Err(no_frames(file_name, &log.messages));
+    }
+    Ok(frame_files)
+}
+
+/// Like [`extract_frames_with_progress`], but for variable frame rate videos: the frames are
+/// kept as they are instead of being duplicated and dropped to a constant rate, and returned
+/// along with how long each one is shown, taken from their presentation timestamps. Without
+/// timestamps for every frame there are no delays.
+pub fn extract_frames_timed(
+    file_name: &str,
+    cache_dir: &str,
+    opts: &ExtractOptions,
+    progress: impl FnMut(u64),
+) -> Result<(Vec<PathBuf>, Option<Vec<Duration>>)> {
+    let log = write_frames(
+        file_name,
+        cache_dir,
+        opts,
+        &opts.trim_args(),
+        &[String::from("-fps_mode"), String::from("passthrough")],
+        true,
+        progress,
+    )?;
+    let frame_files = list_frames(cache_dir)?;
+    if frame_files.is_empty() {
+        return Err(no_frames(file_name, &log.messages));
+    }
+    let delays = (log.timestamps.len() == frame_files.len())
+        .then(|| frame_delays(&log.timestamps))
+        .flatten();
+    Ok((frame_files, delays))
+}
+
+/// How long each frame is shown given the presentation timestamps of all of them: until the
+/// next one, the last one as long as the one before it.
+fn frame_delays(timestamps: &[Duration]) -> Option<Vec<Duration>> {
+    let mut delays: Vec<Duration> = timestamps
+        .windows(2)
+        .map(|pair| pair[1].saturating_sub(pair[0]))
+        .collect();
+    delays.push(*delays.last()?);
+    Some(delays)
+}
+
+/// Reads the presentation timestamp off a line the `showinfo` filter logs for every frame.
+fn showinfo_timestamp(line: &str) -> Option<Duration> {
+    let (_, rest) = line.split_once(" pts_time:")?;
+    let seconds: f64 = rest.split_whitespace().next()?.parse().ok()?;
+    Duration::try_from_secs_f64(seconds.max(0.0)).ok()
+}
+
+/// Like [`extract_frames_with_progress`], but splits the frames of a `duration` long video
+/// at `fps` into `jobs` segments extracted by separate ffmpeg processes at the same time.
+/// `progress` gets the number of frames written by all of them.
+///
+/// Every segment seeks to half a frame before its first frame, so rounding can't make it
+/// start a frame early or late, and stops after its share of frames; the last one runs to
+/// the end of the trimmed range instead, in case the frame count estimated from the duration
+/// is short. Frames are numbered from the segment's offset, so the segments line up in the
+/// cache as if a single process had written them. Segments start on sampled frames, so
+/// sampling within each of them picks the same frames as across the whole video. This
+/// assumes a constant frame rate.
+pub fn extract_frames_segmented(
+    file_name: &str,
+    cache_dir: &str,
+    opts: &ExtractOptions,
+    duration: Duration,
+    fps: f64,
+    jobs: usize,
+    progress: impl Fn(u64) + Sync,
+) -> Result<Vec<PathBuf>> {
+    let start = opts.start.unwrap_or_default();
+    let first = (start.as_secs_f64() * fps).round() as u64;
+    let total = (opts.trimmed_duration(duration).as_secs_f64() * fps).round() as u64;
+    // segments are cut in sampled frames, each of which stands for this many source frames
+    let step = opts.sample_rate.max(1) as u64;
+    let total = opts.sampled(total);
+    let jobs = (jobs as u64).clamp(1, total.max(1));
+    let written: Vec<AtomicU64> = (0..jobs).map(|_| AtomicU64::new(0)).collect();
+
+    let logs = thread::scope(|s| {
+        let segments: Vec<_> = (0..jobs)
+            .map(|job| {
+                let (from, to) = (total * job / jobs, total * (job + 1) / jobs);
+                let (written, progress) = (&written, &progress);
+                s.spawn(move || {
+                    let seek = ((first + from * step) as f64 - 0.5).max(0.0) / fps;
+                    let mut input_args = Vec::new();
+                    if seek > 0.0 {
+                        input_args.extend([String::from("-ss"), format!("{}", seek)]);
+                    }
+                    let mut output_args =
+                        vec![String::from("-start_number"), format!("{}", from + 1)];
+                    if job + 1 < jobs {
+                        output_args.extend([String::from("-frames:v"), format!("{}", to - from)]);
+                    } else if let Some(end) = opts.end {
+                        let length = end.as_secs_f64() - seek;
+                        input_args.extend([String::from("-t"), format!("{}", length)]);
+                    }
+
+                    write_frames(
+                        file_name,
+                        cache_dir,
+                        opts,
+                        &input_args,
+                        &output_args,
+                        false,
+                        |n| {
+                            written[job as usize].store(n, Ordering::Relaxed);
+                            progress(written.iter().map(|n| n.load(Ordering::Relaxed)).sum());
+                        },
+                    )
+                })
+            })
+            .collect();
+        segments
+            .into_iter()
+            .map(|segment| segment.join().expect("extraction thread panicked"))
+            .collect::<Result<Vec<_>>>()
+    })?;
+
+    let frame_files = list_frames(cache_dir)?;
+    if frame_files.is_empty() {
+        // every segment fails the same way, the first one tells why
+        let messages = logs.first().map_or(&[][..], |log| &log.messages[..]);
+        return Err(no_frames(file_name, messages));
+    }
+    Ok(frame_files)
+}
+
+/// The error for an extraction without a single frame, with the end of ffmpeg's log.
+fn no_frames(file_name: &str, messages: &[String]) -> anyhow::Error {
+    if messages.is_empty() {
+        anyhow!(
+            "ffmpeg produced no frames from {}, check that it is a video ffmpeg can decode",
+            file_name
+        )
+    } else {
+        anyhow!(
+            "ffmpeg produced no frames from {}{}",
+            file_name,
+            tail(messages)
+        )
+    }
+}
+
+/// ffmpeg's last messages to append to an error, indented below it.
+fn tail(messages: &[String]) -> String {
+    if messages.is_empty() {
+        String::new()
+    } else {
+        format!(", it said:\n  {}", messages.join("\n  "))
+    }
+}
+
+/// What ffmpeg logged while writing frames.
+#[derive(Default)]
+struct Log {
+    /// Presentation timestamps of the frames, only with `showinfo`
+    timestamps: Vec<Duration>,
+    /// The last few other messages
+    messages: Vec<String>,
+}
+
+impl Log {
+    fn read(stderr: impl Read) -> Log {
+        let mut log = Log::default();
+        for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+            trace!("ffmpeg: {}", line);
+            if let Some(timestamp) = showinfo_timestamp(&line) {
+                log.timestamps.push(timestamp);
+            } else if !line.trim().is_empty() {
+                if log.messages.len() == LOG_TAIL {
+                    log.messages.remove(0);
+                }
+                log.messages.push(line.trim_end().to_owned());
+            }
+        }
+        log
+    }
+}
+
+/// Runs ffmpeg writing the frames of `file_name` into `cache_dir` as numbered PNG files, with
+/// `input_args` in front of the input and `output_args` in front of the output. With `timed`
+/// the presentation timestamps of the frames are logged too.
+fn write_frames(
+    file_name: &str,
+    cache_dir: &str,
+    opts: &ExtractOptions,
+    input_args: &[String],
+    output_args: &[String],
+    timed: bool,
+    mut progress: impl FnMut(u64),
+) -> Result<Log> {
+    let mut filter = opts.filter();
+    if timed {
+        filter.push_str(",showinfo");
+    }
+    // ffmpeg [<ffmpeg_args>] [-ss <start>] [-t <length>] -i <file_name> -f image2 -vf <filter>[,showinfo] -progress pipe:1 -nostats [-fps_mode passthrough] [<output_args>] <cache>/frame-%07d.png
+    let mut command = Command::new(&opts.ffmpeg);
+    command
+        .args(&opts.ffmpeg_args.input)
+        .args(input_args)
+        .args(vec![
+            "-i",
+            file_name,
+            "-f",
+            "image2",
+            "-vf",
+            &filter,
+            "-progress",
+            "pipe:1",
+            "-nostats",
+        ])
+        .args(opts.sampling_args())
+        .args(output_args)
+        .arg(format!("{}/frame-%07d.png", cache_dir))
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    debug!("running {:?}", command);
+    let mut ffmpeg = command.spawn().with_context(|| {
+        format!(
+            "failed to execute {} - do you have ffmpeg installed?",
+            opts.ffmpeg.display()
+        )
+    })?;
+
+    // the log on stderr has to be drained alongside the progress on stdout
+    let log = ffmpeg
+        .stderr
+        .take()
+        .map(|stderr| thread::spawn(move || Log::read(stderr)));
+
+    // progress comes in blocks of key=value lines, frame=<n> being the one of interest
+    if let Some(stdout) = ffmpeg.stdout.take() {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            if let Some(frame) = line
+                .strip_prefix("frame=")
+                .and_then(|n| n.trim().parse().ok())
+            {
+                progress(frame);
+            }
+        }
+    }
+    let status = ffmpeg.wait().context("failed to wait for ffmpeg")?;
+    let log = log
+        .and_then(|thread| thread.join().ok())
+        .unwrap_or_default();
+    if !status.success() {
+        bail!(
+            "ffmpeg failed to extract frames from {} ({}){}",
+            file_name,
+            status,
+            tail(&log.messages)
+        );
+    }
+    Ok(log)
+}
+
+/// Raw frames read straight from an ffmpeg pipe, without going through the disk. Dropping
+/// the stream stops ffmpeg.
+pub struct FrameStream {
+    ffmpeg: Child,
+    stdout: ChildStdout,
+    log: Option<thread::JoinHandle<Log>>,
+    file_name: String,
+    width: u32,
+    height: u32,
+    format: PixelFormat,
+}
+
+/// How ffmpeg writes the pixels of streamed frames to the pipe.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// Red, green and blue byte of every pixel
+    Rgb24,
+    /// A single byte of luminosity per pixel, the Y plane video is encoded with
+    Gray,
+}
+
+impl PixelFormat {
+    fn name(self) -> &'static str {
+        match self {
+            PixelFormat::Rgb24 => "rgb24",
+            PixelFormat::Gray => "gray",
+        }
+    }
+
+    fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::Rgb24 => 3,
+            PixelFormat::Gray => 1,
+        }
+    }
+}
+
+/// A frame as read from ffmpeg: `width` pixels per row in `format`, top to bottom without
+/// any padding between the rows.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RawFrame {
+    pub width: u32,
+    pub height: u32,
+    pub format: PixelFormat,
+    pub data: Vec<u8>,
+}
+
+impl RawFrame {
+    /// Reads the next `width`x`height` frame in `format` from `reader`. None at the end of
+    /// the stream, a frame cut off by it included.
+    fn read(reader: &mut impl Read, width: u32, height: u32, format: PixelFormat) -> Option<Self> {
+        let mut data = vec![0; width as usize * height as usize * format.bytes_per_pixel()];
+        // read_exact keeps reading across partial pipe reads
+        reader.read_exact(&mut data).ok()?;
+        Some(RawFrame {
+            width,
+            height,
+            format,
+            data,
+        })
+    }
+
+    pub fn into_image(self) -> DynamicImage {
+        let (width, height) = (self.width, self.height);
+        let image = match self.format {
+            PixelFormat::Rgb24 => {
+                RgbImage::from_raw(width, height, self.data).map(DynamicImage::ImageRgb8)
+            }
+            PixelFormat::Gray => {
+                GrayImage::from_raw(width, height, self.data).map(DynamicImage::ImageLuma8)
+            }
+        };
+        image.expect("frames are read at their exact size")
+    }
+}
+
+impl FrameStream {
+    /// Starts decoding `file_name` into frames of `format`. Frames have to be exactly
+    /// `opts.width`x`opts.height` to be read back from the pipe, so `opts` must not keep the
+    /// aspect ratio; see [`ExtractOptions::fitted`].
+    pub fn open(
+        file_name: &str,
+        opts: &ExtractOptions,
+        format: PixelFormat,
+    ) -> Result<FrameStream> {
+        // ffmpeg [<ffmpeg_args>] [-ss <start>] [-t <length>] -i <file_name> -vf <filter> [-fps_mode passthrough] -f rawvideo -pix_fmt <format> pipe:1
+        let mut command = Command::new(&opts.ffmpeg);
+        command
+            .args(&opts.ffmpeg_args.input)
+            .args(opts.trim_args())
+            .args(vec!["-i", file_name, "-vf", &opts.filter()])
+            .args(opts.sampling_args())
+            .args(vec!["-f", "rawvideo", "-pix_fmt", format.name(), "pipe:1"])
+            // piped video is read straight from our stdin
+            .stdin(if file_name == STDIN {
+                Stdio::inherit()
+            } else {
+                Stdio::null()
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        debug!("running {:?}", command);
+        let mut ffmpeg = command.spawn().with_context(|| {
+            format!(
+                "failed to execute {} - do you have ffmpeg installed?",
+                opts.ffmpeg.display()
+            )
+        })?;
+        let stdout = ffmpeg.stdout.take().context("ffmpeg has no output pipe")?;
+        let log = ffmpeg
+            .stderr
+            .take()
+            .map(|stderr| thread::spawn(move || Log::read(stderr)));
+
+        Ok(FrameStream {
+            ffmpeg,
+            stdout,
+            log,
+            file_name: file_name.to_owned(),
+            width: opts.width,
+            height: opts.height,
+            format,
+        })
+    }
+
+    /// Waits for ffmpeg to exit once the stream has ended, failing with the end of its log
+    /// if it did.
+    pub fn finish(mut self) -> Result<()> {
+        let status = self.ffmpeg.wait().context("failed to wait for ffmpeg")?;
+        let log = self
+            .log
+            .take()
+            .and_then(|thread| thread.join().ok())
+            .unwrap_or_default();
+        if !status.success() {
+            bail!(
+                "ffmpeg failed to read {} ({}){}",
+                self.file_name,
+                status,
+                tail(&log.messages)
+            );
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for FrameStream {
+    type Item = RawFrame;
+
+    fn next(&mut self) -> Option<RawFrame> {
+        RawFrame::read(&mut self.stdout, self.width, self.height, self.format)
+    }
+}
+
+impl Drop for FrameStream {
+    fn drop(&mut self) {
+        let _ = self.ffmpeg.kill();
+        let _ = self.ffmpeg.wait();
+    }
+}
+
+/// Extracts the video's audio track into the cache as a WAV file, returning its path, or
+/// None if there is no audio stream to extract. The track is trimmed like the frames.
+pub fn extract_audio(file_name: &str, cache_dir: &str, opts: &ExtractOptions) -> Option<String> {
+    // ffmpeg [-ss <start>] [-t <length>] -i <file_name> -vn -acodec pcm_s16le <cache>/audio.wav
+    let path = format!("{}/{}", cache_dir, AUDIO_FILE);
+    if Path::new(&path).is_file() {
+        // left over from a previous run with --reuse-cache
+        return Some(path);
+    }
+    let mut command = Command::new(&opts.ffmpeg);
+    command
+        .args(opts.trim_args())
+        .args(vec!["-i", file_name, "-vn", "-acodec", "pcm_s16le", &path])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    debug!("running {:?}", command);
+    let status = command.status();
+
+    match status {
+        Ok(status) if status.success() => Some(path),
+        _ => {
+            warn!("Could not extract an audio track, playing without sound");
+            None
+        }
+    }
+}
+
+/// What ffprobe can tell about the first video stream of a file, anything it couldn't
+/// left out.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VideoInfo {
+    /// Codec name, like `h264`
+    pub codec: Option<String>,
+    /// Width and height in pixels
+    pub dimensions: Option<(u32, u32)>,
+    pub duration: Option<Duration>,
+    /// Frame rate, fractional for rates like NTSC's `30000/1001`
+    pub frame_rate: Option<f64>,
+    /// Frames per second on average over the whole stream
+    pub average_frame_rate: Option<f64>,
+    /// Transfer characteristics the frames are encoded with, like `bt709` or `smpte2084`
+    pub color_transfer: Option<String>,
+    /// Matrix the colors are turned into luminosity and chroma with, like `bt709`
+    pub color_space: Option<String>,
+}
+
+// the parts of `ffprobe -of json` output that are asked for
+#[derive(Deserialize)]
+struct ProbeOutput {
+    #[serde(default)]
+    streams: Vec<ProbeStream>,
+    format: Option<ProbeFormat>,
+}
+
+#[derive(Deserialize)]
+struct ProbeStream {
+    codec_name: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+    r_frame_rate: Option<String>,
+    avg_frame_rate: Option<String>,
+    color_transfer: Option<String>,
+    color_space: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ProbeFormat {
+    // ffprobe prints numbers with their unit as strings
+    duration: Option<String>,
+}
+
+impl VideoInfo {
+    /// Reads the output of the ffprobe call in [`probe_video`].
+    fn from_json(json: &str) -> Option<VideoInfo> {
+        let output: ProbeOutput = serde_json::from_str(json).ok()?;
+        let stream = output.streams.into_iter().next();
+        let stream = stream.as_ref();
+        Some(VideoInfo {
+            codec: stream.and_then(|stream| stream.codec_name.clone()),
+            dimensions: stream
+                .and_then(|stream| stream.width.zip(stream.height))
+                .filter(|&(width, height)| width > 0 && height > 0),
+            duration: output
+                .format
+                .and_then(|format| format.duration)
+                .and_then(|duration| duration.parse().ok())
+                .filter(|duration: &f64| *duration > 0.0)
+                .and_then(|duration| Duration::try_from_secs_f64(duration).ok()),
+            frame_rate: stream
+                .and_then(|stream| stream.r_frame_rate.as_deref())
+                .and_then(parse_frame_rate),
+            average_frame_rate: stream
+                .and_then(|stream| stream.avg_frame_rate.as_deref())
+                .and_then(parse_frame_rate),
+            color_transfer: stream.and_then(|stream| stream.color_transfer.clone()),
+            color_space: stream.and_then(|stream| stream.color_space.clone()),
+        })
+    }
+
+    /// Whether the frames aren't evenly spaced, which shows in an average frame rate off the
+    /// stream's base rate by more than a percent.
+    pub fn is_variable_frame_rate(&self) -> bool {
+        match (self.frame_rate, self.average_frame_rate) {
+            (Some(rate), Some(average)) => (rate - average).abs() > rate * 0.01,
+            _ => false,
+        }
+    }
+
+    /// Whether the frames are HDR, encoded with the PQ (`smpte2084`) or HLG (`arib-std-b67`)
+    /// transfer function, which look washed out unless tone mapped.
+    pub fn is_hdr(&self) -> bool {
+        matches!(
+            self.color_transfer.as_deref(),
+            Some("smpte2084" | "arib-std-b67")
+        )
+    }
+
+    /// How the luminosity of the frames, the Y plane ffmpeg decodes them to, weights the
+    /// color channels: BT.709 for HD video, BT.601 for SD. `None` for matrices ffprobe
+    /// doesn't know, or that weight differently, like BT.2020's.
+    pub fn luma_mode(&self) -> Option<LumaMode> {
+        match self.color_space.as_deref()? {
+            "bt709" => Some(LumaMode::Rec709),
+            "bt470bg" | "smpte170m" => Some(LumaMode::Rec601),
+            _ => None,
+        }
+    }
+}
+
+/// How to get ffmpeg, which comes with ffprobe, on this platform.
+const INSTALL_HINT: &str = if cfg!(target_os = "macos") {
+    "Install it with `brew install ffmpeg`"
+} else if cfg!(windows) {
+    "Install it with `winget install ffmpeg`, or download a build from https://ffmpeg.org/download.html and add its bin folder to PATH"
+} else {
+    "Install the ffmpeg package of your distribution, e.g. `sudo apt install ffmpeg` or `sudo dnf install ffmpeg`"
+};
+
+/// Makes sure `ffmpeg` and `ffprobe` can be run before anything gets written, failing with
+/// a hint on how to install them otherwise.
+pub fn check_dependencies(ffmpeg: &Path, ffprobe: &Path) -> Result<()> {
+    let missing: Vec<String> = [ffmpeg, ffprobe]
+        .iter()
+        .filter(|binary| !runs(binary))
+        .map(|binary| binary.display().to_string())
+        .collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+    bail!(
+        "could not run {}. {}, or point --ffmpeg-path and --ffprobe-path at the binaries",
+        missing.join(" or "),
+        INSTALL_HINT
+    )
+}
+
+/// Whether `binary` answers `-version`, which ffmpeg and ffprobe both understand.
+fn runs(binary: &Path) -> bool {
+    Command::new(binary)
+        .arg("-version")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Whether `ffmpeg` was built with the filter `name`, which for some like `zscale` depends on
+/// the libraries it was compiled against.
+pub fn has_filter(ffmpeg: &Path, name: &str) -> bool {
+    Command::new(ffmpeg)
+        .args(["-hide_banner", "-filters"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .is_ok_and(|out| {
+            // lines are like ` T.C zscale            V->V       Apply resizing, ...`
+            String::from_utf8_lossy(&out.stdout)
+                .lines()
+                .any(|line| line.split_whitespace().nth(1) == Some(name))
+        })
+}
+
+/// Asks ffprobe about the codec, size, duration and frame rate of the video in one go.
+/// Everything is unknown if ffprobe can't be run or doesn't understand the file.
+pub fn probe_video(ffprobe: &Path, video: &str) -> VideoInfo {
+    // ffprobe -v error -select_streams v:0 -show_entries <entries> -of json <video>
+    let out = Command::new(ffprobe)
+        .args(vec![
+            "-v",
+            "error",
+            "-select_streams",
+            "v:0",
+            "-show_entries",
+            "stream=codec_name,width,height,r_frame_rate,avg_frame_rate,color_transfer,color_space:format=duration",
+            "-of",
+            "json",
+            video,
+        ])
+        .output()
+        .map_err(|e| {
+            if !PROBE_FAILED.swap(true, Ordering::SeqCst) {
+                warn!("Failed to execute {}: {}", ffprobe.display(), e);
+            }
+        });
+    let info = out
+        .ok()
+        .and_then(|out| VideoInfo::from_json(&String::from_utf8_lossy(&out.stdout)))
+        .unwrap_or_default();
+    debug!("probed {}: {:?}", video, info);
+    info
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crop_comes_before_scaling() {
+        let crop = Crop::parse("1080:1080:420:0").unwrap();
+        assert_eq!(
+            crop,
+            Crop {
+                width: 1080,
+                height: 1080,
+                x: 420,
+                y: 0
+            }
+        );
+        for invalid in ["1080:1080:420", "0:1080:0:0", "a:b:c:d", "1:2:3:4:5", ""] {
+            assert_eq!(Crop::parse(invalid), None, "{}", invalid);
+        }
+
+        let opts = ExtractOptions {
+            ffmpeg: PathBuf::from("ffmpeg"),
+            width: 40,
+            height: 40,
+            cell_aspect: Some(1.0),
+            start: None,
+            end: None,
+            crop: Some(crop),
+            sample_rate: 1,
+            transform: Transform::default(),
+            scale_filter: ScaleFilter::Bicubic,
+            ffmpeg_args: FfmpegArgs::default(),
+            interpolation: None,
+            tonemap: None,
+        };
+        assert_eq!(
+            opts.filter(),
+            "crop=1080:1080:420:0,scale=iw:ih/1,scale=40:40:force_original_aspect_ratio=decrease"
+        );
+        // the square region of a 16:9 source fills the square area
+        assert_eq!(opts.fit(1920, 1080), (40, 40));
+        assert_eq!(
+            ExtractOptions { crop: None, ..opts }.fit(1920, 1080),
+            (40, 23)
+        );
+    }
+
+    #[test]
+    fn keep_aspect_fits_within_the_playback_area() {
+        let opts = ExtractOptions {
+            ffmpeg: PathBuf::from("ffmpeg"),
+            width: 64,
+            height: 24,
+            cell_aspect: Some(2.0),
+            start: None,
+            end: None,
+            crop: None,
+            sample_rate: 1,
+            transform: Transform::default(),
+            scale_filter: ScaleFilter::Bicubic,
+            ffmpeg_args: FfmpegArgs::default(),
+            interpolation: None,
+            tonemap: None,
+        };
+        assert_eq!(
+            opts.filter(),
+            "scale=iw:ih/2,scale=64:24:force_original_aspect_ratio=decrease"
+        );
+        // cells twice as high as wide halve the rows, landscape fills the width
+        assert_eq!(opts.fit(1280, 720), (64, 18));
+        // and portrait the height
+        assert_eq!(opts.fit(720, 1280), (27, 24));
+        // however thin the source, no axis is empty
+        assert_eq!(opts.fit(10000, 1), (64, 1));
+
+        // without it the frames are stretched over the whole area
+        let stretched = ExtractOptions {
+            cell_aspect: None,
+            ..opts
+        };
+        assert_eq!(stretched.filter(), "scale=64:24");
+        assert_eq!(stretched.fit(720, 1280), (64, 24));
+    }
+
+    #[test]
+    fn sampling_selects_frames_first() {
+        let opts = ExtractOptions {
+            ffmpeg: PathBuf::from("ffmpeg"),
+            width: 40,
+            height: 20,
+            cell_aspect: None,
+            start: None,
+            end: None,
+            crop: None,
+            sample_rate: 3,
+            transform: Transform::default(),
+            scale_filter: ScaleFilter::Bicubic,
+            ffmpeg_args: FfmpegArgs::default(),
+            interpolation: None,
+            tonemap: None,
+        };
+        assert_eq!(opts.filter(), "select=not(mod(n\\,3)),scale=40:20");
+        assert_eq!(opts.sampling_args(), ["-fps_mode", "passthrough"]);
+        assert_eq!((opts.sampled(9), opts.sampled(10)), (3, 4));
+
+        let opts = ExtractOptions {
+            sample_rate: 1,
+            ..opts
+        };
+        assert_eq!(opts.filter(), "scale=40:20");
+        assert!(opts.sampling_args().is_empty());
+
+        // only a filter other than ffmpeg's default is given
+        let opts = ExtractOptions {
+            scale_filter: ScaleFilter::Neighbor,
+            ..opts
+        };
+        assert_eq!(opts.filter(), "scale=40:20:flags=neighbor");
+        assert_eq!(opts.sampled(10), 10);
+    }
+
+    #[test]
+    fn quarter_turns_swap_the_axes() {
+        assert_eq!(Rotation::from_degrees(270), Some(Rotation::ThreeQuarters));
+        for invalid in [45, 360, 1] {
+            assert_eq!(Rotation::from_degrees(invalid), None, "{}", invalid);
+        }
+
+        let opts = ExtractOptions {
+            ffmpeg: PathBuf::from("ffmpeg"),
+            width: 40,
+            height: 40,
+            cell_aspect: Some(1.0),
+            start: None,
+            end: None,
+            crop: Some(Crop::parse("1000:500:0:0").unwrap()),
+            sample_rate: 1,
+            transform: Transform {
+                rotate: Rotation::Quarter,
+                flip_h: true,
+                flip_v: false,
+            },
+            scale_filter: ScaleFilter::Bicubic,
+            ffmpeg_args: FfmpegArgs::default(),
+            interpolation: None,
+            tonemap: None,
+        };
+        assert_eq!(
+            opts.filter(),
+            "crop=1000:500:0:0,transpose=clock,hflip,scale=iw:ih/1,scale=40:40:force_original_aspect_ratio=decrease"
+        );
+        // the wide crop stands upright
+        assert_eq!(opts.region_size(1920, 1080), (500, 1000));
+        assert_eq!(opts.fit(1920, 1080), (20, 40));
+
+        // in-process the frame turns the same way
+        let mut image = RgbImage::new(2, 1);
+        image.put_pixel(1, 0, image::Rgb([255, 255, 255]));
+        let turned = opts
+            .transform
+            .apply(DynamicImage::ImageRgb8(image))
+            .into_rgb8();
+        assert_eq!(turned.dimensions(), (1, 2));
+        assert_eq!(turned.get_pixel(0, 1).0, [255, 255, 255]);
+    }
+
+    #[test]
+    fn ffmpeg_args_filters_run_first() {
+        assert_eq!(
+            split_args(r#" -hwaccel  auto -metadata 'title=a b' "x\"y" a\ b"#).unwrap(),
+            ["-hwaccel", "auto", "-metadata", "title=a b", "x\"y", "a b"]
+        );
+        assert_eq!(split_args("''").unwrap(), [""]);
+        assert_eq!(split_args("'open"), None);
+
+        let args = FfmpegArgs::parse("-hwaccel auto -vf yadif -filter:v 'eq=gamma=1.2'").unwrap();
+        assert_eq!(args.input, ["-hwaccel", "auto"]);
+        assert_eq!(args.filter.as_deref(), Some("yadif,eq=gamma=1.2"));
+        assert_eq!(FfmpegArgs::parse("-vf"), None);
+
+        let opts = ExtractOptions {
+            ffmpeg: PathBuf::from("ffmpeg"),
+            width: 40,
+            height: 20,
+            cell_aspect: None,
+            start: None,
+            end: None,
+            crop: None,
+            sample_rate: 2,
+            transform: Transform::default(),
+            scale_filter: ScaleFilter::Bicubic,
+            ffmpeg_args: args,
+            interpolation: None,
+            tonemap: None,
+        };
+        assert_eq!(
+            opts.filter(),
+            "yadif,eq=gamma=1.2,select=not(mod(n\\,2)),scale=40:20"
+        );
+    }
+
+    #[test]
+    fn interpolation_runs_after_scaling() {
+        let opts = ExtractOptions {
+            ffmpeg: PathBuf::from("ffmpeg"),
+            width: 40,
+            height: 20,
+            cell_aspect: None,
+            start: None,
+            end: None,
+            crop: None,
+            sample_rate: 1,
+            transform: Transform::default(),
+            scale_filter: ScaleFilter::Bicubic,
+            ffmpeg_args: FfmpegArgs::default(),
+            interpolation: Some(Interpolation {
+                mode: InterpolationMode::Blend,
+                frame_rate: 59.94,
+            }),
+            tonemap: None,
+        };
+        assert_eq!(
+            opts.filter(),
+            "scale=40:20,minterpolate=fps=59.94:mi_mode=blend"
+        );
+    }
+
+    #[test]
+    fn tone_mapping_comes_before_cropping() {
+        let opts = ExtractOptions {
+            ffmpeg: PathBuf::from("ffmpeg"),
+            width: 40,
+            height: 20,
+            cell_aspect: None,
+            start: None,
+            end: None,
+            crop: Crop::parse("100:100:0:0"),
+            sample_rate: 1,
+            transform: Transform::default(),
+            scale_filter: ScaleFilter::Bicubic,
+            ffmpeg_args: FfmpegArgs::default(),
+            interpolation: None,
+            tonemap: Some(Tonemap::Mobius),
+        };
+        assert_eq!(
+            opts.filter(),
+            "zscale=t=linear:npl=100,format=gbrpf32le,zscale=p=bt709,\
+             tonemap=tonemap=mobius:desat=0,zscale=t=bt709:m=bt709:r=tv,format=yuv420p,\
+             crop=100:100:0:0,scale=40:20"
+        );
+    }
+
+    #[test]
+    fn raw_frames_are_read_row_by_row_without_padding() {
+        // a 3x2 pattern whose every byte tells where it belongs, followed by a cut off frame
+        let gray = [0x00, 0x01, 0x02, 0x10, 0x11, 0x12, 0xFF];
+        let mut reader = &gray[..];
+        let frame = RawFrame::read(&mut reader, 3, 2, PixelFormat::Gray).unwrap();
+        assert_eq!(frame.data, &gray[..6]);
+        let image = frame.into_image().to_luma8();
+        assert_eq!(image.get_pixel(2, 0).0, [0x02]);
+        assert_eq!(image.get_pixel(0, 1).0, [0x10]);
+        assert_eq!(RawFrame::read(&mut reader, 3, 2, PixelFormat::Gray), None);
+
+        let rgb: Vec<u8> = (0..2 * 2 * 3).collect();
+        let frame = RawFrame::read(&mut &rgb[..], 2, 2, PixelFormat::Rgb24).unwrap();
+        let image = frame.into_image().to_rgb8();
+        assert_eq!(image.get_pixel(1, 0).0, [3, 4, 5]);
+        assert_eq!(image.get_pixel(0, 1).0, [6, 7, 8]);
+    }
+
+    #[test]
+    fn video_info_reads_ffprobe_json() {
+        let json = r#"{
+            "programs": [],
+            "streams": [
+                {
+                    "codec_name": "h264",
+                    "width": 1920,
+                    "height": 1080,
+                    "r_frame_rate": "30000/1001",
+                    "avg_frame_rate": "30000/1001",
+                    "color_transfer": "smpte2084",
+                    "color_space": "bt2020nc"
+                }
+            ],
+            "format": {
+                "duration": "12.345000"
+            }
+        }"#;
+        assert_eq!(
+            VideoInfo::from_json(json),
+            Some(VideoInfo {
+                codec: Some(String::from("h264")),
+                dimensions: Some((1920, 1080)),
+                duration: Some(Duration::from_secs_f64(12.345)),
+                frame_rate: Some(30000.0 / 1001.0),
+                average_frame_rate: Some(30000.0 / 1001.0),
+                color_transfer: Some(String::from("smpte2084")),
+                color_space: Some(String::from("bt2020nc")),
+            })
+        );
+        assert!(VideoInfo::from_json(json).unwrap().is_hdr());
+    }
+
+    #[test]
+    fn variable_frame_rates_get_delays_from_timestamps() {
+        let info = VideoInfo {
+            frame_rate: Some(60.0),
+            average_frame_rate: Some(27.5),
+            ..VideoInfo::default()
+        };
+        assert!(info.is_variable_frame_rate());
+        assert!(!VideoInfo {
+            average_frame_rate: Some(59.9),
+            ..info
+        }
+        .is_variable_frame_rate());
+
+        let line =
+            "[Parsed_showinfo_1 @ 0x55d0] n:   2 pts:   6006 pts_time:0.0667333 duration:3003";
+        assert_eq!(
+            showinfo_timestamp(line),
+            Some(Duration::from_secs_f64(0.0667333))
+        );
+        assert_eq!(showinfo_timestamp("[info] n: 2 pts: 6006"), None);
+        for broken in ["pts_time:inf", "pts_time:1e300"] {
+            assert_eq!(showinfo_timestamp(&format!("[info] n: 2 {}", broken)), None);
+        }
+
+        let millis = |ms: u64| Duration::from_millis(ms);
+        assert_eq!(
+            frame_delays(&[millis(0), millis(40), millis(60), millis(160)]),
+            Some(vec![millis(40), millis(20), millis(100), millis(100)])
+        );
+        assert_eq!(frame_delays(&[millis(0)]), None);
+    }
+
+    #[test]
+    fn video_info_leaves_out_what_is_missing() {
+        // streams without a frame rate, like cover art, and formats of unknown length
+        let json = r#"{"streams": [{"codec_name": "png", "r_frame_rate": "0/0"}], "format": {}}"#;
+        assert_eq!(
+            VideoInfo::from_json(json),
+            Some(VideoInfo {
+                codec: Some(String::from("png")),
+                ..VideoInfo::default()
+            })
+        );
+        assert_eq!(VideoInfo::from_json("{}"), Some(VideoInfo::default()));
+    }
+
+    #[test]
+    fn luma_mode_follows_the_color_matrix() {
+        let matrix = |color_space: Option<&str>| {
+            VideoInfo {
+                color_space: color_space.map(String::from),
+                ..VideoInfo::default()
+            }
+            .luma_mode()
+        };
+        assert_eq!(matrix(Some("bt709")), Some(LumaMode::Rec709));
+        assert_eq!(matrix(Some("smpte170m")), Some(LumaMode::Rec601));
+        assert_eq!(matrix(Some("bt470bg")), Some(LumaMode::Rec601));
+        assert_eq!(matrix(Some("bt2020nc")), None);
+        assert_eq!(matrix(Some("unknown")), None);
+        assert_eq!(matrix(None), None);
+    }
+}
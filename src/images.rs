@@ -0,0 +1,332 @@
+/*
+   This file is part of term-video.
+
+   term-video is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   term-video is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with term-video.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Frame sources decoded in-process with the `image` crate, no ffmpeg involved.
+
+use crate::ffmpeg::ExtractOptions;
+use anyhow::{Context, Result};
+use image::{
+    codecs::gif::GifDecoder, imageops, AnimationDecoder, DynamicImage, Frame, GenericImageView,
+    ImageFormat,
+};
+use log::warn;
+use rayon::prelude::*;
+use std::{
+    fs::File,
+    io::BufReader,
+    ops::Range,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use walkdir::WalkDir;
+
+/// Whether `input` is a folder of images to play as frames.
+pub fn is_image_sequence(input: &str) -> bool {
+    Path::new(input).is_dir()
+}
+
+/// Whether `input` looks like a GIF the image crate can decode by itself.
+pub fn is_gif(input: &str) -> bool {
+    Path::new(input)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("gif"))
+}
+
+/// Decodes an animated GIF and saves its resized frames into `cache_dir`, named the same way
+/// ffmpeg would name them. Returns the frame files along with how long each one is shown.
+pub fn extract_gif_frames(
+    file_name: &str,
+    cache_dir: &str,
+    opts: &ExtractOptions,
+) -> Result<(Vec<PathBuf>, Vec<Duration>)> {
+    let mut frame_files = Vec::new();
+    let delays = decode_gif(file_name, opts, |frame| {
+        let mut buffer = frame.into_buffer();
+        // the size is fit to the crop within the whole frame
+        let (width, height) = opts.fit(buffer.width(), buffer.height());
+        if let Some(crop) = opts.crop {
+            buffer =
+                imageops::crop_imm(&buffer, crop.x, crop.y, crop.width, crop.height).to_image();
+        }
+        let buffer = opts
+            .transform
+            .apply(DynamicImage::ImageRgba8(buffer))
+            .into_rgba8();
+        let resized = imageops::resize(&buffer, width, height, opts.scale_filter.filter_type());
+
+        let path = Path::new(cache_dir).join(format!("frame-{:07}.png", frame_files.len() + 1));
+        resized
+            .save(&path)
+            .with_context(|| format!("could not write {}", path.display()))?;
+        frame_files.push(path);
+        Ok(())
+    })?;
+
+    Ok((frame_files, delays))
+}
+
+/// How long each frame of the trimmed GIF is shown, without saving any frames.
+pub fn gif_delays(file_name: &str, opts: &ExtractOptions) -> Result<Vec<Duration>> {
+    decode_gif(file_name, opts, |_| Ok(()))
+}
+
+/// Decodes the frames of a GIF within the trimmed range, handing each sampled one to `each`
+/// and returning their delays. A sampled frame is shown for as long as the frames dropped
+/// after it together, so the GIF plays as long as before.
+fn decode_gif(
+    file_name: &str,
+    opts: &ExtractOptions,
+    mut each: impl FnMut(Frame) -> Result<()>,
+) -> Result<Vec<Duration>> {
+    let file = File::open(file_name).with_context(|| format!("could not open {}", file_name))?;
+    let decoder = GifDecoder::new(BufReader::new(file))
+        .with_context(|| format!("could not read {} as a GIF", file_name))?;
+
+    let mut delays = Vec::new();
+    let mut position = Duration::ZERO;
+    let mut kept = 0;
+    for (index, frame) in decoder.into_frames().enumerate() {
+        let frame = frame
+            .with_context(|| format!("could not decode frame {} of {}", index + 1, file_name))?;
+        let (numer, denom) = frame.delay().numer_denom_ms();
+        let delay = gif_delay(numer, denom);
+
+        // trimming works on whole frames, those overlapping the range are kept
+        let shown_at = position;
+        position += delay;
+        if opts.start.is_some_and(|start| position <= start) {
+            continue;
+        }
+        if opts.end.is_some_and(|end| shown_at >= end) {
+            break;
+        }
+        if kept % opts.sample_rate.max(1) as usize == 0 {
+            delays.push(delay);
+            each(frame)?;
+        } else if let Some(last) = delays.last_mut() {
+            *last += delay;
+        }
+        kept += 1;
+    }
+
+    Ok(delays)
+}
+
+/// The image files directly inside `dir`, sorted by name, leaving out files of formats the
+/// image crate doesn't know with a warning. Names should be zero-padded like `0001.png` to
+/// sort in order.
+pub fn list_images(dir: &str) -> Result<Vec<PathBuf>> {
+    let mut images = Vec::new();
+    for entry in WalkDir::new(dir)
+        .min_depth(1)
+        .max_depth(1)
+        .sort_by_file_name()
+    {
+        let entry = entry.with_context(|| format!("could not read directory {}", dir))?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let path = entry.into_path();
+        if ImageFormat::from_path(&path).is_ok() {
+            images.push(path);
+        } else {
+            warn!("Skipping {}: not an image", path.display());
+        }
+    }
+    Ok(images)
+}
+
+/// Resizes the images in the folder `dir` into frames inside `cache_dir`, named the same way
+/// ffmpeg would name them, in parallel. The images follow each other at `fps`, which decides
+/// where the trimmed range starts and ends before they are sampled. Images that can't be
+/// decoded are skipped with a warning.
+pub fn extract_image_frames(
+    dir: &str,
+    cache_dir: &str,
+    opts: &ExtractOptions,
+    fps: f64,
+) -> Result<Vec<PathBuf>> {
+    let images = list_images(dir)?;
+    let frames: Vec<Option<PathBuf>> = images[trimmed_images(images.len(), opts, fps)]
+        .par_iter()
+        .step_by(opts.sample_rate.max(1) as usize)
+        .enumerate()
+        .map(|(index, image)| {
+            let path = Path::new(cache_dir).join(format!("frame-{:07}.png", index + 1));
+            let resize = || -> Result<()> {
+                let mut image = image::open(image)?;
+                let (width, height) = opts.fit(image.width(), image.height());
+                if let Some(crop) = opts.crop {
+                    image = image.crop_imm(crop.x, crop.y, crop.width, crop.height);
+                }
+                image = opts.transform.apply(image);
+                image
+                    .resize_exact(width, height, opts.scale_filter.filter_type())
+                    .save(&path)?;
+                Ok(())
+            };
+            match resize() {
+                Ok(()) => Some(path),
+                Err(e) => {
+                    warn!("Skipping {}: {:#}", image.display(), e);
+                    None
+                }
+            }
+        })
+        .collect();
+    Ok(frames.into_iter().flatten().collect())
+}
+
+/// The range of a folder of `count` images, played at `fps`, that --start and --end keep.
+pub fn trimmed_images(count: usize, opts: &ExtractOptions, fps: f64) -> Range<usize> {
+    let index_at = |time: Duration| (time.as_secs_f64() * fps).round() as usize;
+    let first = opts.start.map_or(0, index_at).min(count);
+    let last = opts.end.map_or(count, index_at).clamp(first, count);
+    first..last
+}
+
+/// Browsers show frames with delays below 20ms for 100ms instead, and plenty of GIFs rely on
+/// that, so do the same.
+fn gif_delay(numer: u32, denom: u32) -> Duration {
+    let millis = numer as f64 / denom.max(1) as f64;
+    if millis < 20.0 {
+        Duration::from_millis(100)
+    } else {
+        Duration::from_secs_f64(millis / 1000.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ffmpeg::{FfmpegArgs, ScaleFilter, Transform};
+    use image::{codecs::gif::GifEncoder, Delay, RgbImage, RgbaImage};
+    use std::{env, fs, process};
+
+    fn extract_options() -> ExtractOptions {
+        ExtractOptions {
+            ffmpeg: PathBuf::from("ffmpeg"),
+            width: 2,
+            height: 1,
+            cell_aspect: None,
+            start: None,
+            end: None,
+            crop: None,
+            sample_rate: 1,
+            transform: Transform::default(),
+            scale_filter: ScaleFilter::Bicubic,
+            ffmpeg_args: FfmpegArgs::default(),
+            interpolation: None,
+            tonemap: None,
+        }
+    }
+
+    #[test]
+    fn images_are_played_in_name_order() {
+        let dir = env::temp_dir().join(format!("term-video-test-{}-list", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("0000.png")).unwrap();
+        for name in ["0010.png", "0002.jpg", "notes.txt", "0001.png"] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+        let images = list_images(dir.to_str().unwrap());
+        let _ = fs::remove_dir_all(&dir);
+
+        // neither the text file nor the folder named like an image are frames
+        let names: Vec<_> = images
+            .unwrap()
+            .iter()
+            .map(|path| path.file_name().unwrap().to_str().unwrap().to_owned())
+            .collect();
+        assert_eq!(names, ["0001.png", "0002.jpg", "0010.png"]);
+    }
+
+    #[test]
+    fn unreadable_images_are_skipped() {
+        let dir = env::temp_dir().join(format!("term-video-test-{}-images", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let cache = dir.join("cache");
+        fs::create_dir_all(&cache).unwrap();
+        RgbImage::new(8, 4).save(dir.join("0001.png")).unwrap();
+        fs::write(dir.join("0002.png"), "not a png").unwrap();
+        RgbImage::new(8, 4).save(dir.join("0003.png")).unwrap();
+        let frames = extract_image_frames(
+            dir.to_str().unwrap(),
+            cache.to_str().unwrap(),
+            &extract_options(),
+            24.0,
+        );
+        let sizes: Vec<_> = frames
+            .iter()
+            .flatten()
+            .map(|frame| image::image_dimensions(frame).ok())
+            .collect();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(
+            frames.unwrap(),
+            [
+                cache.join("frame-0000001.png"),
+                cache.join("frame-0000003.png")
+            ]
+        );
+        assert_eq!(sizes, [Some((2, 1)), Some((2, 1))]);
+    }
+
+    #[test]
+    fn gif_frames_keep_their_delays() {
+        let dir = env::temp_dir().join(format!("term-video-test-{}-gif", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let gif = dir.join("clip.gif");
+        let mut encoded = Vec::new();
+        {
+            let mut encoder = GifEncoder::new(&mut encoded);
+            for millis in [50, 10, 200] {
+                let delay = Delay::from_numer_denom_ms(millis, 1);
+                encoder
+                    .encode_frame(Frame::from_parts(RgbaImage::new(8, 4), 0, 0, delay))
+                    .unwrap();
+            }
+        }
+        fs::write(&gif, encoded).unwrap();
+        let gif = gif.to_str().unwrap();
+        let opts = extract_options();
+        let extracted = extract_gif_frames(gif, dir.to_str().unwrap(), &opts);
+        let sizes: Vec<_> = extracted
+            .iter()
+            .flat_map(|(frames, _)| frames)
+            .map(|frame| image::image_dimensions(frame).ok())
+            .collect();
+        let sampled = gif_delays(
+            gif,
+            &ExtractOptions {
+                sample_rate: 2,
+                ..opts
+            },
+        );
+        let _ = fs::remove_dir_all(&dir);
+
+        // the 10ms frame is shown for 100ms, like browsers do
+        let ms = Duration::from_millis;
+        let (_, delays) = extracted.unwrap();
+        assert_eq!(delays, [ms(50), ms(100), ms(200)]);
+        assert_eq!(sizes, [Some((2, 1)); 3]);
+        // the frame dropped by sampling adds to the one before it
+        assert_eq!(sampled.unwrap(), [ms(150), ms(200)]);
+    }
+}
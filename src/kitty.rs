@@ -0,0 +1,111 @@
+/*
+   This file is part of term-video.
+
+   term-video is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   term-video is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with term-video.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! The Kitty graphics protocol, which takes whole PNG images over `\x1B_G` escapes.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use image::{
+    codecs::png::{CompressionType, FilterType, PngEncoder},
+    ColorType, RgbImage,
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Image and placement id shared by every frame.
+const IMAGE_ID: u32 = 1;
+// the protocol caps every escape at 4096 bytes of payload
+const CHUNK_SIZE: usize = 4096;
+
+// set once a frame has been encoded or played, so there is an image to delete on exit
+static TRANSMITTED: AtomicBool = AtomicBool::new(false);
+
+/// Encodes `image` as escapes transmitting and displaying it at the cursor, scaled to cover
+/// `columns`x`rows` cells. The cursor doesn't move, so an image touching the bottom row
+/// doesn't scroll the terminal.
+///
+/// All frames use the same image and placement id: transmitting a frame replaces the
+/// previous one instead of piling up images in the terminal's memory.
+pub fn encode(image: &RgbImage, columns: u32, rows: u32) -> String {
+    let mut png = Vec::new();
+    // playback needs to keep up more than the frames need to be small
+    PngEncoder::new_with_quality(&mut png, CompressionType::Fast, FilterType::Sub)
+        .encode(image, image.width(), image.height(), ColorType::Rgb8)
+        .expect("encoding a PNG in memory can't fail");
+    TRANSMITTED.store(true, Ordering::SeqCst);
+
+    let data = STANDARD.encode(png);
+    let chunks: Vec<&[u8]> = data.as_bytes().chunks(CHUNK_SIZE).collect();
+    let mut out = String::with_capacity(data.len() + chunks.len() * 16 + 64);
+    for (i, chunk) in chunks.iter().enumerate() {
+        out.push_str("\x1B_G");
+        if i == 0 {
+            // q=2 keeps the terminal from answering on stdin, C=1 from moving the cursor
+            out.push_str(&format!(
+                "a=T,f=100,i={id},p={id},q=2,C=1,c={},r={},",
+                columns,
+                rows,
+                id = IMAGE_ID
+            ));
+        }
+        let more = i + 1 < chunks.len();
+        out.push_str(if more { "m=1;" } else { "m=0;" });
+        // base64 is plain ASCII
+        out.push_str(std::str::from_utf8(chunk).unwrap());
+        out.push_str("\x1B\\");
+    }
+    out
+}
+
+/// Whether `frame` shows an image over the Kitty graphics protocol.
+pub fn is_kitty_frame(frame: &str) -> bool {
+    frame.contains("\x1B_G")
+}
+
+/// Notes that frames encoded by an earlier run, like the ones of a recording, are about to
+/// be shown, so [`cleanup`] frees their image as well.
+pub fn mark_transmitted() {
+    TRANSMITTED.store(true, Ordering::SeqCst);
+}
+
+/// The escape freeing the image in the terminal, if any frame was encoded or played.
+pub fn cleanup() -> Option<String> {
+    TRANSMITTED
+        .swap(false, Ordering::SeqCst)
+        .then(|| format!("\x1B_Ga=d,d=I,i={},q=2\x1B\\", IMAGE_ID))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn large_frames_are_split_into_chunks() {
+        // noise doesn't compress, so the PNG needs several chunks
+        let image = RgbImage::from_fn(64, 64, |x, y| {
+            let n = (x * 7919 + y * 104729) ^ (x * y * 31);
+            image::Rgb([n as u8, (n >> 8) as u8, (n >> 16) as u8])
+        });
+        let out = encode(&image, 8, 4);
+        let escapes: Vec<&str> = out.split_terminator("\x1B\\").collect();
+
+        assert!(escapes.len() > 1);
+        assert!(escapes[0].starts_with("\x1B_Ga=T,f=100,i=1,p=1,q=2,C=1,c=8,r=4,m=1;"));
+        assert!(escapes[1..escapes.len() - 1]
+            .iter()
+            .all(|escape| escape.starts_with("\x1B_Gm=1;")));
+        assert!(escapes.last().unwrap().starts_with("\x1B_Gm=0;"));
+    }
+}
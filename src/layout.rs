@@ -0,0 +1,281 @@
+/*
+   This file is part of term-video.
+
+   term-video is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   term-video is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with term-video.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! The size of the playback area in characters, worked out from the terminal, the size asked
+//! for on the command line and the aspect ratio of the frames.
+
+use crate::sheet;
+use log::warn;
+
+/// Terminal size assumed when it can't be measured, like when the output is piped.
+pub const DEFAULT_TERMINAL: (u32, u32) = (80, 24);
+
+/// Everything the playback size depends on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Layout {
+    /// Columns asked for with `--width`
+    pub width: Option<u32>,
+    /// Rows asked for with `--height`
+    pub height: Option<u32>,
+    /// `--fit`: the whole terminal but its last row
+    pub fit: bool,
+    /// `--force`: sizes larger than the terminal are kept
+    pub force: bool,
+    /// Columns and rows of the terminal, `None` if it can't be measured
+    pub terminal: Option<(u32, u32)>,
+    /// Width and height of the frames in pixels as they are shown, after cropping and rotating
+    pub source: Option<(u32, u32)>,
+    /// Pixels of the frame a character cell shows across and down
+    pub cell_pixels: (u32, u32),
+    /// Height to width ratio of the area a pixel covers on screen
+    pub pixel_aspect: f32,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout {
+            width: None,
+            height: None,
+            fit: false,
+            force: false,
+            terminal: None,
+            source: None,
+            cell_pixels: (1, 1),
+            pixel_aspect: 1.0,
+        }
+    }
+}
+
+impl Layout {
+    /// The playback size in characters, the terminal's unless `width` or `height` say
+    /// otherwise. Given only one of them, the other follows from the aspect ratio of the
+    /// `source` frames, capped at the terminal. With `fit_terminal` sizes larger than the
+    /// terminal are clamped to it, since only part of such frames would show, unless `force`
+    /// insists on them. A terminal without columns or rows counts as one that can't be measured.
+    ///
+    /// `fit` ignores `width` and `height` and takes the terminal but its last row, which the
+    /// renderer letterboxes the video into, so it is as large as fits and centered.
+    pub fn playback_size(&self, fit_terminal: bool) -> (u32, u32) {
+        let terminal = self.measured_terminal();
+        let (term_w, term_h) = terminal.unwrap_or(DEFAULT_TERMINAL);
+        if self.fit {
+            return (term_w, term_h.saturating_sub(1).max(1));
+        }
+        let fit_terminal = fit_terminal && terminal.is_some();
+        let fit = |given: u32, name: &str, limit: u32, unit: &str| {
+            if !fit_terminal || given <= limit {
+                given
+            } else if self.force {
+                warn!(
+                    "--{} {} is larger than the terminal's {} {}, the frames will be cut off",
+                    name, given, limit, unit
+                );
+                given
+            } else {
+                warn!(
+                    "--{} {} is larger than the terminal, playing at its {} {} instead (--force keeps it)",
+                    name, given, limit, unit
+                );
+                limit
+            }
+        };
+        let w = self.width.map(|w| fit(w, "width", term_w, "columns"));
+        let h = self.height.map(|h| fit(h, "height", term_h, "rows"));
+
+        // the derived axis never grows past the terminal
+        let derived = |cells: f32, limit: u32| {
+            let cells = (cells.round() as u32).max(1);
+            if fit_terminal && !self.force {
+                cells.min(limit)
+            } else {
+                cells
+            }
+        };
+        let (cell_width, cell_height) = self.cell_pixels;
+        let (cell_width, cell_height) = (cell_width as f32, cell_height as f32);
+        let aspect = self.pixel_aspect;
+        match (w, h, self.source) {
+            (Some(w), None, Some((src_w, src_h))) => {
+                let pixels = w as f32 * cell_width * src_h as f32 / src_w.max(1) as f32 / aspect;
+                (w, derived(pixels / cell_height, term_h))
+            }
+            (None, Some(h), Some((src_w, src_h))) => {
+                let pixels = h as f32 * cell_height * src_w as f32 / src_h.max(1) as f32 * aspect;
+                (derived(pixels / cell_width, term_w), h)
+            }
+            (w, h, _) => (w.unwrap_or(term_w), h.unwrap_or(term_h)),
+        }
+    }
+
+    /// The size of every thumbnail of a contact sheet `columns` thumbnails wide: by default
+    /// the terminal's width shared between them, the height following from the source's
+    /// aspect ratio, unless `width` or `height` give it.
+    pub fn thumbnail_size(&self, columns: u32) -> (u32, u32) {
+        let (term_w, _) = self.measured_terminal().unwrap_or(DEFAULT_TERMINAL);
+        let width = self
+            .width
+            .unwrap_or_else(|| sheet::thumbnail_width(term_w, columns));
+        Layout {
+            width: Some(width),
+            ..*self
+        }
+        .playback_size(false)
+    }
+
+    // some terminals, like serial consoles, report a size of 0x0
+    fn measured_terminal(&self) -> Option<(u32, u32)> {
+        self.terminal
+            .filter(|&(columns, rows)| columns > 0 && rows > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a mono character cell is twice as high as it is wide
+    fn layout(width: Option<u32>, height: Option<u32>, source: (u32, u32)) -> Layout {
+        Layout {
+            width,
+            height,
+            terminal: Some((120, 40)),
+            source: Some(source),
+            pixel_aspect: 2.0,
+            ..Layout::default()
+        }
+    }
+
+    #[test]
+    fn playback_takes_the_terminal_without_a_size() {
+        assert_eq!(
+            layout(None, None, (1920, 1080)).playback_size(true),
+            (120, 40)
+        );
+        let piped = Layout {
+            terminal: None,
+            ..layout(None, None, (1920, 1080))
+        };
+        assert_eq!(piped.playback_size(false), DEFAULT_TERMINAL);
+    }
+
+    #[test]
+    fn one_axis_follows_the_aspect_ratio_of_the_source() {
+        // 80 columns of 16:9 pixels are 45 pixels high, in cells twice as high as wide
+        assert_eq!(
+            layout(Some(80), None, (1920, 1080)).playback_size(true),
+            (80, 23)
+        );
+        assert_eq!(
+            layout(None, Some(20), (1920, 1080)).playback_size(true),
+            (71, 20)
+        );
+        // half blocks show two pixels a cell, which makes them square
+        let half_blocks = Layout {
+            cell_pixels: (1, 2),
+            pixel_aspect: 1.0,
+            ..layout(Some(80), None, (1920, 1080))
+        };
+        assert_eq!(half_blocks.playback_size(true), (80, 23));
+        // without a source both come from the terminal
+        let unknown = Layout {
+            source: None,
+            ..layout(Some(80), None, (1920, 1080))
+        };
+        assert_eq!(unknown.playback_size(true), (80, 40));
+    }
+
+    #[test]
+    fn sources_smaller_than_the_terminal_are_scaled_up() {
+        assert_eq!(layout(None, None, (16, 9)).playback_size(true), (120, 40));
+        assert_eq!(
+            layout(Some(100), None, (16, 9)).playback_size(true),
+            (100, 28)
+        );
+        // an empty source doesn't divide by zero
+        assert_eq!(
+            layout(Some(100), None, (0, 0)).playback_size(true),
+            (100, 1)
+        );
+    }
+
+    #[test]
+    fn sizes_larger_than_the_terminal_are_clamped() {
+        assert_eq!(
+            layout(Some(200), Some(100), (1920, 1080)).playback_size(true),
+            (120, 40)
+        );
+        // the derived height of a portrait video is capped as well
+        assert_eq!(
+            layout(Some(200), None, (1080, 1920)).playback_size(true),
+            (120, 40)
+        );
+        let forced = Layout {
+            force: true,
+            ..layout(Some(200), None, (1080, 1920))
+        };
+        assert_eq!(forced.playback_size(true), (200, 178));
+        // frames that aren't shown right away keep their size
+        assert_eq!(
+            layout(Some(200), None, (1080, 1920)).playback_size(false),
+            (200, 178)
+        );
+    }
+
+    #[test]
+    fn fit_takes_the_terminal_but_its_last_row() {
+        let fit = Layout {
+            fit: true,
+            ..layout(Some(50), Some(10), (1920, 1080))
+        };
+        assert_eq!(fit.playback_size(true), (120, 39));
+        let one_row = Layout {
+            terminal: Some((120, 1)),
+            ..fit
+        };
+        assert_eq!(one_row.playback_size(true), (120, 1));
+    }
+
+    #[test]
+    fn empty_terminals_count_as_unmeasured() {
+        let empty = Layout {
+            terminal: Some((0, 0)),
+            ..layout(None, None, (1920, 1080))
+        };
+        assert_eq!(empty.playback_size(true), DEFAULT_TERMINAL);
+        assert_eq!(Layout { fit: true, ..empty }.playback_size(true), (80, 23));
+        // nothing to clamp to
+        assert_eq!(
+            Layout {
+                width: Some(200),
+                ..empty
+            }
+            .playback_size(true),
+            (200, 56)
+        );
+        assert_eq!(empty.thumbnail_size(4), (18, 5));
+    }
+
+    #[test]
+    fn thumbnails_share_the_terminal_width() {
+        // 4 thumbnails of 28 columns with 3 gaps of 2 fill 120 columns
+        assert_eq!(layout(None, None, (1920, 1080)).thumbnail_size(4), (28, 8));
+        assert_eq!(
+            layout(Some(40), None, (1920, 1080)).thumbnail_size(4),
+            (40, 11)
+        );
+    }
+}
@@ -0,0 +1,59 @@
+/*
+   This file is part of term-video.
+
+   term-video is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   term-video is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with term-video.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Playback of videos as text in the terminal.
+//!
+//! Frames are extracted and resized by ffmpeg ([`ffmpeg`]), converted to printable text by a
+//! [`Renderer`] and shown on a frame clock by the [`player`]. The command line tool is a thin
+//! wrapper around these pieces.
+
+pub mod bench;
+pub mod cache;
+pub mod color;
+pub mod config;
+pub mod controls;
+pub mod diff;
+pub mod dither;
+pub mod edges;
+pub mod export;
+pub mod ffmpeg;
+pub mod images;
+pub mod kitty;
+pub mod layout;
+pub mod memory;
+pub mod palette;
+pub mod parse;
+pub mod player;
+pub mod ramp;
+pub mod recording;
+pub mod render;
+pub mod render_cache;
+pub mod sheet;
+pub mod sixel;
+pub mod smooth;
+pub mod source;
+pub mod subtitles;
+pub mod terminal;
+pub mod timestamp;
+pub mod tone;
+
+pub use color::{ColorMode, LumaMode, MapChannel};
+pub use ffmpeg::{extract_frames, ExtractOptions};
+pub use player::PlaybackOptions;
+pub use render::{
+    get_pixel_char, AutoLevels, Background, CharsetPreset, RenderMode, RenderOptions, Renderer,
+};
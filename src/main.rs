@@ -15,40 +15,263 @@
    along with term-video.  If not, see <https://www.gnu.org/licenses/>.
 */
 
-use clap::Parser;
-use image::{io::Reader, GenericImageView, Pixel};
+use anyhow::{bail, Context, Result};
+use clap::{
+    builder::Resettable, ArgAction, Args, CommandFactory, FromArgMatches, Parser, ValueEnum,
+};
+use image::DynamicImage;
+use indicatif::{HumanBytes, ProgressBar, ProgressStyle};
+use log::{debug, error, info, warn, Level, LevelFilter};
+use rayon::prelude::*;
+use serde::Serialize;
 use std::{
+    fmt::Write as _,
     fs,
-    io::Write,
-    process::{exit, Command, Stdio},
-    str::FromStr,
-    thread,
-    time::Duration,
+    io::IsTerminal,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    process::exit,
+    time::{Duration, UNIX_EPOCH},
+};
+use term_video::{
+    bench::measure,
+    cache::{list_frames, make_dir, remove_cache, CacheInUse, CacheLock, CacheMetadata},
+    config::Config,
+    export::{export, find_font, load_font, ExportOptions, Theme},
+    ffmpeg::{
+        check_dependencies, extract_audio, extract_frames_segmented, extract_frames_timed,
+        extract_frames_with_progress, has_filter, probe_video, Crop, FfmpegArgs, FrameStream,
+        Interpolation, InterpolationMode, PixelFormat, RawFrame, Rotation, ScaleFilter, Tonemap,
+        Transform, VideoInfo,
+    },
+    images::{
+        extract_gif_frames, extract_image_frames, gif_delays, is_gif, is_image_sequence,
+        list_images, trimmed_images,
+    },
+    kitty,
+    layout::Layout,
+    memory::{available_memory, buffer_fits, memory_limit},
+    parse::{parse_frame_rate, parse_rgb, parse_timestamp},
+    player::{
+        display_loop, play_frames, play_stream, render_frames_with_progress, restore_terminal,
+        Corner, MIN_FRAME_RATE,
+    },
+    ramp::{coverage_of, parse_coverage, RampMode},
+    recording::Recording,
+    render::luma_range,
+    render_cache::{self, RenderCache},
+    sheet::{self, playback_position, sample_indices, tile},
+    smooth::Hysteresis,
+    source::{is_url, Input},
+    subtitles::Subtitles,
+    terminal::{cell_size, likely_supports_kitty, likely_supports_sixel},
+    timestamp::format_timestamp,
+    AutoLevels, Background, CharsetPreset, ColorMode, ExtractOptions, LumaMode, MapChannel,
+    PlaybackOptions, RenderMode, RenderOptions, Renderer,
 };
-use walkdir::WalkDir;
 
-/*
-const CHARS: [char; 13] = [
-    ' ', '.', ',', '-', '~', ':', ';', '=', '!', '*', '#', '$', '@',
-];
- */
+// shortest part of a video worth starting another ffmpeg process for
+const MIN_SEGMENT_SECS: f64 = 2.0;
+
+// character cell size in pixels assumed for graphics output when the terminal doesn't tell
+const CELL_SIZE: (u32, u32) = (8, 16);
 
 #[derive(Parser)]
-#[command(version = "0.1.0", author = "Pascal Puffke <pascal@pascalpuffke.de>")]
+#[command(
+    version = "0.1.0",
+    author = "Pascal Puffke <pascal@pascalpuffke.de>",
+    disable_help_flag = true,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Subcommand>,
+    #[command(flatten)]
+    opts: Opts,
+}
+
+#[derive(clap::Subcommand)]
+enum Subcommand {
+    /// Extracts, renders and plays a video in one go, the same as without a subcommand
+    #[command(disable_help_flag = true)]
+    Run(Opts),
+    /// Extracts and renders a video into a recording, to be played later with `play`
+    #[command(disable_help_flag = true)]
+    Compile(CompileOpts),
+    /// Plays a recording made with `compile`, without needing the source video or ffmpeg
+    #[command(disable_help_flag = true)]
+    Play(PlayOpts),
+    /// Measures how fast each render mode turns the frames of a video into text
+    #[command(disable_help_flag = true)]
+    Bench(BenchOpts),
+}
+
+#[derive(Args)]
+struct CompileOpts {
+    #[arg(
+        value_name = "RECORDING",
+        conflicts_with_all = ["output", "render_to", "play"],
+        help = "Recording file to write the rendered frames to"
+    )]
+    recording: PathBuf,
+    #[command(flatten)]
+    opts: Opts,
+}
+
+#[derive(Args)]
+#[command(mut_arg("input", |arg| arg.required_unless_present(Resettable::Reset)))]
+struct PlayOpts {
+    #[arg(
+        value_name = "RECORDING",
+        conflicts_with_all = ["input", "output", "play"],
+        help = "Recording file made with compile or --output"
+    )]
+    recording: PathBuf,
+    #[command(flatten)]
+    opts: Opts,
+}
+
+#[derive(Args)]
+struct BenchOpts {
+    #[arg(
+        long,
+        default_value = "3",
+        value_parser = clap::value_parser!(u32).range(1..),
+        help = "How many times to render every frame in each mode"
+    )]
+    rounds: u32,
+    #[command(flatten)]
+    opts: Opts,
+}
+
+impl Cli {
+    /// The options of the subcommand that runs, or the top level ones without one.
+    fn opts(&self) -> &Opts {
+        match &self.command {
+            Some(Subcommand::Run(opts)) => opts,
+            Some(Subcommand::Compile(compile)) => &compile.opts,
+            Some(Subcommand::Play(play)) => &play.opts,
+            Some(Subcommand::Bench(bench)) => &bench.opts,
+            None => &self.opts,
+        }
+    }
+
+    fn opts_mut(&mut self) -> &mut Opts {
+        match &mut self.command {
+            Some(Subcommand::Run(opts)) => opts,
+            Some(Subcommand::Compile(compile)) => &mut compile.opts,
+            Some(Subcommand::Play(play)) => &mut play.opts,
+            Some(Subcommand::Bench(bench)) => &mut bench.opts,
+            None => &mut self.opts,
+        }
+    }
+}
+
+#[derive(Args, Clone)]
 struct Opts {
+    #[arg(long, action = ArgAction::Help, help = "Prints help information")]
+    help: Option<bool>,
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Config file with default options [default: ~/.config/term-video/config.toml]"
+    )]
+    config: Option<PathBuf>,
+    #[arg(
+        long,
+        value_parser = parse_time,
+        help = "Only play the video from this position on, given as SS, MM:SS or HH:MM:SS"
+    )]
+    start: Option<Duration>,
+    #[arg(
+        long,
+        value_parser = parse_time,
+        help = "Stop playing at this position, given as SS, MM:SS or HH:MM:SS"
+    )]
+    end: Option<Duration>,
+    #[arg(
+        long,
+        value_parser = parse_time,
+        help = "Start playback at this position, given like --start, but unlike it keep the part before it to seek back to"
+    )]
+    seek: Option<Duration>,
+    #[arg(
+        long,
+        value_name = "W:H:X:Y",
+        value_parser = parse_crop,
+        help = "Only play this region of the video, W by H pixels from X, Y on"
+    )]
+    crop: Option<Crop>,
+    #[arg(
+        long,
+        value_name = "DEGREES",
+        value_parser = parse_rotation,
+        help = "Turn the video clockwise by 90, 180 or 270 degrees, for footage shot sideways"
+    )]
+    rotate: Option<Rotation>,
+    #[arg(long, help = "Mirror the video left to right, like a selfie camera")]
+    flip_h: bool,
+    #[arg(long, help = "Mirror the video top to bottom")]
+    flip_v: bool,
+    #[arg(
+        long,
+        env = "TERM_VIDEO_FFMPEG",
+        default_value = "ffmpeg",
+        help = "ffmpeg binary to use, looked up in PATH unless it contains a directory"
+    )]
+    ffmpeg_path: PathBuf,
+    #[arg(
+        long,
+        value_name = "ARGS",
+        default_value = "",
+        allow_hyphen_values = true,
+        value_parser = parse_ffmpeg_args,
+        help = "Extra options for ffmpeg extracting the frames, quoted like in a shell and given in front of the input. Filters given with -vf run before the frames are resized"
+    )]
+    ffmpeg_args: FfmpegArgs,
+    #[arg(
+        long,
+        env = "TERM_VIDEO_FFPROBE",
+        default_value = "ffprobe",
+        help = "ffprobe binary to use, looked up in PATH unless it contains a directory"
+    )]
+    ffprobe_path: PathBuf,
     #[arg(
         short,
         long,
-        default_value_t = String::from(env!("CARGO_MANIFEST_DIR")) + "/split_frames",
-        help = "Where to save temporary frame data"
+        help = "Where to save temporary frame data [default: a new folder in the temp directory]"
     )]
-    cache: String,
+    cache: Option<String>,
+    /// `--cache` or the default picked for this run
+    #[arg(skip)]
+    cache_dir: String,
     #[arg(
         short,
         long,
-        help = "Input video file, can be any format as long as it's supported by ffmpeg."
+        required_unless_present_any = ["play", "clear_cache", "camera"],
+        action = ArgAction::Append,
+        value_delimiter = ',',
+        help = "Input video file or http(s) URL, can be any format as long as it's supported by ffmpeg, or - to read it from stdin. Animated GIFs are decoded without ffmpeg. Several inputs, repeated or separated by commas, play back to back"
+    )]
+    input: Vec<String>,
+    #[arg(
+        long,
+        value_name = "DEVICE",
+        num_args = 0..=1,
+        default_missing_value = "",
+        conflicts_with_all = [
+            "input", "play", "start", "end", "seek", "reuse_cache", "audio", "reverse", "bounce",
+            "step", "subtitle", "output", "render_to", "dump_frames"
+        ],
+        help = "Play a live camera feed instead of an input, the first camera unless a device is given [default: /dev/video0 on Linux, 0 on macOS]"
+    )]
+    camera: Option<String>,
+    #[arg(
+        long,
+        help = "Download remote videos to a temporary file before extracting frames"
     )]
-    input: String,
+    download: bool,
     #[arg(
         short,
         long,
@@ -61,159 +284,2293 @@ struct Opts {
         help = "Vertical playback resolution [default: current terminal columns]"
     )]
     height: Option<u32>,
+    #[arg(
+        long,
+        help = "Keep --width and --height even if they are larger than the terminal"
+    )]
+    force: bool,
+    #[arg(
+        long,
+        help = "Play at the largest size that fits the terminal keeping the source aspect ratio, centered and with a row left for the prompt. Overrides --width and --height"
+    )]
+    fit: bool,
     #[arg(
         short,
         long,
-        help = "Playback frame rate [default: input video FPS, or 30 should ffprobe fail]"
+        value_parser = parse_fps,
+        help = "Playback frame rate, may be fractional like 23.976 or 24000/1001 [default: input video FPS, or 30 should ffprobe fail]. Overrides the frame delays of GIFs"
     )]
-    fps: Option<u32>,
-}
-
-fn main() {
-    let opts = Opts::parse();
-    let term_dim = term_size::dimensions().unwrap_or((80, 24));
-    let w = opts.width.unwrap_or(term_dim.0 as u32);
-    let h = opts.height.unwrap_or(term_dim.1 as u32);
-    let fps = opts
-        .fps
-        .unwrap_or(get_frame_rate(&opts.input).unwrap_or(30));
-
-    make_dir(&opts.cache);
-    split_and_resize_frames(&opts.input, &opts.cache, w, h);
-    display_loop(&opts.cache, w, h, fps);
-
-    // clean up temporary directory before exiting
-    fs::remove_dir_all(&opts.cache).expect("could not delete temporary directory, enjoy the mess");
-}
-
-fn make_dir(name: &str) {
-    if let Err(_) = fs::create_dir(name) {
-        fs::remove_dir_all(name).expect(&format!("could not delete directory {}", name));
-        fs::create_dir(name).expect(&format!("could not create directory {}", name));
-    }
-}
-
-fn split_and_resize_frames(file_name: &str, cache_dir: &str, width: u32, height: u32) {
-    // ffmpeg -i <file_name> -f image2 -vf scale=<w:h> <cache>/frame-%07d.png
-    Command::new("ffmpeg")
-        .args(vec![
-            "-i",
-            file_name,
-            "-f",
-            "image2",
-            "-vf",
-            &format!("scale={}:{}", width, height),
-            &format!("{}/frame-%07d.png", cache_dir),
-        ])
-        .stdout(Stdio::null())
-        .output()
-        .unwrap_or_else(|e| {
-            println!("Failed to execute ffmpeg - do you have it installed? {}", e);
-            exit(1);
-        });
-}
-
-fn get_frame_rate(video: &str) -> Option<u32> {
-    let ffprobe = Command::new("ffprobe")
-        .args(vec![
-            "-v",
-            "error",
-            "-select_streams",
-            "v:0",
-            "-show_entries",
-            "stream=r_frame_rate",
-            "-of",
-            "default=noprint_wrappers=1:nokey=1",
-            video,
-        ])
-        .output();
-
-    if let Ok(out) = ffprobe {
-        if let Ok(fps_str) = String::from_utf8(out.stdout) {
-            if let Some((num, den)) = fps_str.trim().split_once('/') {
-                if let (Ok(num), Ok(den)) = (num.parse::<f32>(), den.parse::<f32>()) {
-                    return Some((num / den) as u32);
-                }
-            }
-        }
-    }
-
-    None
-}
-
-fn display_loop(cache_dir: &str, width: u32, height: u32, frame_rate: u32) {
-    let mut frame_buffer = String::with_capacity((height + (width * height)) as usize);
-
-    // 清空屏幕并移动到左上角
-    print!("\x1B[2J\x1B[H");
-    // 隐藏光标
-    print!("\x1B[?25l");
-    // 禁用行包装
-    print!("\x1B[?7l");
-
-    let mut frame_files: Vec<_> = WalkDir::new(cache_dir)
-        .into_iter()
-        .skip(1)
-        .map(|e| e.unwrap().path().to_owned())
-        .collect();
-    frame_files.sort();
-
-    let mut display_buffer: Vec<String> = Vec::with_capacity(frame_files.len());
-
-    // 按顺序处理每一帧
-    for frame_path in frame_files {
-        let frame = Reader::open(&frame_path).unwrap().decode().unwrap();
-
-        for y in 0..height {
-            for x in 0..width {
-                frame_buffer.push(get_pixel_char(
-                    *frame.get_pixel(x, y).to_luma().0.get(0).unwrap(),
-                ))
-            }
-            frame_buffer.push('\n');
-        }
-
-        display_buffer.push(frame_buffer.clone());
-        frame_buffer.clear();
-    }
-
-    // 显示每一帧
-    for frame in &display_buffer {
-        // 仅移动光标到起始位置
-        print!("\x1B[H");
-        // 使用单次输出
-        print!("{}", frame);
-        // 立即刷新输出
-        std::io::stdout().flush().unwrap();
-        thread::sleep(Duration::from_micros((1000000 / frame_rate) as u64));
-    }
+    fps: Option<f64>,
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u32).range(1..),
+        help = "Only extract and play every Nth frame, each shown N times as long so the video plays as long as before"
+    )]
+    sample_rate: u32,
+    #[arg(
+        long,
+        value_enum,
+        value_name = "MODE",
+        num_args = 0..=1,
+        default_missing_value = "motion",
+        requires = "fps",
+        conflicts_with = "sample_rate",
+        help = "Make up frames between the ones in the video to play it at --fps, with ffmpeg's minterpolate filter. Motion interpolation is slow to extract"
+    )]
+    interpolate: Option<InterpolationMode>,
+    #[arg(
+        long,
+        value_enum,
+        value_name = "CURVE",
+        num_args = 0..=1,
+        default_missing_value = "hable",
+        help = "Tone map HDR video to SDR with ffmpeg's zscale and tonemap filters, which happens on its own for video ffprobe reports as HDR"
+    )]
+    tonemap: Option<Tonemap>,
+    #[arg(
+        long,
+        conflicts_with = "tonemap",
+        help = "Don't tone map HDR video on its own"
+    )]
+    no_tonemap: bool,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = ScaleFilter::Bicubic,
+        help = "How frames are interpolated when scaled down to the playback size, neighbor or lanczos look sharper"
+    )]
+    scale_filter: ScaleFilter,
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u32).range(1..=8),
+        conflicts_with_all = ["sixel", "kitty"],
+        help = "Extract frames N times larger and average every NxN block of pixels into one, against aliasing and noise at small sizes"
+    )]
+    supersample: u32,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = ColorMode::Mono,
+        help = "Output color mode, use 256 for terminals without truecolor support"
+    )]
+    color_mode: ColorMode,
+    #[arg(long, help = "Shorthand for --color-mode truecolor")]
+    color: bool,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = LumaMode::Rec709,
+        help = "Weighting of the color channels in the luminosity that picks characters"
+    )]
+    luma_mode: LumaMode,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = MapChannel::Luma,
+        help = "Property of the pixels that picks characters, saturation makes vivid regions dense and grays sparse"
+    )]
+    map_channel: MapChannel,
+    #[arg(
+        long,
+        value_name = "R,G,B|darken",
+        value_parser = parse_background,
+        help = "Fill the cell backgrounds in color modes with this color, or a darkened copy of each cell's own"
+    )]
+    background: Option<Background>,
+    #[arg(
+        long,
+        value_name = "R,G,B",
+        value_parser = parse_tint,
+        conflicts_with_all = ["color", "sixel", "kitty"],
+        help = "Draw the characters of mono output in this color, like 51,255,51 for a green phosphor screen"
+    )]
+    tint: Option<[u8; 3]>,
+    #[arg(
+        long,
+        value_name = "0-255",
+        default_value_t = 1,
+        help = "Leave pixels less opaque than this blank, showing the background like the padding around the video, for GIFs and images with transparency. 0 draws them all"
+    )]
+    alpha_threshold: u8,
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u16).range(2..=256),
+        conflicts_with_all = ["sixel", "kitty", "stream"],
+        help = "Snap the colors to a fixed palette of N colors built from a sample of the frames, so they don't flicker from frame to frame"
+    )]
+    palette_size: Option<u16>,
+    #[arg(
+        long,
+        value_parser = parse_charset,
+        help = "Characters to map luminosity onto, ordered from darkest to brightest"
+    )]
+    charset: Option<String>,
+    #[arg(
+        long,
+        value_enum,
+        conflicts_with = "charset",
+        default_value_t = CharsetPreset::Ascii,
+        help = "Named character ramp to use when no --charset is given"
+    )]
+    charset_preset: CharsetPreset,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = RampMode::Linear,
+        help = "Pick characters in equal bands of luminosity, or by how much of the cell they cover for smoother tones"
+    )]
+    ramp: RampMode,
+    #[arg(
+        long,
+        value_name = "CHAR=SHARE",
+        value_parser = parse_coverage_pair,
+        action = ArgAction::Append,
+        help = "Share of the cell between 0 and 1 a character covers, for --ramp coverage. Can be given several times"
+    )]
+    coverage: Vec<(char, f32)>,
+    #[arg(
+        long,
+        default_value_t = 0.0,
+        allow_negative_numbers = true,
+        value_parser = parse_finite,
+        help = "Added to every pixel value before mapping it to a character, brightens dark footage"
+    )]
+    brightness: f32,
+    #[arg(
+        long,
+        default_value_t = 1.0,
+        allow_negative_numbers = true,
+        value_parser = parse_finite,
+        help = "Scales pixel values around the midpoint, values above 1 bring out detail in flat footage"
+    )]
+    contrast: f32,
+    #[arg(
+        long,
+        default_value_t = 1.0,
+        value_parser = parse_gamma,
+        help = "Gamma correction applied before mapping pixels to characters, values above 1 lift shadows"
+    )]
+    gamma: f32,
+    #[arg(
+        long,
+        help = "Map the brightest pixels to the sparsest characters, for dark-on-light terminals"
+    )]
+    invert: bool,
+    #[arg(
+        long,
+        value_name = "FRAMES",
+        default_value_t = 0,
+        conflicts_with_all = ["stream", "camera", "play"],
+        help = "Fade in from an empty screen over the first FRAMES frames and out again over the last ones"
+    )]
+    fade: u32,
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 256,
+        value_parser = clap::value_parser!(u16).range(1..=256),
+        help = "Posterize luminosity into N levels before mapping it to characters"
+    )]
+    levels: u16,
+    #[arg(
+        long,
+        help = "Stretch the luminosity range of the footage over the whole character ramp"
+    )]
+    auto_levels: bool,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = AutoLevelsMode::Frame,
+        help = "Whether --auto-levels stretches each frame on its own or all of them alike"
+    )]
+    auto_levels_mode: AutoLevelsMode,
+    #[arg(
+        long,
+        help = "Smooth out banding in gradients with Floyd-Steinberg dithering of the character choice"
+    )]
+    dither: bool,
+    #[arg(
+        long,
+        help = "Draw outlines: map the Sobel edge strength onto the characters instead of the brightness"
+    )]
+    edges: bool,
+    #[arg(
+        long,
+        value_name = "0-255",
+        default_value_t = 0,
+        requires = "edges",
+        help = "Edge strength below which pixels are treated as flat, raise it to suppress noise"
+    )]
+    edge_threshold: u8,
+    #[arg(
+        long,
+        help = "Keep cells from flickering between neighbouring characters: a cell only changes once its luminosity moved past --smooth-threshold"
+    )]
+    smooth: bool,
+    #[arg(
+        long,
+        value_name = "1-255",
+        default_value_t = 8,
+        value_parser = clap::value_parser!(u8).range(1..),
+        requires = "smooth",
+        help = "How far the luminosity of a cell has to move before --smooth lets it change"
+    )]
+    smooth_threshold: u8,
+    #[arg(
+        long,
+        help = "Decode frames just-in-time on a background thread instead of buffering them all"
+    )]
+    lazy: bool,
+    #[arg(
+        long,
+        default_value_t = 16,
+        help = "Number of frames decoded ahead of playback in --lazy mode"
+    )]
+    prebuffer: usize,
+    #[arg(
+        long,
+        value_name = "MB",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Memory in MiB the rendered frames may take up when they are all buffered, beyond that they play as with --lazy [default: half of the available memory]"
+    )]
+    max_memory: Option<u64>,
+    #[arg(
+        long,
+        help = "Preserve the source aspect ratio, letterboxing the video inside the playback area"
+    )]
+    keep_aspect: bool,
+    #[arg(
+        long,
+        default_value_t = 2.0,
+        value_parser = parse_cell_aspect,
+        help = "Height to width ratio of a terminal character cell, used by --keep-aspect"
+    )]
+    cell_aspect: f32,
+    #[arg(
+        long,
+        conflicts_with = "cell_aspect",
+        help = "Treat character cells as square, mapping source pixels 1:1 onto cells, for square-cell fonts"
+    )]
+    no_cell_correct: bool,
+    #[arg(
+        long,
+        help = "Play the video's audio track in sync with the frames, requires ffplay"
+    )]
+    audio: bool,
+    #[arg(
+        long,
+        visible_alias = "no-clean",
+        help = "Keep extracted frames after playback and reuse them when the input and size match"
+    )]
+    reuse_cache: bool,
+    #[arg(
+        long,
+        conflicts_with = "stream",
+        help = "Keep the rendered frames compressed in ~/.cache/term-video and play them from there when the inputs and settings match"
+    )]
+    render_cache: bool,
+    #[arg(long, help = "Delete all frames kept with --render-cache")]
+    clear_cache: bool,
+    #[arg(
+        long,
+        help = "Render two pixels per cell with Unicode half blocks, best combined with --color"
+    )]
+    half_block: bool,
+    #[arg(
+        long,
+        conflicts_with = "half_block",
+        help = "Render every pixel as a space in its color as background, turning the terminal into a low-res RGB display. Implies --color unless --color-mode 256 is given"
+    )]
+    blocks: bool,
+    #[arg(
+        long,
+        conflicts_with_all = ["half_block", "blocks"],
+        help = "Render 2x4 pixels per cell as Unicode Braille dots, for line art and high-contrast footage"
+    )]
+    braille: bool,
+    #[arg(
+        long,
+        value_name = "0-255",
+        default_value_t = 128,
+        help = "Brightness from which a pixel gets a dot in --braille mode, ignored with --dither"
+    )]
+    braille_threshold: u8,
+    #[arg(
+        long,
+        conflicts_with_all = ["half_block", "blocks", "braille"],
+        help = "Show the actual video as Sixel graphics, for terminals like xterm, mlterm or foot"
+    )]
+    sixel: bool,
+    #[arg(
+        long,
+        conflicts_with_all = ["half_block", "blocks", "braille", "sixel"],
+        help = "Show the actual video with the Kitty graphics protocol, for Kitty, WezTerm or Ghostty"
+    )]
+    kitty: bool,
+    #[arg(
+        long = "loop",
+        value_name = "N",
+        num_args = 0..=1,
+        default_missing_value = "0",
+        help = "Play the video N times, or forever if N is 0 or omitted"
+    )]
+    plays: Option<u32>,
+    #[arg(
+        long,
+        value_name = "MS",
+        default_value_t = 0,
+        help = "Hold the last frame for this many milliseconds before the video starts over with --loop"
+    )]
+    loop_delay: u64,
+    #[arg(
+        long,
+        help = "Only redraw the cells that changed between frames, saves a lot of output on mostly static footage"
+    )]
+    diff: bool,
+    #[arg(
+        long,
+        visible_alias = "no-clear",
+        conflicts_with_all = ["diff", "stats", "sixel", "kitty"],
+        help = "Print every frame below the previous one instead of over it, leaving them in the scrollback for recording tools like asciinema"
+    )]
+    scroll: bool,
+    #[arg(
+        long,
+        default_value_t = 1.0,
+        value_parser = parse_speed,
+        help = "Playback speed multiplier, e.g. 0.5 for slow motion or 2 for double speed"
+    )]
+    speed: f64,
+    #[arg(
+        long,
+        conflicts_with = "stream",
+        help = "Play the video backwards, from the last frame to the first"
+    )]
+    reverse: bool,
+    #[arg(
+        long,
+        conflicts_with_all = ["stream", "reverse"],
+        help = "Play the video forward, then backward, then forward again, a cycle per --loop"
+    )]
+    bounce: bool,
+    #[arg(
+        long,
+        conflicts_with_all = ["stream", "lazy", "output", "render_to", "dump_frames"],
+        help = "Pause on the first frame and move one frame on with space or the right arrow key, back with the left one"
+    )]
+    step: bool,
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["reverse", "bounce"],
+        help = "Burn the captions of an SRT file into the bottom rows of the frames"
+    )]
+    subtitle: Option<PathBuf>,
+    #[arg(
+        long,
+        conflicts_with_all = ["audio", "reuse_cache"],
+        help = "Pipe raw frames straight from ffmpeg instead of extracting them to the cache first"
+    )]
+    stream: bool,
+    #[arg(
+        short,
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..),
+        help = "Number of ffmpeg processes extracting parts of the video at once [default: number of CPUs]"
+    )]
+    jobs: Option<u32>,
+    #[arg(
+        long,
+        help = "Don't show a progress bar while extracting frames, and only report warnings and errors"
+    )]
+    quiet: bool,
+    #[arg(
+        short,
+        long,
+        action = ArgAction::Count,
+        conflicts_with = "quiet",
+        help = "Report more of what's going on, like the ffmpeg commands run; twice for ffmpeg's own log too"
+    )]
+    verbose: u8,
+    #[arg(
+        long,
+        help = "Show the measured and target frame rate, frame index and dropped frames in the top-right corner"
+    )]
+    stats: bool,
+    #[arg(
+        long,
+        value_enum,
+        value_name = "CORNER",
+        num_args = 0..=1,
+        default_missing_value = "bottom-right",
+        conflicts_with_all = ["sixel", "kitty"],
+        help = "Show the position in the video and its length in a corner of the frame"
+    )]
+    timecode: Option<Corner>,
+    #[arg(
+        long,
+        help = "Exit with an error if the terminal couldn't keep up with the frame rate, for scripts and CI"
+    )]
+    strict: bool,
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Save the rendered frames to a recording file instead of playing them"
+    )]
+    output: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with = "output",
+        help = "Draw the rendered frames into an animated GIF, or a video like MP4 through ffmpeg, instead of playing them"
+    )]
+    render_to: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "Write every rendered frame to a text file of its own in DIR, frame-0000001.txt and on, instead of playing them. Combines with --output and --render-to"
+    )]
+    dump_frames: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Monospace TrueType or OpenType font --render-to draws the characters with [default: a commonly installed one]"
+    )]
+    font: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "PX",
+        default_value_t = 16.0,
+        value_parser = parse_font_size,
+        help = "Height of a line of characters in --render-to pictures, in pixels"
+    )]
+    font_size: f32,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = Theme::Dark,
+        help = "Colors of --render-to pictures where the frames don't set any"
+    )]
+    theme: Theme,
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["input", "output"],
+        help = "Play a recording saved with --output, without needing the source video or ffmpeg"
+    )]
+    play: Option<PathBuf>,
+    #[arg(
+        long,
+        visible_alias = "dry-run",
+        conflicts_with = "play",
+        help = "Print the frame rate, frame count, duration and memory needed for playback, then exit without extracting"
+    )]
+    info: bool,
+    #[arg(
+        long,
+        value_name = "COLUMNS",
+        value_parser = clap::value_parser!(u32).range(1..),
+        conflicts_with_all = [
+            "stream", "camera", "play", "step", "fit", "info", "output", "render_to", "dump_frames",
+            "sixel", "kitty"
+        ],
+        help = "Print thumbnails of frames sampled evenly across the video in a grid COLUMNS wide, then exit. --width and --height set the size of every thumbnail"
+    )]
+    contact_sheet: Option<u32>,
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..),
+        requires = "contact_sheet",
+        help = "How many frames the contact sheet shows [default: three rows of thumbnails]"
+    )]
+    sheet_frames: Option<u32>,
+    #[arg(long, requires = "info", help = "Print --info as JSON")]
+    json: bool,
+}
+
+fn parse_charset(s: &str) -> Result<String, String> {
+    if s.is_empty() {
+        Err(String::from("charset must contain at least one character"))
+    } else {
+        Ok(s.to_owned())
+    }
+}
+
+fn parse_cell_aspect(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(aspect) if aspect > 0.0 && aspect.is_finite() => Ok(aspect),
+        _ => Err(String::from("cell aspect must be a positive number")),
+    }
+}
+
+fn parse_background(s: &str) -> Result<Background, String> {
+    if s == "darken" {
+        return Ok(Background::Darkened);
+    }
+    parse_rgb(s)
+        .map(Background::Color)
+        .ok_or_else(|| String::from("expected R,G,B with values from 0 to 255, or darken"))
+}
+
+fn parse_tint(s: &str) -> Result<[u8; 3], String> {
+    parse_rgb(s).ok_or_else(|| String::from("expected R,G,B with values from 0 to 255"))
+}
+
+fn parse_coverage_pair(s: &str) -> Result<(char, f32), String> {
+    parse_coverage(s)
+        .ok_or_else(|| String::from("expected a character and its coverage, like @=0.28"))
+}
+
+fn parse_crop(s: &str) -> Result<Crop, String> {
+    Crop::parse(s).ok_or_else(|| String::from("expected W:H:X:Y in pixels, like 640:360:0:60"))
+}
+
+fn parse_rotation(s: &str) -> Result<Rotation, String> {
+    s.parse()
+        .ok()
+        .and_then(Rotation::from_degrees)
+        .ok_or_else(|| String::from("expected 0, 90, 180 or 270 degrees"))
+}
+
+fn parse_ffmpeg_args(s: &str) -> Result<FfmpegArgs, String> {
+    FfmpegArgs::parse(s)
+        .ok_or_else(|| String::from("unterminated quote, or -vf without a filter after it"))
+}
+
+fn parse_finite(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(value) if value.is_finite() => Ok(value),
+        _ => Err(String::from("expected a number")),
+    }
+}
+
+fn parse_time(s: &str) -> Result<Duration, String> {
+    parse_timestamp(s).ok_or_else(|| String::from("expected a time like 90, 01:30 or 00:01:30"))
+}
+
+fn parse_fps(s: &str) -> Result<f64, String> {
+    parse_frame_rate(s).ok_or_else(|| {
+        format!(
+            "frame rate must be a number or fraction like 24000/1001 of at least {}",
+            MIN_FRAME_RATE
+        )
+    })
+}
+
+/// Speed multipliers --speed accepts, from a hundredth to a hundred times the frame rate.
+const SPEEDS: RangeInclusive<f64> = 0.01..=100.0;
+
+fn parse_speed(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(speed) if SPEEDS.contains(&speed) => Ok(speed),
+        _ => Err(format!(
+            "speed must be a number from {} to {}",
+            SPEEDS.start(),
+            SPEEDS.end()
+        )),
+    }
+}
+
+fn parse_gamma(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(gamma) if gamma > 0.0 && gamma.is_finite() => Ok(gamma),
+        _ => Err(String::from("gamma must be a positive number")),
+    }
+}
+
+fn parse_font_size(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(size) if size >= 1.0 && size.is_finite() => Ok(size),
+        _ => Err(String::from("font size must be at least 1 pixel")),
+    }
+}
+
+fn main() {
+    let cli = match parse_opts() {
+        Ok(cli) => cli,
+        Err(e) => {
+            eprintln!("error: {:#}", e);
+            exit(1);
+        }
+    };
+    let opts = cli.opts();
+    init_logging(opts);
+    let result = match &cli.command {
+        Some(Subcommand::Bench(bench_opts)) => bench(opts, bench_opts),
+        _ => run(opts),
+    };
+    if let Err(e) = result {
+        restore_terminal();
+        // a cache directory another run holds has that run's frames in it
+        let in_use = e.chain().any(|cause| cause.is::<CacheInUse>());
+        if owns_cache(opts) && !in_use && Path::new(&opts.cache_dir).exists() {
+            let _ = remove_cache(&opts.cache_dir);
+        }
+        error!("{:#}", e);
+        exit(1);
+    }
+}
+
+/// Sends all diagnostics to stderr, keeping stdout for frames and machine readable output.
+/// `--quiet` leaves only warnings and errors, every `-v` adds a level of detail and RUST_LOG
+/// overrides both.
+fn init_logging(opts: &Opts) {
+    use std::io::Write as _;
+
+    let level = match (opts.quiet, opts.verbose) {
+        (true, _) => LevelFilter::Warn,
+        (false, 0) => LevelFilter::Info,
+        (false, 1) => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+    let mut builder = env_logger::Builder::new();
+    match std::env::var("RUST_LOG") {
+        Ok(filters) => builder.parse_filters(&filters),
+        // other crates only get to warn
+        Err(_) => builder
+            .filter_level(LevelFilter::Warn)
+            .filter_module("term_video", level),
+    };
+    builder
+        .format(|buf, record| match record.level() {
+            Level::Info | Level::Warn => writeln!(buf, "{}", record.args()),
+            level => writeln!(buf, "{}: {}", level.as_str().to_lowercase(), record.args()),
+        })
+        .target(env_logger::Target::Stderr)
+        .init();
+}
+
+/// Parses the command line on top of the config file: options given on the command line win
+/// over environment variables, which win over the config file, which wins over the built-in
+/// defaults.
+fn parse_opts() -> Result<Cli> {
+    // the config file has to be read before the arguments it provides defaults for are parsed
+    let mut args = std::env::args_os().skip(1);
+    let mut config_path = None;
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("--config") => config_path = args.next().map(PathBuf::from),
+            Some(arg) => {
+                if let Some(path) = arg.strip_prefix("--config=") {
+                    config_path = Some(PathBuf::from(path));
+                }
+            }
+            None => {}
+        }
+    }
+    let config = Config::load(config_path.as_deref())?;
+
+    let mut command = Cli::command();
+    let subcommands: Vec<String> = command
+        .get_subcommands()
+        .map(|subcommand| subcommand.get_name().to_owned())
+        .collect();
+    for (id, value) in config.defaults() {
+        command = command.mut_arg(id, |arg| arg.default_value(value.clone()));
+        for name in &subcommands {
+            command = command.mut_subcommand(name, |subcommand| {
+                subcommand.mut_arg(id, |arg| arg.default_value(value.clone()))
+            });
+        }
+    }
+    let matches = command.get_matches();
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    // compile and play are --output and --play spelled as subcommands
+    match &mut cli.command {
+        Some(Subcommand::Compile(compile)) => compile.opts.output = Some(compile.recording.clone()),
+        Some(Subcommand::Play(play)) => play.opts.play = Some(play.recording.clone()),
+        _ => {}
+    }
+    let opts = cli.opts_mut();
+    opts.cache_dir = match &opts.cache {
+        Some(cache) => cache.clone(),
+        None => default_cache_dir(opts.reuse_cache),
+    };
+    Ok(cli)
+}
+
+/// Cache directory for runs without `--cache`. Every run gets its own below the temp
+/// directory, so several can play at once, except with `--reuse-cache`, whose frames have to
+/// be found again by the next run.
+fn default_cache_dir(reuse_cache: bool) -> String {
+    let name = if reuse_cache {
+        String::from("term-video-frames")
+    } else {
+        format!("term-video-{}", std::process::id())
+    };
+    std::env::temp_dir()
+        .join(name)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Whether frames go to a terminal. Otherwise they are written once, as fast as possible and
+/// without any escapes besides colors, so `term-video ... > frames.txt` gives clean text.
+fn interactive() -> bool {
+    std::io::stdout().is_terminal()
+}
+
+/// Whether the terminal can be driven with cursor movement and screen control escapes, which
+/// dumb terminals, or no terminal type at all, can't.
+fn terminal_escapes() -> bool {
+    interactive() && std::env::var("TERM").is_ok_and(|term| !term.is_empty() && term != "dumb")
+}
+
+/// Whether the cache directory only holds frames for this run and must be deleted afterwards.
+fn owns_cache(opts: &Opts) -> bool {
+    opts.play.is_none() && !opts.stream && opts.camera.is_none() && !opts.reuse_cache
+}
+
+/// Locks the cache directory while this run uses it, if other runs can be pointed at it too:
+/// one given with `--cache`, or the shared one of `--reuse-cache`. Every other run gets a
+/// directory of its own.
+fn lock_cache(opts: &Opts) -> Result<Option<CacheLock>> {
+    if opts.cache.is_none() && !opts.reuse_cache {
+        return Ok(None);
+    }
+    CacheLock::acquire(&opts.cache_dir).map(Some)
+}
+
+/// Restores the terminal on Ctrl-C and cleans up the temporary files of this run.
+fn install_interrupt_handler(cache_dir: Option<String>, downloaded: Vec<PathBuf>) -> Result<()> {
+    ctrlc::set_handler(move || {
+        // hold stdout so the display loop can't print another frame over the restored terminal
+        let _stdout = std::io::stdout().lock();
+        restore_terminal();
+        if let Some(cache_dir) = cache_dir.as_deref().filter(|dir| Path::new(dir).exists()) {
+            let _ = remove_cache(cache_dir);
+        }
+        for downloaded in &downloaded {
+            let _ = fs::remove_file(downloaded);
+        }
+        exit(130);
+    })
+    .context("could not install Ctrl-C handler")
+}
+
+fn plays(opts: &Opts) -> Option<u32> {
+    if !interactive() {
+        return Some(1);
+    }
+    match opts.plays {
+        None => Some(1),
+        Some(0) => None,
+        plays => plays,
+    }
+}
+
+fn play_recording(opts: &Opts, path: &Path) -> Result<()> {
+    let recording = Recording::load(path)?;
+    if opts.render_to.is_some() || opts.dump_frames.is_some() {
+        return save_recording(opts, &recording, load_subtitles(opts, false)?.as_ref());
+    }
+    install_interrupt_handler(None, Vec::new())?;
+    if let (Some((columns, rows)), Some((term_w, term_h)), true) =
+        (recording.size, term_size::dimensions(), interactive())
+    {
+        if columns as usize > term_w || rows as usize > term_h {
+            warn!(
+                "The recording is {}x{} characters, larger than the {}x{} terminal, the frames will be cut off",
+                columns, rows, term_w, term_h
+            );
+        }
+    }
+
+    // Sixel and Kitty recordings start with a cursor move and the image itself
+    let graphics = recording
+        .frames
+        .first()
+        .is_some_and(|frame| frame.contains("\x1BPq") || kitty::is_kitty_frame(frame));
+    let playback_opts = PlaybackOptions {
+        frame_rate: opts.fps.unwrap_or(recording.frame_rate),
+        plays: plays(opts),
+        loop_delay: Duration::from_millis(opts.loop_delay),
+        frame_delays: recording.frame_delays.filter(|_| opts.fps.is_none()),
+        diff: opts.diff,
+        speed: opts.speed,
+        stats: opts.stats,
+        timecode: opts.timecode,
+        escapes: terminal_escapes(),
+        paced: interactive(),
+        reverse: opts.reverse,
+        bounce: opts.bounce,
+        step: opts.step,
+        scroll: opts.scroll,
+        graphics,
+        subtitles: load_subtitles(opts, graphics)?,
+        strict: opts.strict,
+        ..PlaybackOptions::default()
+    };
+    play_frames(&recording.frames, &playback_opts)
+}
+
+fn run(opts: &Opts) -> Result<()> {
+    if opts.clear_cache {
+        if let Some(dir) = render_cache::clear()? {
+            info!("Deleted the rendered frames in {}", dir.display());
+        }
+        if opts.input.is_empty() && opts.play.is_none() {
+            return Ok(());
+        }
+    }
+    if let Some(path) = &opts.play {
+        return play_recording(opts, path);
+    }
+    // clap requires one of them, the camera takes the place of the input
+    let camera = opts.camera.clone().map(|device| vec![device]);
+    let sources = camera.as_ref().unwrap_or(&opts.input);
+    if opts.stream && sources.len() > 1 {
+        bail!("--stream can only play a single input");
+    }
+    if let (Some(start), Some(end)) = (opts.start, opts.end) {
+        if end <= start {
+            bail!("--end must be later than --start");
+        }
+    }
+    if let Some(seek) = opts.seek {
+        if opts.start.is_some_and(|start| seek < start) {
+            bail!("--seek must not be earlier than --start");
+        }
+        if opts.end.is_some_and(|end| seek >= end) {
+            bail!("--seek must be earlier than --end");
+        }
+    }
+    let piped = sources.iter().filter(|source| *source == "-").count();
+    if piped > 1 {
+        bail!("stdin can only be read once, give - as input a single time");
+    }
+    if piped > 0 && opts.fps.is_none() {
+        warn!("ffprobe can't look into piped video ahead of ffmpeg, playing it at 30 fps unless --fps says otherwise");
+    }
+    check_ffmpeg(opts, sources)?;
+    let inputs = sources
+        .iter()
+        // nothing is extracted with --info, so there is no need to download anything
+        .map(|source| match &opts.camera {
+            Some(_) => Input::camera(source),
+            None => Input::resolve(source, opts.download && !opts.info),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let mode = render_mode(opts);
+    warn_uncovered(opts);
+    if opts.render_to.is_some() && mode.is_graphics() {
+        bail!("--render-to draws characters, it can't be combined with --sixel or --kitty");
+    }
+    // one axis may follow from the other, which only the size of the first input tells
+    let source = match (opts.width, opts.height) {
+        _ if opts.fit => source_size(opts, &inputs[0]),
+        // thumbnails always have a width
+        (_, None) if opts.contact_sheet.is_some() => source_size(opts, &inputs[0]),
+        (Some(_), None) | (None, Some(_)) => source_size(opts, &inputs[0]),
+        _ => None,
+    };
+    // frames that aren't shown right away can be any size
+    let size = match opts.contact_sheet {
+        Some(columns) => layout(opts, mode, source).thumbnail_size(columns),
+        None => layout(opts, mode, source).playback_size(interactive() && !saves_frames(opts)),
+    };
+    let (renderer, extract_opts) = renderer(opts, size, mode, color_mode(opts));
+    debug!(
+        "drawing {}x{} cells in {:?} mode with {:?} colors",
+        size.0,
+        size.1,
+        mode,
+        color_mode(opts)
+    );
+    if let Some((src_w, src_h)) = source.filter(|_| opts.fit) {
+        let (width, height) = extract_opts.fit(src_w, src_h);
+        let (cell_width, cell_height) = mode.cell_pixels();
+        let (cell_width, cell_height) = (
+            cell_width * opts.supersample,
+            cell_height * opts.supersample,
+        );
+        debug!(
+            "fitting {}x{} source pixels into {}x{} cells",
+            src_w,
+            src_h,
+            width.div_ceil(cell_width),
+            height.div_ceil(cell_height)
+        );
+    }
+
+    if opts.info {
+        return print_info(opts, sources, &inputs, &extract_opts, &renderer);
+    }
+
+    install_interrupt_handler(
+        owns_cache(opts).then(|| opts.cache_dir.clone()),
+        inputs
+            .iter()
+            .filter_map(|input| input.downloaded().map(Path::to_owned))
+            .collect(),
+    )?;
+
+    let subtitles = load_subtitles(opts, mode.is_graphics())?;
+    let subtitles = subtitles.as_ref();
+    if opts.stream || opts.camera.is_some() {
+        if decoded_in_process(&inputs[0]) == Some(Decoded::ImageSequence) {
+            bail!("--stream needs a video, not a folder of images");
+        }
+        if opts.auto_levels && opts.auto_levels_mode == AutoLevelsMode::Global {
+            warn!(
+                "Streamed frames can't be sampled ahead, --auto-levels stretches each on its own"
+            );
+        }
+        let fps = frame_rate(opts, &inputs[0]) / opts.sample_rate as f64;
+        return stream(opts, &inputs[0], &extract_opts, &renderer, subtitles, fps);
+    }
+    let _cache_lock = lock_cache(opts)?;
+
+    // a contact sheet renders few frames, and at another size than for playback
+    let render_cache = (opts.render_cache && opts.contact_sheet.is_none())
+        .then(|| render_cache_entry(opts, &inputs, &renderer, &extract_opts))
+        .flatten();
+    if let Some(recording) = render_cache.as_ref().and_then(RenderCache::load) {
+        info!("Playing frames rendered by an earlier run");
+        if saves_frames(opts) {
+            return save_recording(opts, &recording, subtitles);
+        }
+        let audio = audio_track(opts, &inputs, &extract_opts)?;
+        let playback_opts = playback_options(
+            opts,
+            &renderer,
+            subtitles,
+            recording.frame_rate,
+            recording.frame_delays,
+            audio,
+        );
+        play_frames(&recording.frames, &playback_opts)?;
+        if owns_cache(opts) && Path::new(&opts.cache_dir).exists() {
+            remove_cache(&opts.cache_dir)?;
+        }
+        return Ok(());
+    }
+
+    // a single input is extracted straight into the cache, several into one folder each
+    let clip_dirs: Vec<String> = if inputs.len() == 1 {
+        vec![opts.cache_dir.clone()]
+    } else {
+        if opts.reuse_cache {
+            fs::create_dir_all(&opts.cache_dir)
+                .with_context(|| format!("cannot use cache directory {}", opts.cache_dir))?;
+        } else {
+            make_dir(&opts.cache_dir)?;
+        }
+        (1..=inputs.len())
+            .map(|n| format!("{}/clip-{:03}", opts.cache_dir, n))
+            .collect()
+    };
+    let clips = sources
+        .iter()
+        .zip(&inputs)
+        .zip(&clip_dirs)
+        .map(|((source, input), dir)| extract_clip(opts, source, input, dir, &extract_opts))
+        .collect::<Result<Vec<_>>>()?;
+
+    // clips play back to back in the order they were given
+    let frame_files: Vec<PathBuf> = clips
+        .iter()
+        .flat_map(|clip| clip.frame_files.iter().cloned())
+        .collect();
+    let renderer = match opts.auto_levels_mode {
+        AutoLevelsMode::Global if opts.auto_levels => with_global_levels(renderer, &frame_files),
+        _ => renderer,
+    };
+    let renderer = with_palette(renderer, &frame_files);
+    let fps = clips[0].fps;
+    let frame_delays = if opts.fps.is_some() {
+        // an explicit --fps wins over the sources' own timing
+        None
+    } else if clips
+        .iter()
+        .all(|clip| clip.frame_delays.is_none() && clip.fps == fps)
+    {
+        None
+    } else {
+        // clips with different frame rates only line up with per-frame delays
+        Some(clips.iter().flat_map(Clip::frame_delays).collect())
+    };
+
+    if let Some(columns) = opts.contact_sheet {
+        print_contact_sheet(opts, &frame_files, &renderer, fps, frame_delays, columns)?;
+        if !opts.reuse_cache {
+            remove_cache(&opts.cache_dir)?;
+        }
+        return Ok(());
+    }
+
+    // decode the frames as they play rather than run out of memory buffering them
+    let lazy = (opts.lazy && !opts.step)
+        || !buffer_fits(
+            frame_files.len(),
+            renderer.frame_len(),
+            memory_limit(opts.max_memory, available_memory),
+            saves_frames(opts) || opts.step,
+        )?;
+    // buffered frames are all rendered before playback starts, and only those are kept for
+    // later runs
+    let rendered = (saves_frames(opts) || !lazy).then(|| Recording {
+        frame_rate: fps,
+        size: Some(renderer.size()),
+        frames: render_all(opts, &frame_files, &renderer),
+        frame_delays: frame_delays.clone(),
+    });
+    if let (Some(render_cache), Some(recording)) = (&render_cache, &rendered) {
+        if let Err(e) = render_cache.store(recording) {
+            warn!("Rendered frames won't be reused: {:#}", e);
+        }
+    }
+
+    if saves_frames(opts) {
+        save_recording(
+            opts,
+            rendered.as_ref().expect("frames are rendered for --output"),
+            subtitles,
+        )?;
+    } else {
+        let audio = audio_track(opts, &inputs, &extract_opts)?;
+        match &rendered {
+            Some(recording) => play_frames(
+                &recording.frames,
+                &playback_options(opts, &renderer, subtitles, fps, frame_delays, audio),
+            )?,
+            None => display_loop(
+                &frame_files,
+                &renderer,
+                &PlaybackOptions {
+                    // without buffering only a few frames are rendered ahead
+                    prebuffer: Some(opts.prebuffer),
+                    ..playback_options(opts, &renderer, subtitles, fps, frame_delays, audio)
+                },
+            )?,
+        }
+    }
+
+    // clean up temporary directory before exiting, unless it's meant to be reused
+    if !opts.reuse_cache {
+        remove_cache(&opts.cache_dir)?;
+    }
+
+    Ok(())
+}
+
+/// Whether `NO_COLOR` asks for no color at all, whatever the options say.
+fn no_color() -> bool {
+    // https://no-color.org: any non-empty NO_COLOR turns color off
+    std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty())
+}
+
+/// The color mode asked for, unless `NO_COLOR` is set.
+fn color_mode(opts: &Opts) -> ColorMode {
+    if no_color() {
+        ColorMode::Mono
+    } else if opts.color || (opts.blocks && opts.color_mode == ColorMode::Mono) {
+        ColorMode::Truecolor
+    } else {
+        opts.color_mode
+    }
+}
+
+/// The render mode picked by the flags, warning about graphics the terminal likely can't show.
+fn render_mode(opts: &Opts) -> RenderMode {
+    if opts.sixel {
+        if !likely_supports_sixel() {
+            warn!("This terminal might not support Sixel graphics, the video may not show up");
+        }
+        let (cell_width, cell_height) = cell_size().unwrap_or(CELL_SIZE);
+        RenderMode::Sixel {
+            cell_width,
+            cell_height,
+        }
+    } else if opts.kitty {
+        if !likely_supports_kitty() {
+            warn!("This terminal might not support the Kitty graphics protocol, the video may not show up");
+        }
+        let (cell_width, cell_height) = cell_size().unwrap_or(CELL_SIZE);
+        RenderMode::Kitty {
+            cell_width,
+            cell_height,
+        }
+    } else if opts.half_block {
+        RenderMode::HalfBlock
+    } else if opts.blocks {
+        RenderMode::Blocks
+    } else if opts.braille {
+        RenderMode::Braille {
+            threshold: opts.braille_threshold,
+        }
+    } else {
+        RenderMode::Ascii
+    }
+}
+
+/// What the playback size of `mode` depends on, for frames of `source_size` pixels before
+/// `--crop` and `--rotate`.
+fn layout(opts: &Opts, mode: RenderMode, source_size: Option<(u32, u32)>) -> Layout {
+    let source = source_size.map(|(width, height)| {
+        let (width, height) = match opts.crop {
+            Some(crop) => crop.size_within(width, height),
+            None => (width, height),
+        };
+        transform(opts).size(width, height)
+    });
+    Layout {
+        width: opts.width,
+        height: opts.height,
+        fit: opts.fit,
+        force: opts.force,
+        terminal: term_size::dimensions().map(|(w, h)| (w as u32, h as u32)),
+        source,
+        cell_pixels: mode.cell_pixels(),
+        pixel_aspect: pixel_aspect(opts, mode),
+    }
+}
+
+/// Prints thumbnails of frames sampled evenly across `frame_files`, `columns` of them a row,
+/// each captioned with the position of its frame in playback.
+fn print_contact_sheet(
+    opts: &Opts,
+    frame_files: &[PathBuf],
+    renderer: &Renderer,
+    fps: f64,
+    frame_delays: Option<Vec<Duration>>,
+    columns: u32,
+) -> Result<()> {
+    let samples = opts.sheet_frames.unwrap_or(columns * sheet::ROWS) as usize;
+    let indices = sample_indices(frame_files.len(), samples);
+    let thumbnails = indices
+        .par_iter()
+        .map(|&index| {
+            let path = &frame_files[index];
+            let frame = image::open(path)
+                .with_context(|| format!("could not decode {}", path.display()))?;
+            let position = playback_position(index, frame_delays.as_deref(), fps, opts.speed);
+            let caption = format_timestamp(position);
+            Ok((renderer.render_frame(&frame), caption))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    print!(
+        "{}",
+        tile(&thumbnails, columns as usize, sheet::GAP as usize)
+    );
+    Ok(())
+}
+
+/// Height to width ratio of the area a pixel of `mode`'s pixel grid covers on screen.
+fn pixel_aspect(opts: &Opts, mode: RenderMode) -> f32 {
+    if mode.is_graphics() {
+        // image pixels are square
+        1.0
+    } else {
+        let (cell_width, cell_height) = mode.cell_pixels();
+        let cell_aspect = if opts.no_cell_correct {
+            1.0
+        } else {
+            opts.cell_aspect
+        };
+        cell_aspect * cell_width as f32 / cell_height as f32
+    }
+}
+
+/// The size of the frames of `input` in pixels, if it can be found out.
+fn source_size(opts: &Opts, input: &Input) -> Option<(u32, u32)> {
+    match decoded_in_process(input) {
+        Some(Decoded::Gif) => image::image_dimensions(&input.path).ok(),
+        Some(Decoded::ImageSequence) => list_images(&input.path)
+            .ok()?
+            .first()
+            .and_then(|image| image::image_dimensions(image).ok()),
+        None => probe(opts, input).dimensions,
+    }
+}
+
+/// Frames sampled across the video to find the luminosity range of `--auto-levels-mode global`.
+const AUTO_LEVELS_SAMPLES: usize = 16;
+
+/// `renderer` stretching every frame by the luminosity range of frames sampled evenly across
+/// `frame_files`, so the picture doesn't pump as the content of single frames changes.
+fn with_global_levels(renderer: Renderer, frame_files: &[PathBuf]) -> Renderer {
+    let options = renderer.options();
+    let samples = frame_files.len().min(AUTO_LEVELS_SAMPLES);
+    let (lo, hi) = (0..samples)
+        .into_par_iter()
+        .filter_map(|n| image::open(&frame_files[n * frame_files.len() / samples]).ok())
+        .map(|frame| luma_range(&frame.to_rgb8(), options.luma_mode))
+        .reduce(
+            || (u8::MAX, u8::MIN),
+            |(lo, hi), (frame_lo, frame_hi)| (lo.min(frame_lo), hi.max(frame_hi)),
+        );
+    Renderer::new(RenderOptions {
+        auto_levels: Some(AutoLevels::Range(lo, hi)),
+        ..options.clone()
+    })
+}
+
+/// Number of frames, spread evenly across the video, the `--palette-size` palette is built
+/// from.
+const PALETTE_SAMPLES: usize = 16;
+
+/// `renderer` with the `--palette-size` palette of a sample of the frames, when asked for.
+fn with_palette(renderer: Renderer, frame_files: &[PathBuf]) -> Renderer {
+    if renderer.options().palette_size.is_none() {
+        return renderer;
+    }
+    let samples = frame_files.len().min(PALETTE_SAMPLES);
+    let frames: Vec<DynamicImage> = (0..samples)
+        .into_par_iter()
+        .filter_map(|n| image::open(&frame_files[n * frame_files.len() / samples]).ok())
+        .collect();
+    let renderer = renderer.with_palette(&frames);
+    if let Some(palette) = renderer.palette() {
+        debug!(
+            "palette of {} colors: {:?}",
+            palette.colors().len(),
+            palette.colors()
+        );
+    }
+    renderer
+}
+
+/// How `--auto-levels` finds the luminosity range to stretch.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum AutoLevelsMode {
+    /// Every frame by its own range, following changes in exposure
+    Frame,
+    /// Every frame by the range of a sample across the video, keeping brightness consistent
+    Global,
+}
+
+/// Characters of the ramp, from `--charset` or the preset.
+fn charset(opts: &Opts) -> Vec<char> {
+    opts.charset
+        .as_deref()
+        .unwrap_or_else(|| opts.charset_preset.chars())
+        .chars()
+        .collect()
+}
+
+/// How `--rotate`, `--flip-h` and `--flip-v` turn the frames.
+fn transform(opts: &Opts) -> Transform {
+    Transform {
+        rotate: opts.rotate.unwrap_or_default(),
+        flip_h: opts.flip_h,
+        flip_v: opts.flip_v,
+    }
+}
+
+/// Warns about characters `--ramp coverage` leaves out as their coverage isn't known.
+fn warn_uncovered(opts: &Opts) {
+    if opts.ramp != RampMode::Coverage {
+        return;
+    }
+    let uncovered: String = charset(opts)
+        .into_iter()
+        .filter(|&ch| coverage_of(ch, &opts.coverage).is_none())
+        .collect();
+    if !uncovered.is_empty() {
+        warn!(
+            "The coverage of \"{}\" isn't known, leaving them out of the ramp. Give it with --coverage",
+            uncovered
+        );
+    }
+}
+
+/// The renderer for `mode` at `size`, along with the options extracting frames at the
+/// resolution of its pixel grid.
+fn renderer(
+    opts: &Opts,
+    (w, h): (u32, u32),
+    mode: RenderMode,
+    color_mode: ColorMode,
+) -> (Renderer, ExtractOptions) {
+    // a Sixel image reaching the bottom row scrolls the terminal, so leave that row free,
+    // which --fit already did
+    let h = if matches!(mode, RenderMode::Sixel { .. }) && opts.height.is_none() && !opts.fit {
+        h.saturating_sub(1).max(1)
+    } else {
+        h
+    };
+
+    let charset = charset(opts);
+
+    let cell_aspect = (opts.keep_aspect || opts.fit).then(|| pixel_aspect(opts, mode));
+
+    let renderer = Renderer::new(RenderOptions {
+        width: w,
+        height: h,
+        mode,
+        color_mode,
+        luma_mode: opts.luma_mode,
+        map_channel: opts.map_channel,
+        charset,
+        ramp: opts.ramp,
+        coverage: opts.coverage.clone(),
+        brightness: opts.brightness,
+        contrast: opts.contrast,
+        gamma: opts.gamma,
+        invert: opts.invert,
+        levels: opts.levels,
+        auto_levels: opts.auto_levels.then_some(AutoLevels::PerFrame),
+        dither: opts.dither,
+        edges: opts.edges,
+        edge_threshold: opts.edge_threshold,
+        smooth: opts.smooth,
+        smooth_threshold: opts.smooth_threshold,
+        background: opts.background,
+        tint: opts.tint.filter(|_| !no_color()),
+        palette_size: opts.palette_size.filter(|_| color_mode != ColorMode::Mono),
+        supersample: opts.supersample,
+        alpha_threshold: opts.alpha_threshold,
+        fade: opts.fade,
+    });
+    // frames are extracted at the resolution of the render mode's pixel grid, each pixel
+    // of which covers only part of a character cell, or with --supersample several pixels
+    // for every one of the grid
+    let (width, height) = renderer.options().frame_size();
+    let extract_opts = ExtractOptions {
+        ffmpeg: opts.ffmpeg_path.clone(),
+        width,
+        height,
+        cell_aspect,
+        start: opts.start,
+        end: opts.end,
+        crop: opts.crop,
+        sample_rate: opts.sample_rate,
+        transform: transform(opts),
+        scale_filter: opts.scale_filter,
+        ffmpeg_args: opts.ffmpeg_args.clone(),
+        interpolation: opts
+            .interpolate
+            .zip(opts.fps)
+            .map(|(mode, frame_rate)| Interpolation { mode, frame_rate }),
+        tonemap: opts.tonemap,
+    };
+    (renderer, extract_opts)
+}
+
+/// Extracts the frames of the input once and reports how fast each text render mode turns
+/// them into text, leaving out decoding and the display.
+fn bench(opts: &Opts, bench_opts: &BenchOpts) -> Result<()> {
+    let source = match opts.input.as_slice() {
+        [source] => source,
+        _ => bail!("bench takes a single input"),
+    };
+    check_ffmpeg(opts, std::slice::from_ref(source))?;
+    let input = Input::resolve(source, opts.download)?;
+    warn_uncovered(opts);
+    let color = match color_mode(opts) {
+        ColorMode::Mono => ColorMode::Truecolor,
+        color => color,
+    };
+    let braille = RenderMode::Braille {
+        threshold: opts.braille_threshold,
+    };
+    let modes = vec![
+        ("mono", RenderMode::Ascii, ColorMode::Mono),
+        ("color", RenderMode::Ascii, color),
+        ("half-block", RenderMode::HalfBlock, color),
+        ("blocks", RenderMode::Blocks, color),
+        ("braille", braille, ColorMode::Mono),
+    ];
+
+    install_interrupt_handler(
+        owns_cache(opts).then(|| opts.cache_dir.clone()),
+        input.downloaded().map(Path::to_owned).into_iter().collect(),
+    )?;
+    let _cache_lock = lock_cache(opts)?;
+    // Braille has the finest pixel grid, the frames are scaled down for the other modes
+    let size = layout(opts, RenderMode::Ascii, source_size(opts, &input)).playback_size(false);
+    let (_, extract_opts) = renderer(opts, size, braille, ColorMode::Mono);
+    let clip = extract_clip(opts, source, &input, &opts.cache_dir, &extract_opts)?;
+    let frames: Vec<DynamicImage> = clip
+        .frame_files
+        .par_iter()
+        .filter_map(|path| match image::open(path) {
+            Ok(frame) => Some(frame),
+            Err(e) => {
+                warn!("Skipping frame {}: {}", path.display(), e);
+                None
+            }
+        })
+        .collect();
+    if frames.is_empty() {
+        bail!("no frames could be read from {}", source);
+    }
+
+    println!(
+        "Rendering {} frames of {}x{} characters {} times in each mode",
+        frames.len(),
+        size.0,
+        size.1,
+        bench_opts.rounds
+    );
+    println!(
+        "{:<12}{:>10}{:>10}{:>10}{:>10}",
+        "mode", "frames/s", "min", "mean", "max"
+    );
+    let millis = |duration: Duration| format!("{:.2}ms", duration.as_secs_f64() * 1000.0);
+    for (name, mode, color_mode) in modes {
+        let (renderer, _) = renderer(opts, size, mode, color_mode);
+        if let Some(timings) = measure(&frames, &renderer, bench_opts.rounds) {
+            println!(
+                "{:<12}{:>10.1}{:>10}{:>10}{:>10}",
+                name,
+                timings.frames_per_second(),
+                millis(timings.min),
+                millis(timings.mean),
+                millis(timings.max)
+            );
+        }
+    }
+
+    if !opts.reuse_cache {
+        remove_cache(&opts.cache_dir)?;
+    }
+    Ok(())
+}
+
+/// Reads the `--subtitle` file, with the cues moved to line up with `--start`. Captions
+/// can't be drawn over terminal graphics.
+fn load_subtitles(opts: &Opts, graphics: bool) -> Result<Option<Subtitles>> {
+    let Some(path) = &opts.subtitle else {
+        return Ok(None);
+    };
+    if graphics {
+        warn!("Subtitles can't be drawn over Sixel or Kitty graphics, playing without them");
+        return Ok(None);
+    }
+    let subtitles = Subtitles::load(path)?;
+    Ok(Some(subtitles.shifted(opts.start.unwrap_or_default())))
+}
+
+/// Extracts the audio track to play along with `--audio`, if there is one that can be played.
+fn audio_track(
+    opts: &Opts,
+    inputs: &[Input],
+    extract_opts: &ExtractOptions,
+) -> Result<Option<String>> {
+    if !opts.audio {
+        return Ok(None);
+    }
+    if opts.speed != 1.0 {
+        warn!("The audio track can't be played at a different speed, playing without sound");
+        return Ok(None);
+    }
+    if opts.reverse || opts.bounce {
+        warn!("The audio track can't be played in reverse, playing without sound");
+        return Ok(None);
+    }
+    if opts.step {
+        return Ok(None);
+    }
+    if inputs.len() > 1 {
+        warn!("Audio can only be played for a single input, playing without sound");
+        return Ok(None);
+    }
+    let input = &inputs[0];
+    if !interactive() || decoded_in_process(input).is_some() {
+        return Ok(None);
+    }
+    if input.stdin {
+        warn!("Piped video has already been read for its frames, playing without sound");
+        return Ok(None);
+    }
+    // frames played from the render cache weren't extracted, so there is no cache yet
+    if !Path::new(&opts.cache_dir).is_dir() {
+        fs::create_dir_all(&opts.cache_dir)
+            .with_context(|| format!("could not create directory {}", opts.cache_dir))?;
+    }
+    Ok(extract_audio(&input.path, &opts.cache_dir, extract_opts))
+}
+
+/// The `--render-cache` entry for playing `inputs` with these settings. Remote inputs can
+/// change without notice, their frames aren't kept.
+fn render_cache_entry(
+    opts: &Opts,
+    inputs: &[Input],
+    renderer: &Renderer,
+    extract_opts: &ExtractOptions,
+) -> Option<RenderCache> {
+    let mut key = String::new();
+    for input in inputs {
+        if input.remote || input.stdin {
+            return None;
+        }
+        let path = fs::canonicalize(&input.path).ok()?;
+        let metadata = fs::metadata(&path).ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        writeln!(
+            key,
+            "{} {} {}",
+            path.display(),
+            metadata.len(),
+            modified.as_nanos()
+        )
+        .ok()?;
+    }
+    write!(
+        key,
+        "{:?}\n{:?}\nfps {:?}\nauto-levels {:?}",
+        renderer.options(),
+        extract_opts,
+        opts.fps,
+        opts.auto_levels.then_some(opts.auto_levels_mode)
+    )
+    .ok()?;
+    RenderCache::new(key)
+}
+
+/// Kinds of input ffmpeg isn't needed for.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Decoded {
+    Gif,
+    ImageSequence,
+}
+
+/// How `input` is decoded without ffmpeg, None if it is a video for ffmpeg.
+fn decoded_in_process(input: &Input) -> Option<Decoded> {
+    if input.remote {
+        None
+    } else if is_gif(&input.path) {
+        Some(Decoded::Gif)
+    } else if is_image_sequence(&input.path) {
+        Some(Decoded::ImageSequence)
+    } else {
+        None
+    }
+}
+
+/// Fails unless ffmpeg and ffprobe can be run, before anything is downloaded or extracted.
+/// GIFs and image folders are decoded in-process, so playing only those works without them.
+fn check_ffmpeg(opts: &Opts, sources: &[String]) -> Result<()> {
+    let in_process =
+        |source: &String| !is_url(source) && (is_gif(source) || is_image_sequence(source));
+    if sources.iter().all(in_process) {
+        return Ok(());
+    }
+    check_dependencies(&opts.ffmpeg_path, &opts.ffprobe_path)
+}
+
+/// What ffprobe knows about `input`. Piped video can't be read twice and cameras can't be
+/// probed without their format, so nothing is known about them.
+fn probe(opts: &Opts, input: &Input) -> VideoInfo {
+    if input.stdin || input.format.is_some() {
+        VideoInfo::default()
+    } else {
+        probe_video(&opts.ffprobe_path, &input.path)
+    }
+}
+
+/// `extract_opts` for `input`, tone mapped to SDR if ffprobe reports it as HDR and neither
+/// `--tonemap` nor `--no-tonemap` decided already. Tone mapping takes ffmpeg's `zscale`
+/// filter, which not every build has.
+fn tone_mapped(
+    opts: &Opts,
+    input: &Input,
+    extract_opts: &ExtractOptions,
+) -> Result<ExtractOptions> {
+    if opts.no_tonemap || decoded_in_process(input).is_some() {
+        return Ok(extract_opts.clone());
+    }
+    let zscale = || has_filter(&opts.ffmpeg_path, "zscale");
+    let tonemap = match extract_opts.tonemap {
+        Some(tonemap) => {
+            if !zscale() {
+                bail!(
+                    "--tonemap needs ffmpeg's zscale filter, which {} was built without",
+                    opts.ffmpeg_path.display()
+                );
+            }
+            Some(tonemap)
+        }
+        None if probe(opts, input).is_hdr() => {
+            if zscale() {
+                info!(
+                    "{} is HDR, tone mapping it to SDR, --no-tonemap turns that off",
+                    input.path
+                );
+                Some(Tonemap::default())
+            } else {
+                warn!(
+                    "{} is HDR, but {} lacks the zscale filter to tone map it, so it may look washed out",
+                    input.path,
+                    opts.ffmpeg_path.display()
+                );
+                None
+            }
+        }
+        None => None,
+    };
+    Ok(ExtractOptions {
+        tonemap,
+        ..extract_opts.clone()
+    })
+}
+
+/// Frame rate to play an input at, `--fps` if given.
+fn frame_rate(opts: &Opts, input: &Input) -> f64 {
+    opts.fps.unwrap_or_else(|| {
+        if decoded_in_process(input).is_some() {
+            30.0
+        } else {
+            probe(opts, input).frame_rate.unwrap_or(30.0)
+        }
+    })
+}
+
+/// What playing the inputs would take, printed by `--info`.
+#[derive(Serialize)]
+struct Info {
+    inputs: Vec<InputInfo>,
+    /// Playback area in character cells
+    width: u32,
+    height: u32,
+    /// Character ramp, from darkest to brightest
+    charset: String,
+    /// Totals over all inputs, unknown if they are for any of them
+    frames: Option<u64>,
+    /// Playback time in seconds, at the playback speed
+    duration: Option<f64>,
+    /// Bytes the rendered frames take up when they are all buffered, i.e. without `--lazy`
+    memory: Option<u64>,
+    available_memory: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct InputInfo {
+    source: String,
+    /// What ffmpeg reads, a direct video URL for YouTube links or the file a video was
+    /// downloaded to
+    path: String,
+    codec: Option<String>,
+    source_width: Option<u32>,
+    source_height: Option<u32>,
+    /// Frame rate detected by ffprobe
+    source_fps: Option<f64>,
+    /// Whether ffprobe reports HDR frames, which get tone mapped
+    hdr: bool,
+    /// Playback frame rate
+    fps: f64,
+    /// Size of the extracted frames in pixels
+    frame_width: u32,
+    frame_height: u32,
+    frames: Option<u64>,
+    duration: Option<f64>,
+}
+
+/// Probes the inputs and prints the parameters playback would run with, without extracting
+/// anything.
+fn print_info(
+    opts: &Opts,
+    sources: &[String],
+    inputs: &[Input],
+    extract_opts: &ExtractOptions,
+    renderer: &Renderer,
+) -> Result<()> {
+    let mut infos = Vec::new();
+    for (source, input) in sources.iter().zip(inputs) {
+        let mut images = None;
+        let (probed, delays) = match decoded_in_process(input) {
+            Some(Decoded::Gif) => {
+                let probed = VideoInfo {
+                    codec: Some(String::from("gif")),
+                    dimensions: image::image_dimensions(&input.path).ok(),
+                    ..VideoInfo::default()
+                };
+                (probed, Some(gif_delays(&input.path, extract_opts)?))
+            }
+            Some(Decoded::ImageSequence) => {
+                let listed = list_images(&input.path)?;
+                let probed = VideoInfo {
+                    dimensions: listed
+                        .first()
+                        .and_then(|image| image::image_dimensions(image).ok()),
+                    ..VideoInfo::default()
+                };
+                images = Some(listed.len());
+                (probed, None)
+            }
+            None => (probe(opts, input), None),
+        };
+        // variable frame rate videos keep every frame with its own timing, which comes to
+        // their average rate
+        let source_fps = if probed.is_variable_frame_rate() && opts.fps.is_none() {
+            probed.average_frame_rate
+        } else {
+            probed.frame_rate
+        };
+        // GIFs and images have no frame rate, like in frame_rate()
+        let fps = opts.fps.unwrap_or_else(|| source_fps.unwrap_or(30.0));
+        let frames = match (&delays, images) {
+            (Some(delays), _) => Some(delays.len() as u64),
+            (None, Some(images)) => {
+                Some(extract_opts.sampled(trimmed_images(images, extract_opts, fps).len() as u64))
+            }
+            // ffmpeg extracts every frame of the source, whatever the playback frame rate
+            (None, None) => source_fps
+                .zip(probed.duration)
+                .map(|(source_fps, duration)| {
+                    let frames = extract_opts.trimmed_duration(duration).as_secs_f64() * source_fps;
+                    extract_opts.sampled(frames.round() as u64)
+                }),
+        };
+        // sampled frames are shown for as long as the ones dropped after them
+        let fps = fps / opts.sample_rate as f64;
+        let duration = match delays.filter(|_| opts.fps.is_none()) {
+            Some(delays) => Some(delays.iter().sum::<Duration>().as_secs_f64()),
+            None => frames.map(|frames| frames as f64 / fps),
+        }
+        .map(|duration| duration / opts.speed);
+
+        let (frame_width, frame_height) = match (extract_opts.cell_aspect, probed.dimensions) {
+            (Some(_), Some((width, height))) => extract_opts.fit(width, height),
+            _ => (extract_opts.width, extract_opts.height),
+        };
+        infos.push(InputInfo {
+            source: source.clone(),
+            path: input.path.clone(),
+            hdr: probed.is_hdr(),
+            codec: probed.codec,
+            source_width: probed.dimensions.map(|(width, _)| width),
+            source_height: probed.dimensions.map(|(_, height)| height),
+            source_fps: probed.frame_rate,
+            fps,
+            frame_width,
+            frame_height,
+            frames,
+            duration,
+        });
+    }
+
+    let frames: Option<u64> = infos.iter().map(|info| info.frames).sum();
+    let info = Info {
+        width: renderer.options().width,
+        height: renderer.options().height,
+        charset: renderer.options().charset.iter().collect(),
+        frames,
+        duration: infos.iter().map(|info| info.duration).sum(),
+        memory: frames.map(|frames| frames * renderer.frame_len() as u64),
+        available_memory: available_memory(),
+        inputs: infos,
+    };
+
+    if let (Some(memory), Some(limit)) =
+        (info.memory, memory_limit(opts.max_memory, available_memory))
+    {
+        if memory > limit && !opts.lazy {
+            warn!(
+                "Buffering all frames would take more than the {} --max-memory allows, they would be rendered as they play",
+                HumanBytes(limit)
+            );
+        }
+    }
+    if opts.json {
+        println!("{}", serde_json::to_string_pretty(&info)?);
+        return Ok(());
+    }
+
+    let unknown = || String::from("unknown");
+    let time = |secs: Option<f64>| {
+        secs.and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+            .map_or_else(unknown, format_timestamp)
+    };
+    for input in &info.inputs {
+        let mut probed = Vec::new();
+        probed.extend(input.codec.clone());
+        if let (Some(width), Some(height)) = (input.source_width, input.source_height) {
+            probed.push(format!("{}x{}", width, height));
+        }
+        probed.extend(
+            input
+                .source_fps
+                .map(|fps| format!("{} fps", format_fps(fps))),
+        );
+        if input.hdr {
+            probed.push(String::from("HDR"));
+        }
+        println!("{}: {}", input.source, probed.join(", "));
+        println!(
+            "  plays at {} fps, {} frames of {}x{} pixels, {}",
+            format_fps(input.fps),
+            input
+                .frames
+                .map_or_else(unknown, |frames| frames.to_string()),
+            input.frame_width,
+            input.frame_height,
+            time(input.duration)
+        );
+    }
+    println!("Playback area: {}x{} characters", info.width, info.height);
+    if info.inputs.len() > 1 {
+        println!(
+            "Total: {} frames, {}",
+            info.frames
+                .map_or_else(unknown, |frames| frames.to_string()),
+            time(info.duration)
+        );
+    }
+    println!(
+        "Memory when buffered: {}{}",
+        info.memory
+            .map_or_else(unknown, |bytes| HumanBytes(bytes).to_string()),
+        info.available_memory
+            .map(|bytes| format!(" of {} available", HumanBytes(bytes)))
+            .unwrap_or_default()
+    );
+    Ok(())
+}
+
+/// Formats a frame rate with up to three decimals, 29.97 rather than 29.97002997002997.
+fn format_fps(fps: f64) -> String {
+    format!("{:.3}", fps)
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_owned()
+}
+
+/// The frames extracted from one input.
+struct Clip {
+    frame_files: Vec<PathBuf>,
+    /// Per-frame timing of sources that carry their own
+    frame_delays: Option<Vec<Duration>>,
+    fps: f64,
+}
+
+impl Clip {
+    /// How long each frame is shown, from the frame rate if there is no per-frame timing.
+    fn frame_delays(&self) -> Vec<Duration> {
+        match &self.frame_delays {
+            Some(delays) => delays.clone(),
+            None => vec![Duration::from_secs_f64(1.0 / self.fps); self.frame_files.len()],
+        }
+    }
+}
+
+/// Extracts the frames of `input` into `cache_dir`, or reuses the ones already there with
+/// `--reuse-cache`.
+fn extract_clip(
+    opts: &Opts,
+    source: &str,
+    input: &Input,
+    cache_dir: &str,
+    extract_opts: &ExtractOptions,
+) -> Result<Clip> {
+    let extract_opts = &tone_mapped(opts, input, extract_opts)?;
+    // GIFs carry their own per-frame delays, neither they nor images need ffprobe or ffmpeg
+    let decoded = decoded_in_process(input);
+    let fps = frame_rate(opts, input);
+    // sampled frames are shown for as long as the ones dropped after them
+    let playback_fps = fps / opts.sample_rate as f64;
+    let metadata = CacheMetadata::new(
+        source,
+        extract_opts.width,
+        extract_opts.height,
+        fps,
+        extract_opts,
+    );
+
+    // whatever is piped in next time may be a different video
+    let cached = (opts.reuse_cache && !input.stdin)
+        .then(|| metadata.load_matching(cache_dir))
+        .flatten();
+    if let Some(cached) = cached {
+        info!("Reusing frames cached in {}", cache_dir);
+        return Ok(Clip {
+            frame_files: list_frames(cache_dir)?,
+            frame_delays: cached.frame_delays(),
+            fps: playback_fps,
+        });
+    }
+
+    if extract_opts.interpolation.is_some() {
+        match decoded {
+            Some(_) => warn!(
+                "Only ffmpeg can interpolate frames, {} plays without it",
+                input.path
+            ),
+            None if opts.interpolate == Some(InterpolationMode::Motion) => {
+                warn!("Interpolating motion, extracting frames may take a while")
+            }
+            None => {}
+        }
+    }
+
+    make_dir(cache_dir)?;
+    let (frame_files, frame_delays) = match decoded {
+        Some(Decoded::Gif) => {
+            let (frame_files, delays) = extract_gif_frames(&input.path, cache_dir, extract_opts)?;
+            (frame_files, Some(delays))
+        }
+        Some(Decoded::ImageSequence) => (
+            extract_image_frames(&input.path, cache_dir, extract_opts, fps)?,
+            None,
+        ),
+        None => extract_video_frames(opts, input, cache_dir, extract_opts, fps).map_err(|e| {
+            if input.remote {
+                e.context(format!(
+                    "could not read {}, check the address and your network connection",
+                    source
+                ))
+            } else {
+                e
+            }
+        })?,
+    };
+    if frame_files.is_empty() {
+        bail!("no frames could be read from {}", source);
+    }
+    if opts.reuse_cache {
+        if let Err(e) = metadata
+            .with_frames(frame_files.len(), frame_delays.as_deref())
+            .write(cache_dir)
+        {
+            warn!("Frames won't be reused: {:#}", e);
+        }
+    }
+    Ok(Clip {
+        frame_files,
+        frame_delays,
+        fps: playback_fps,
+    })
+}
+
+/// Saves the frames of `recording`, with the captions of `subtitles` burned in.
+fn save_recording(opts: &Opts, recording: &Recording, subtitles: Option<&Subtitles>) -> Result<()> {
+    let captioned;
+    let recording = match subtitles {
+        Some(subtitles) => {
+            let period = Duration::from_secs_f64(1.0 / recording.frame_rate);
+            let mut position = Duration::ZERO;
+            let mut frames = Vec::with_capacity(recording.frames.len());
+            for (index, frame) in recording.frames.iter().enumerate() {
+                frames.push(subtitles.burn_in(frame, position).into_owned());
+                position += recording
+                    .frame_delays
+                    .as_ref()
+                    .and_then(|delays| delays.get(index))
+                    .copied()
+                    .unwrap_or(period);
+            }
+            captioned = Recording {
+                frame_rate: recording.frame_rate,
+                size: recording.size,
+                frames,
+                frame_delays: recording.frame_delays.clone(),
+            };
+            &captioned
+        }
+        None => recording,
+    };
+    if let Some(dir) = &opts.dump_frames {
+        recording.dump_frames(dir)?;
+        info!(
+            "Wrote {} frames to {}",
+            recording.frames.len(),
+            dir.display()
+        );
+    }
+    if let Some(path) = &opts.render_to {
+        return render_recording(opts, recording, path);
+    }
+    if let Some(output) = &opts.output {
+        recording.save(output)?;
+        info!(
+            "Saved {} frames to {}",
+            recording.frames.len(),
+            output.display()
+        );
+    }
+    Ok(())
+}
+
+/// Whether the rendered frames are saved, with `--output`, `--render-to` or `--dump-frames`,
+/// rather than played.
+fn saves_frames(opts: &Opts) -> bool {
+    opts.output.is_some() || opts.render_to.is_some() || opts.dump_frames.is_some()
+}
+
+/// Draws the frames of `recording` into the picture or video at `path` for `--render-to`.
+fn render_recording(opts: &Opts, recording: &Recording, path: &Path) -> Result<()> {
+    let font_path = match &opts.font {
+        Some(font) => font.clone(),
+        None => find_font().context("no monospace font found, pick one with --font")?,
+    };
+    let export_opts = ExportOptions {
+        font: load_font(&font_path)?,
+        font_size: opts.font_size,
+        theme: opts.theme,
+        ffmpeg: opts.ffmpeg_path.clone(),
+    };
+    export(recording, path, &export_opts)?;
+    info!(
+        "Drew {} frames into {}",
+        recording.frames.len(),
+        path.display()
+    );
+    Ok(())
+}
+
+/// Plays, or records with `--output`, frames piped straight from ffmpeg without any files.
+fn stream(
+    opts: &Opts,
+    input: &Input,
+    extract_opts: &ExtractOptions,
+    renderer: &Renderer,
+    subtitles: Option<&Subtitles>,
+    fps: f64,
+) -> Result<()> {
+    // frames have to be read back at their exact size
+    let source_size = extract_opts
+        .cell_aspect
+        .and_then(|_| probe(opts, input).dimensions);
+    let mut extract_opts = tone_mapped(opts, input, extract_opts)?.fitted(source_size);
+    if let Some(format) = input.format {
+        // the camera captures at the rate the frames are played at
+        let mut args = vec![
+            String::from("-f"),
+            format.to_owned(),
+            String::from("-framerate"),
+            format!("{}", frame_rate(opts, input)),
+        ];
+        args.append(&mut extract_opts.ffmpeg_args.input);
+        extract_opts.ffmpeg_args.input = args;
+    }
+    // frames drawn from their luminosity alone skip ffmpeg's conversion to color as well as
+    // weighting the channels back into luminosity, tone mapping leaves them in BT.709
+    let weighting = if extract_opts.tonemap.is_some() {
+        Some(LumaMode::Rec709)
+    } else {
+        probe(opts, input).luma_mode()
+    };
+    let pixel_format = if renderer.renders_luma(weighting) {
+        PixelFormat::Gray
+    } else {
+        PixelFormat::Rgb24
+    };
+    // ffmpeg seeks in front of the input, which is much faster than decoding up to the position
+    let open = |seek: Duration| {
+        let start = extract_opts.start.unwrap_or_default() + seek;
+        FrameStream::open(
+            &input.path,
+            &ExtractOptions {
+                // a live feed has no position to seek to
+                start: input.format.is_none().then_some(start),
+                ..extract_opts.clone()
+            },
+            pixel_format,
+        )
+    };
+    // every pass over the stream is smoothed from scratch
+    let render = |frames: FrameStream| {
+        let mut history = Hysteresis::default();
+        frames.map(move |frame| render_raw(renderer, frame, &mut history))
+    };
+    if input.format.is_some() {
+        // a camera that is busy or missing only says so in ffmpeg's log
+        let mut camera = open(Duration::ZERO)?;
+        if camera.next().is_none() {
+            camera
+                .finish()
+                .with_context(|| format!("could not capture from camera {}", input.path))?;
+            bail!("camera {} sent no frames", input.path);
+        }
+    }
+
+    if saves_frames(opts) {
+        return save_recording(
+            opts,
+            &Recording {
+                frame_rate: fps,
+                size: Some(renderer.size()),
+                frames: render(open(Duration::ZERO)?).collect(),
+                frame_delays: None,
+            },
+            subtitles,
+        );
+    }
+
+    // the stream runs from --start to --end or the end of the video
+    let length = opts
+        .timecode
+        .and_then(|_| probe(opts, input).duration)
+        .map(|duration| {
+            let end = extract_opts.end.map_or(duration, |end| end.min(duration));
+            end.saturating_sub(extract_opts.start.unwrap_or_default())
+        });
+    let playback_opts = PlaybackOptions {
+        frame_rate: fps,
+        prebuffer: Some(opts.prebuffer),
+        plays: plays(opts),
+        loop_delay: Duration::from_millis(opts.loop_delay),
+        diff: opts.diff,
+        speed: opts.speed,
+        stats: opts.stats,
+        timecode: opts.timecode,
+        length,
+        escapes: terminal_escapes(),
+        paced: interactive(),
+        graphics: renderer.options().mode.is_graphics(),
+        subtitles: subtitles.cloned(),
+        seek: seek_offset(opts),
+        strict: opts.strict,
+        scroll: opts.scroll,
+        ..PlaybackOptions::default()
+    };
+    play_stream(|seek| open(seek).map(render), &playback_opts)
+}
+
+/// Renders a frame streamed from ffmpeg, straight from its luminosity if it came as gray.
+fn render_raw(renderer: &Renderer, frame: RawFrame, history: &mut Hysteresis) -> String {
+    match frame.format {
+        PixelFormat::Gray => renderer.render_luma(&frame.data, frame.width, frame.height, history),
+        PixelFormat::Rgb24 => renderer.render_next_frame(&frame.into_image(), history),
+    }
+}
+
+/// Runs ffmpeg on the input with a progress bar, unless `--quiet` was given.
+fn extract_video_frames(
+    opts: &Opts,
+    input: &Input,
+    cache_dir: &str,
+    extract_opts: &ExtractOptions,
+    fps: f64,
+) -> Result<(Vec<PathBuf>, Option<Vec<Duration>>)> {
+    let probed = probe(opts, input);
+    let duration = probed.duration;
+    // frames of variable frame rate videos keep their own timing, unless --fps overrides it
+    let timed = probed.is_variable_frame_rate() && opts.fps.is_none();
+    let fps = match probed.average_frame_rate {
+        Some(average) if timed => average,
+        _ => fps,
+    };
+    let progress = if opts.quiet {
+        ProgressBar::hidden()
+    } else {
+        match duration {
+            Some(duration) => frame_progress(extract_opts.sampled(
+                (extract_opts.trimmed_duration(duration).as_secs_f64() * fps).round() as u64,
+            )),
+            // 时长未知, 只能显示已提取的帧数
+            None => ProgressBar::new_spinner().with_style(
+                ProgressStyle::with_template("{spinner} {msg}: {pos} frames")
+                    .expect("valid progress template"),
+            ),
+        }
+    };
+    progress.set_message("Extracting frames");
+    progress.enable_steady_tick(Duration::from_millis(100));
+
+    // seeking in remote videos is slow, and without a duration there is nothing to split;
+    // segments are cut by frame count, which only lines up at a constant frame rate;
+    // interpolation needs the frames on both sides of a cut
+    let interpolated = extract_opts.interpolation.is_some();
+    let jobs = match duration.filter(|_| !input.remote && !timed && !interpolated) {
+        Some(duration) => {
+            let segments = extract_opts.trimmed_duration(duration).as_secs_f64() / MIN_SEGMENT_SECS;
+            opts.jobs
+                .map_or_else(default_jobs, |jobs| jobs as usize)
+                .min(segments as usize)
+        }
+        None => 1,
+    };
+    debug!("ffmpeg processes extracting frames: {}", jobs.max(1));
+    let extracted = match duration {
+        _ if timed => extract_frames_timed(&input.path, cache_dir, extract_opts, |n| {
+            progress.set_position(n)
+        }),
+        Some(duration) if jobs > 1 => extract_frames_segmented(
+            &input.path,
+            cache_dir,
+            extract_opts,
+            duration,
+            fps,
+            jobs,
+            |n| progress.set_position(n),
+        )
+        .map(|frame_files| (frame_files, None)),
+        _ => extract_frames_with_progress(&input.path, cache_dir, extract_opts, |n| {
+            progress.set_position(n)
+        })
+        .map(|frame_files| (frame_files, None)),
+    };
+    progress.finish_and_clear();
+    extracted
+}
+
+/// A progress bar over `frames` frames, with the time left.
+fn frame_progress(frames: u64) -> ProgressBar {
+    ProgressBar::new(frames).with_style(
+        ProgressStyle::with_template("{msg} [{bar:40}] {pos}/{len} frames, {eta} left")
+            .expect("valid progress template")
+            .progress_chars("=> "),
+    )
+}
+
+/// Decodes and renders all of `frame_files` ahead of playback, which takes a while for long
+/// videos, with a progress bar unless `--quiet` was given or the frames aren't shown in a
+/// terminal.
+fn render_all(opts: &Opts, frame_files: &[PathBuf], renderer: &Renderer) -> Vec<String> {
+    let progress = if opts.quiet || !interactive() {
+        ProgressBar::hidden()
+    } else {
+        frame_progress(frame_files.len() as u64)
+    };
+    progress.set_message("Rendering frames");
+    let frames =
+        render_frames_with_progress(frame_files, renderer, |done| progress.set_position(done));
+    progress.finish_and_clear();
+    frames
+}
 
-    // 恢复终端设置
-    print!("\x1B[?7h"); // 重新启用行包装
-    print!("\x1B[?25h"); // 显示光标
-    print!("\x1B[H\x1B[2J"); // 清屏并回到开始位置
+/// Number of ffmpeg processes extracting frames at once when `--jobs` isn't given.
+fn default_jobs() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
 }
 
-// TODO make this less dumb
-fn get_pixel_char(luminosity: u8) -> char {
-    match luminosity {
-        0 => ' ',
-        1..=21 => '.',
-        22..=43 => ',',
-        44..=65 => '-',
-        66..=87 => '~',
-        88..=109 => ':',
-        110..=131 => ';',
-        132..=153 => '=',
-        154..=175 => '!',
-        176..=197 => '*',
-        198..=219 => '#',
-        220..=241 => '$',
-        _ => '@',
+/// How to play frames of `renderer` with the playback options given on the command line.
+fn playback_options(
+    opts: &Opts,
+    renderer: &Renderer,
+    subtitles: Option<&Subtitles>,
+    fps: f64,
+    frame_delays: Option<Vec<Duration>>,
+    audio: Option<String>,
+) -> PlaybackOptions {
+    PlaybackOptions {
+        frame_rate: fps,
+        prebuffer: None,
+        audio,
+        plays: plays(opts),
+        loop_delay: Duration::from_millis(opts.loop_delay),
+        frame_delays,
+        diff: opts.diff,
+        speed: opts.speed,
+        stats: opts.stats,
+        timecode: opts.timecode,
+        length: None,
+        escapes: terminal_escapes(),
+        paced: interactive(),
+        graphics: renderer.options().mode.is_graphics(),
+        reverse: opts.reverse,
+        bounce: opts.bounce,
+        subtitles: subtitles.cloned(),
+        seek: seek_offset(opts),
+        strict: opts.strict,
+        step: opts.step,
+        scroll: opts.scroll,
     }
 }
 
-fn clear_screen() {
-    // 使用移动光标到开始位置代替完全清屏
-    print!("\x1B[H");
+/// How far into the extracted frames `--seek` starts playback, as they begin at `--start`.
+fn seek_offset(opts: &Opts) -> Duration {
+    opts.seek.map_or(Duration::ZERO, |seek| {
+        seek.saturating_sub(opts.start.unwrap_or_default())
+    })
 }
@@ -0,0 +1,94 @@
+/*
+   This file is part of term-video.
+
+   term-video is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   term-video is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with term-video.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Keeping buffered frames within the memory the system can spare. Frames that don't fit are
+//! rendered as they play instead.
+
+use anyhow::{bail, Result};
+use indicatif::HumanBytes;
+use log::warn;
+
+/// Memory the system can hand out without swapping, if it reports any.
+pub fn available_memory() -> Option<u64> {
+    let mut system = sysinfo::System::new();
+    system.refresh_memory();
+    Some(system.available_memory()).filter(|&bytes| bytes > 0)
+}
+
+/// How many bytes buffered frames may take up: `max_mib` MiB as given with `--max-memory`,
+/// or half of what `available` reports.
+pub fn memory_limit(max_mib: Option<u64>, available: impl FnOnce() -> Option<u64>) -> Option<u64> {
+    match max_mib {
+        Some(mib) => Some(mib.saturating_mul(1024 * 1024)),
+        None => available().map(|bytes| bytes / 2),
+    }
+}
+
+/// Whether `frames` rendered frames of `frame_len` bytes fit in `limit` when they are all
+/// buffered. Playback that `must_buffer`, like saving the frames or `--step`, fails beyond
+/// the limit.
+pub fn buffer_fits(
+    frames: usize,
+    frame_len: usize,
+    limit: Option<u64>,
+    must_buffer: bool,
+) -> Result<bool> {
+    let Some(limit) = limit else {
+        return Ok(true);
+    };
+    let memory = (frames as u64).saturating_mul(frame_len as u64);
+    if memory <= limit {
+        return Ok(true);
+    }
+    if must_buffer {
+        bail!(
+            "the {} rendered frames would take {}, more than the {} --max-memory allows. A smaller --width and --height, a --sample-rate or a higher --max-memory help",
+            frames,
+            HumanBytes(memory),
+            HumanBytes(limit)
+        );
+    }
+    warn!(
+        "Buffering the {} frames would take {}, more than the {} --max-memory allows, rendering them as they play instead",
+        frames,
+        HumanBytes(memory),
+        HumanBytes(limit)
+    );
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn max_memory_is_given_in_mebibytes() {
+        assert_eq!(memory_limit(Some(2), || Some(100)), Some(2 * 1024 * 1024));
+        assert_eq!(memory_limit(Some(u64::MAX), || None), Some(u64::MAX));
+        assert_eq!(memory_limit(None, || Some(100)), Some(50));
+        assert_eq!(memory_limit(None, || None), None);
+    }
+
+    #[test]
+    fn frames_beyond_the_limit_are_not_buffered() {
+        assert!(buffer_fits(10, 100, Some(1000), false).unwrap());
+        assert!(!buffer_fits(11, 100, Some(1000), false).unwrap());
+        assert!(buffer_fits(11, 100, Some(1000), true).is_err());
+        assert!(buffer_fits(usize::MAX, usize::MAX, None, true).unwrap());
+        assert!(!buffer_fits(usize::MAX, usize::MAX, Some(u64::MAX - 1), false).unwrap());
+    }
+}
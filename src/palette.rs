@@ -0,0 +1,179 @@
+/*
+   This file is part of term-video.
+
+   term-video is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   term-video is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with term-video.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Fixed color palettes built by median cut, so the same shade gets the same color in every
+//! frame instead of flickering between neighbouring ones.
+
+// bits per channel of the colors the nearest palette entry is looked up by
+const LOOKUP_BITS: u32 = 5;
+
+/// A set of colors every pixel is snapped to.
+#[derive(Clone)]
+pub struct Palette {
+    colors: Vec<[u8; 3]>,
+    /// Index of the nearest color for every color with `LOOKUP_BITS` per channel
+    lookup: Vec<u8>,
+}
+
+impl Palette {
+    /// Builds a palette of at most `size` colors, between 1 and 256, that represents
+    /// `pixels` well.
+    ///
+    /// Starting from one box holding all pixels, the box spanning the widest range on any
+    /// channel is split at the median of that channel until there are `size` boxes, or none
+    /// is left with more than one color in it. Every box contributes the average of its
+    /// pixels.
+    pub fn median_cut(mut pixels: Vec<[u8; 3]>, size: usize) -> Palette {
+        debug_assert!((1..=256).contains(&size), "palette size out of range");
+        let mut boxes: Vec<&mut [[u8; 3]]> = Vec::with_capacity(size);
+        if !pixels.is_empty() {
+            boxes.push(&mut pixels);
+        }
+
+        while boxes.len() < size {
+            let widest = boxes
+                .iter()
+                .enumerate()
+                .map(|(i, pixels)| (i, widest_channel(pixels)))
+                .max_by_key(|&(_, (_, range))| range);
+            let Some((i, (channel, range))) = widest else {
+                break;
+            };
+            if range == 0 {
+                break;
+            }
+            let pixels = boxes.swap_remove(i);
+            pixels.sort_unstable_by_key(|pixel| pixel[channel]);
+            let (lower, upper) = pixels.split_at_mut(pixels.len() / 2);
+            boxes.push(lower);
+            boxes.push(upper);
+        }
+
+        let mut colors: Vec<[u8; 3]> = boxes.iter().map(|pixels| average(pixels)).collect();
+        if colors.is_empty() {
+            colors.push([0, 0, 0]);
+        }
+        Palette::new(colors)
+    }
+
+    /// A palette of the given colors, at most 256 and at least one.
+    pub fn new(colors: Vec<[u8; 3]>) -> Palette {
+        let levels = 1 << LOOKUP_BITS;
+        // the middle of the range of colors a lookup entry stands for
+        let center = |level: u32| ((level << (8 - LOOKUP_BITS)) + (1 << (7 - LOOKUP_BITS))) as u8;
+        let mut lookup = Vec::with_capacity(levels * levels * levels);
+        for r in 0..levels as u32 {
+            for g in 0..levels as u32 {
+                for b in 0..levels as u32 {
+                    let color = [center(r), center(g), center(b)];
+                    lookup.push(nearest(&colors, color) as u8);
+                }
+            }
+        }
+        Palette { colors, lookup }
+    }
+
+    pub fn colors(&self) -> &[[u8; 3]] {
+        &self.colors
+    }
+
+    /// The palette color closest to `rgb`. Colors are told apart by their top bits only, so
+    /// very close ones share an entry.
+    pub fn nearest(&self, [r, g, b]: [u8; 3]) -> [u8; 3] {
+        let shift = 8 - LOOKUP_BITS;
+        let index = ((r as usize >> shift) << (2 * LOOKUP_BITS))
+            | ((g as usize >> shift) << LOOKUP_BITS)
+            | (b as usize >> shift);
+        self.colors[self.lookup[index] as usize]
+    }
+}
+
+impl std::fmt::Debug for Palette {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        // the lookup table follows from the colors
+        f.debug_tuple("Palette").field(&self.colors).finish()
+    }
+}
+
+/// The channel with the largest spread of values among `pixels`, and that spread.
+fn widest_channel(pixels: &[[u8; 3]]) -> (usize, u8) {
+    (0..3)
+        .map(|channel| {
+            let (lo, hi) = pixels.iter().fold((u8::MAX, u8::MIN), |(lo, hi), pixel| {
+                (lo.min(pixel[channel]), hi.max(pixel[channel]))
+            });
+            (channel, hi.saturating_sub(lo))
+        })
+        .max_by_key(|&(_, range)| range)
+        .unwrap_or_default()
+}
+
+fn average(pixels: &[[u8; 3]]) -> [u8; 3] {
+    let mut sum = [0u64; 3];
+    for pixel in pixels {
+        for (sum, &channel) in sum.iter_mut().zip(pixel) {
+            *sum += channel as u64;
+        }
+    }
+    sum.map(|sum| (sum / pixels.len().max(1) as u64) as u8)
+}
+
+/// Index of the color in `colors` closest to `rgb` by squared distance.
+fn nearest(colors: &[[u8; 3]], rgb: [u8; 3]) -> usize {
+    let distance = |color: &[u8; 3]| -> i32 {
+        color
+            .iter()
+            .zip(&rgb)
+            .map(|(&a, &b)| (a as i32 - b as i32).pow(2))
+            .sum()
+    };
+    (0..colors.len())
+        .min_by_key(|&i| distance(&colors[i]))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn median_cut_splits_along_the_widest_channel() {
+        // two reds and two blues, far apart on red and blue but not on green
+        let pixels = vec![[250, 0, 0], [240, 0, 10], [0, 0, 250], [10, 0, 240]];
+        let palette = Palette::median_cut(pixels.clone(), 2);
+        let mut colors = palette.colors().to_vec();
+        colors.sort_unstable();
+        assert_eq!(colors, vec![[5, 0, 245], [245, 0, 5]]);
+        assert_eq!(palette.nearest([255, 20, 0]), [245, 0, 5]);
+        assert_eq!(palette.nearest([0, 20, 255]), [5, 0, 245]);
+
+        // fewer distinct colors than asked for give fewer entries
+        let palette = Palette::median_cut(vec![[7, 7, 7]; 10], 16);
+        assert_eq!(palette.colors(), &[[7, 7, 7]]);
+        assert_eq!(Palette::median_cut(Vec::new(), 16).colors(), &[[0, 0, 0]]);
+    }
+
+    #[test]
+    fn every_pixel_maps_to_a_palette_color() {
+        let pixels: Vec<[u8; 3]> = (0..=255u8).map(|v| [v, 255 - v, v / 2]).collect();
+        let palette = Palette::median_cut(pixels.clone(), 8);
+        assert_eq!(palette.colors().len(), 8);
+        for pixel in pixels {
+            assert!(palette.colors().contains(&palette.nearest(pixel)));
+        }
+    }
+}
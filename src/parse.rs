@@ -0,0 +1,164 @@
+/*
+   This file is part of term-video.
+
+   term-video is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   term-video is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with term-video.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Parsing of the frame rates, timestamps and sizes that come in as text from the command
+//! line, ffprobe and recordings. Surrounding whitespace, like the newline ffprobe ends its
+//! output with, is ignored; anything else that doesn't fit the format gives `None`.
+
+use crate::player::MIN_FRAME_RATE;
+use std::time::Duration;
+
+/// Parses a frame rate given as a fraction like ffprobe's `30000/1001` or as a number like
+/// `29.97`. Rates below [`MIN_FRAME_RATE`] aren't accepted, so ffprobe's `0/0` for an unknown
+/// rate gives `None`.
+pub fn parse_frame_rate(s: &str) -> Option<f64> {
+    let s = s.trim();
+    let rate = match s.split_once('/') {
+        Some((num, den)) => num.parse::<f64>().ok()? / den.parse::<f64>().ok()?,
+        None => s.parse().ok()?,
+    };
+    Some(rate).filter(|rate| rate.is_finite() && *rate >= MIN_FRAME_RATE)
+}
+
+/// Parses a position in a video given as `SS`, `MM:SS` or `HH:MM:SS`. The seconds may be
+/// fractional, and the leading field isn't limited to 60.
+pub fn parse_timestamp(s: &str) -> Option<Duration> {
+    let fields: Vec<&str> = s.trim().split(':').collect();
+    let (seconds, larger) = fields.split_last()?;
+    if larger.len() > 2 {
+        return None;
+    }
+
+    let seconds: f64 = seconds
+        .parse()
+        .ok()
+        .filter(|s: &f64| s.is_finite() && *s >= 0.0)?;
+    if !larger.is_empty() && seconds >= 60.0 {
+        return None;
+    }
+    let mut total: u64 = 0;
+    for (index, field) in larger.iter().enumerate() {
+        // only the leading field may exceed 59
+        let value: u64 = field.parse().ok().filter(|&v| index == 0 || v < 60)?;
+        total = total.checked_mul(60)?.checked_add(value)?;
+    }
+
+    // positions too far out for a Duration aren't in any video
+    let seconds = Duration::try_from_secs_f64(seconds).ok()?;
+    Duration::from_secs(total.checked_mul(60)?).checked_add(seconds)
+}
+
+/// Parses a size given as `<width>x<height>`, like `80x24`. Neither may be zero.
+pub fn parse_dimensions(s: &str) -> Option<(u32, u32)> {
+    let (width, height) = s.trim().split_once('x')?;
+    let width: u32 = width.parse().ok()?;
+    let height: u32 = height.parse().ok()?;
+    (width > 0 && height > 0).then_some((width, height))
+}
+
+/// Parses a color given as `R,G,B`, each channel from 0 to 255, like `51,255,51`.
+pub fn parse_rgb(s: &str) -> Option<[u8; 3]> {
+    let channels: Vec<u8> = s
+        .split(',')
+        .map(|channel| channel.trim().parse().ok())
+        .collect::<Option<_>>()?;
+    match channels[..] {
+        [r, g, b] => Some([r, g, b]),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_frame_rate_accepts_fractions_and_numbers() {
+        let ntsc = parse_frame_rate("30000/1001").unwrap();
+        assert!((ntsc - 29.97).abs() < 0.001);
+        assert_eq!(parse_frame_rate("25/1"), Some(25.0));
+        assert_eq!(parse_frame_rate("23.976"), Some(23.976));
+        // ffprobe ends its output with a newline
+        assert_eq!(parse_frame_rate("  24/1\n"), Some(24.0));
+    }
+
+    #[test]
+    fn parse_frame_rate_rejects_malformed_input() {
+        for invalid in [
+            "", "/", "0/0", "30/0", "0/1", "-25/1", "25/-1", "25/", "/1", "abc", "25/1/1", "inf",
+            "NaN", "25 / 1", "1e-300", "1/1e300", "0.0001",
+        ] {
+            assert_eq!(parse_frame_rate(invalid), None, "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn parse_timestamp_accepts_seconds() {
+        assert_eq!(parse_timestamp("90"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_timestamp("1.5"), Some(Duration::from_millis(1500)));
+        assert_eq!(parse_timestamp(" 7\n"), Some(Duration::from_secs(7)));
+    }
+
+    #[test]
+    fn parse_timestamp_accepts_minutes_and_seconds() {
+        assert_eq!(parse_timestamp("02:30"), Some(Duration::from_secs(150)));
+    }
+
+    #[test]
+    fn parse_timestamp_accepts_hours_minutes_and_seconds() {
+        assert_eq!(parse_timestamp("1:02:03"), Some(Duration::from_secs(3723)));
+    }
+
+    #[test]
+    fn parse_timestamp_rejects_malformed_input() {
+        for invalid in [
+            "",
+            "abc",
+            "1:2:3:4",
+            "1:60",
+            "-5",
+            "1::2",
+            "1:2.5:3",
+            "inf",
+            "1e30",
+            "400000000000000000:00",
+            "99999999999999999999:00:00",
+        ] {
+            assert_eq!(parse_timestamp(invalid), None, "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn parse_dimensions_reads_width_by_height() {
+        assert_eq!(parse_dimensions("80x24"), Some((80, 24)));
+        assert_eq!(parse_dimensions("1920x1080\n"), Some((1920, 1080)));
+        for invalid in [
+            "", "80", "80x", "x24", "0x24", "80x0", "80X24", "-80x24", "8 0x24", "80x24x1",
+        ] {
+            assert_eq!(parse_dimensions(invalid), None, "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn parse_rgb_reads_three_channels() {
+        assert_eq!(parse_rgb("51,255,51"), Some([51, 255, 51]));
+        assert_eq!(parse_rgb(" 255, 176 ,0 "), Some([255, 176, 0]));
+        for invalid in ["", "1,2", "1,2,3,4", "256,0,0", "-1,0,0", "a,b,c", "1;2;3"] {
+            assert_eq!(parse_rgb(invalid), None, "{:?}", invalid);
+        }
+    }
+}
@@ -0,0 +1,1284 @@
+/*
+   This file is part of term-video.
+
+   term-video is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   term-video is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with term-video.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::{
+    color::RESET,
+    controls::{Action, Controls},
+    diff::FrameDiff,
+    kitty,
+    render::Renderer,
+    smooth::Hysteresis,
+    subtitles::Subtitles,
+    terminal::{clip_frame, overwrite_cells, visible_width, TerminalSize},
+    timestamp::format_clock,
+};
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use image::{io::Reader, DynamicImage};
+use log::warn;
+use rayon::prelude::*;
+use std::{
+    borrow::Cow,
+    io::{self, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+// set while playback owns the terminal, cleared once it has been restored
+static TERMINAL_ALTERED: AtomicBool = AtomicBool::new(false);
+// set while playback is on the alternate screen, which scrolling playback stays off
+static ALTERNATE_SCREEN: AtomicBool = AtomicBool::new(false);
+
+/// How far the arrow keys jump, in video time.
+const SEEK_STEP: Duration = Duration::from_secs(5);
+
+/// Share of the frames that may be late or dropped before playback is reported as too slow.
+const LATE_SHARE: f64 = 0.1;
+
+/// A corner of the frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// Settings for how frames are shown, as opposed to how they are rendered.
+#[derive(Clone, Debug)]
+pub struct PlaybackOptions {
+    /// Frames per second, may be fractional like NTSC's 29.97
+    pub frame_rate: f64,
+    /// Decode frames just-in-time on a background thread, keeping at most this many frames
+    /// in memory, instead of decoding everything upfront
+    pub prebuffer: Option<usize>,
+    /// Sound file played alongside the video; the player drops frames when it lags behind
+    pub audio: Option<String>,
+    /// How many times to play the video, `None` loops until interrupted
+    pub plays: Option<u32>,
+    /// How long the last frame stays on screen before the video starts over
+    pub loop_delay: Duration,
+    /// How long each frame is shown, for sources with their own timing. Overrides
+    /// `frame_rate` when set.
+    pub frame_delays: Option<Vec<Duration>>,
+    /// Only rewrite the cells that changed since the previous frame
+    pub diff: bool,
+    /// Playback speed multiplier, 2.0 plays twice as fast, must be positive
+    pub speed: f64,
+    /// Show measured and target frame rate, frame index and dropped frames in the corner
+    pub stats: bool,
+    /// Corner of text frames to show the position in the video and its length in
+    pub timecode: Option<Corner>,
+    /// Length of streamed video, for the timecode. Frames that are buffered or read from
+    /// files are measured instead
+    pub length: Option<Duration>,
+    /// Frames are terminal graphics like Sixel images rather than lines of text, so they are
+    /// printed as they are without clipping or diffing
+    pub graphics: bool,
+    /// Move the cursor around and set up the screen with escape sequences. Without them
+    /// frames are printed one after the other, for terminals that can't handle them
+    pub escapes: bool,
+    /// Show frames in real time. Without it they are written as fast as possible, which is
+    /// all that makes sense when the output isn't a terminal, and keys aren't listened for
+    pub paced: bool,
+    /// Play the frames from last to first, along with their delays. Only frames that are
+    /// buffered or read from files can be reversed, not streamed ones, and the audio track
+    /// isn't reversed
+    pub reverse: bool,
+    /// Play the frames forward and then backward again, a cycle per play. The way back stops
+    /// short of the first frame, which starts the next cycle. Like `reverse` only for frames
+    /// that are buffered or read from files
+    pub bounce: bool,
+    /// Captions burned into the bottom rows of text frames, timed against the video rather
+    /// than the playback speed
+    pub subtitles: Option<Subtitles>,
+    /// Video time into the frames the first play starts at, in playback order. Later loops
+    /// start from the beginning, and the frames before it stay available for seeking back
+    pub seek: Duration,
+    /// Fail instead of just warning when too many frames are late to keep the frame rate
+    pub strict: bool,
+    /// Show one frame at a time and move between them with the keyboard instead of playing
+    /// them on the frame clock. Only for frames that are buffered, not decoded as they play
+    pub step: bool,
+    /// Print every frame below the previous one instead of over it, on the normal screen,
+    /// so they stay in the scrollback. Nothing moves the cursor then, so there is no diff,
+    /// stats overlay or graphics
+    pub scroll: bool,
+}
+
+impl Default for PlaybackOptions {
+    fn default() -> Self {
+        PlaybackOptions {
+            frame_rate: 30.0,
+            prebuffer: None,
+            audio: None,
+            plays: Some(1),
+            loop_delay: Duration::ZERO,
+            frame_delays: None,
+            diff: false,
+            speed: 1.0,
+            stats: false,
+            timecode: None,
+            length: None,
+            graphics: false,
+            escapes: true,
+            paced: true,
+            reverse: false,
+            bounce: false,
+            subtitles: None,
+            seek: Duration::ZERO,
+            strict: false,
+            step: false,
+            scroll: false,
+        }
+    }
+}
+
+/// Renders and plays `frame_files`. Frames that can't be decoded, like ones left truncated by
+/// an interrupted extraction, are replaced by the one before, or the first good one at the
+/// start, with a warning, so the frames after them keep their time.
+///
+/// Buffered playback decodes every frame upfront in parallel and reuses them across loop
+/// iterations, while `prebuffer` mode decodes the files again on every iteration.
+pub fn display_loop(
+    frame_files: &[PathBuf],
+    renderer: &Renderer,
+    opts: &PlaybackOptions,
+) -> Result<()> {
+    let depth = match opts.prebuffer {
+        Some(depth) => depth,
+        None => return play_frames(&render_frames(frame_files, renderer), opts),
+    };
+
+    let length = frames_length(opts, frame_files.len());
+    run_loop(opts, Some(length), |clock, session| {
+        let mut history = Hysteresis::default();
+        // the frame before an undecodable one is rendered again rather than kept around, as
+        // decoding rarely fails
+        let mut last_good = None;
+        // before the first good frame, the next one that can be decoded stands in, looked for
+        // once and kept until playback gets to it
+        let mut first_good: Option<Option<(usize, DynamicImage)>> = None;
+        let count = frame_files.len();
+        let order = files_in_order(count, opts);
+        let frames = order.enumerate().filter_map(move |(position, index)| {
+            let reached = |found: &mut Option<(usize, DynamicImage)>| {
+                matches!(found, Some((good, _)) if *good == index)
+            };
+            if let Some(Some((_, frame))) = first_good.take_if(reached) {
+                last_good = Some(index);
+                return Some(renderer.render_frame_at(&frame, index, count, &mut history));
+            }
+            if let Some(frame) = render_file(frame_files, index, renderer, &mut history) {
+                last_good = Some(index);
+                return Some(frame);
+            }
+            if let Some(good) = last_good {
+                return render_file(frame_files, good, renderer, &mut history);
+            }
+            let (good, frame) = first_good
+                .get_or_insert_with(|| {
+                    files_in_order(count, opts)
+                        .skip(position + 1)
+                        .find_map(|later| Some((later, decode_frame(&frame_files[later]).ok()?)))
+                })
+                .as_ref()?;
+            Some(renderer.render_frame_at(frame, *good, count, &mut history))
+        });
+        present_prefetched(frames, depth, clock, session)
+    })
+}
+
+/// Indices of `count` frame files in playback order. Boxed since the reversed iterator is a
+/// different type.
+fn files_in_order(count: usize, opts: &PlaybackOptions) -> Box<dyn Iterator<Item = usize> + Send> {
+    if opts.bounce {
+        Box::new(bounce_order(count))
+    } else if opts.reverse {
+        Box::new((0..count).rev())
+    } else {
+        Box::new(0..count)
+    }
+}
+
+/// Indices of a bounce cycle over `len` frames: all of them forward, then back down to the
+/// second one.
+fn bounce_order(len: usize) -> impl Iterator<Item = usize> + Send {
+    (0..len).chain((1..len.saturating_sub(1)).rev())
+}
+
+/// Plays frames decoded and rendered on the fly, like from an ffmpeg pipe. `open` is called
+/// at the start of every loop iteration with the video time to start at, which is `seek` for
+/// the first one and zero after that, so the stream can jump ahead itself instead of
+/// decoding the frames in between. Frames are pulled from it on a background thread at most
+/// `prebuffer` frames ahead of playback. Streamed frames can't be played in reverse.
+pub fn play_stream<I, F>(open: F, opts: &PlaybackOptions) -> Result<()>
+where
+    I: Iterator<Item = String> + Send,
+    F: Fn(Duration) -> Result<I>,
+{
+    if opts.reverse || opts.bounce {
+        bail!("streamed frames can't be played in reverse, nor bounced back and forth");
+    }
+    let depth = opts.prebuffer.unwrap_or(1);
+    run_loop(opts, opts.length, |clock, session| {
+        let seek = std::mem::take(&mut session.seek);
+        session.skipped = seek;
+        present_prefetched(open(seek)?, depth, clock, session)
+    })
+}
+
+/// Plays frames that have already been rendered.
+pub fn play_frames(frames: &[String], opts: &PlaybackOptions) -> Result<()> {
+    if opts.graphics
+        && frames
+            .first()
+            .is_some_and(|frame| kitty::is_kitty_frame(frame))
+    {
+        kitty::mark_transmitted();
+    }
+    // 显示每一帧
+    run_loop(
+        opts,
+        Some(frames_length(opts, frames.len())),
+        |clock, session| {
+            if opts.bounce {
+                present(&mut Bounced(frames), clock, session)
+            } else if opts.reverse {
+                present(&mut Reversed(frames), clock, session)
+            } else {
+                present(&mut &frames[..], clock, session)
+            }
+        },
+    )
+}
+
+/// Video time `count` frames take to play once, in playback order.
+fn frames_length(opts: &PlaybackOptions, count: usize) -> Duration {
+    let played = if opts.bounce {
+        bounce_order(count).count()
+    } else {
+        count
+    };
+    scale_duration(FrameClock::new(opts).offset(played), opts.speed)
+}
+
+/// Decodes and renders every frame in parallel, with the one before, or the first good one at
+/// the start, in place of every frame that can't be decoded. Smoothed frames depend on the
+/// ones before, those are rendered in order.
+pub fn render_frames(frame_files: &[PathBuf], renderer: &Renderer) -> Vec<String> {
+    render_frames_with_progress(frame_files, renderer, |_| ())
+}
+
+/// Like [`render_frames`], calling `progress` with the number of frames done so far after
+/// every frame, undecodable ones included.
+pub fn render_frames_with_progress(
+    frame_files: &[PathBuf],
+    renderer: &Renderer,
+    progress: impl Fn(u64) + Sync,
+) -> Vec<String> {
+    let done = AtomicU64::new(0);
+    let render = |index, history: &mut Hysteresis| {
+        let frame = render_file(frame_files, index, renderer, history);
+        progress(done.fetch_add(1, Ordering::Relaxed) + 1);
+        frame
+    };
+    if renderer.options().smooth {
+        let mut history = Hysteresis::default();
+        let frames = (0..frame_files.len()).map(|index| render(index, &mut history));
+        return repeat_previous(frames.collect());
+    }
+    // 并行解码每一帧, collect() 保留原有顺序
+    let frames = (0..frame_files.len())
+        .into_par_iter()
+        .map(|index| render(index, &mut Hysteresis::default()));
+    repeat_previous(frames.collect())
+}
+
+/// Fills every missing frame with the one before it, so the frames after keep their place in
+/// time and line up with their per-frame delays. Missing frames at the start have nothing to
+/// repeat and get the first frame there is instead. Without any frame there is nothing to
+/// fill them with.
+fn repeat_previous(frames: Vec<Option<String>>) -> Vec<String> {
+    let Some(first) = frames.iter().flatten().next().cloned() else {
+        return Vec::new();
+    };
+    let mut filled: Vec<String> = Vec::with_capacity(frames.len());
+    for frame in frames {
+        let frame = frame.unwrap_or_else(|| filled.last().unwrap_or(&first).clone());
+        filled.push(frame);
+    }
+    filled
+}
+
+/// Decodes and renders frame `index` of `frame_files`, faded by its position among them.
+fn render_file(
+    frame_files: &[PathBuf],
+    index: usize,
+    renderer: &Renderer,
+    history: &mut Hysteresis,
+) -> Option<String> {
+    match decode_frame(&frame_files[index]) {
+        Ok(frame) => Some(renderer.render_frame_at(&frame, index, frame_files.len(), history)),
+        Err(e) => {
+            warn!(
+                "Frame {} is damaged, showing another in its place: {:#}",
+                index + 1,
+                e
+            );
+            None
+        }
+    }
+}
+
+/// Sets up the terminal and calls `play` once per loop iteration with a fresh frame clock and
+/// the playback session, until all plays are done or the user quits. Afterwards it warns,
+/// or fails with `strict`, if more than a tenth of the frames were late. `length` is the
+/// video time a play takes, if it is known, for the timecode.
+fn run_loop(
+    opts: &PlaybackOptions,
+    length: Option<Duration>,
+    mut play: impl FnMut(&mut FrameClock, &mut Session) -> Result<()>,
+) -> Result<()> {
+    if opts.escapes {
+        setup_terminal(opts.scroll);
+    }
+    // scrolling frames are printed one after the other, nothing may move the cursor back
+    let escapes = opts.escapes && !opts.scroll;
+
+    // what is on screen carries over from one loop iteration to the next
+    let mut session = Session {
+        // both the diff and the overlay position the cursor
+        diff: (opts.diff && !opts.graphics && escapes).then(FrameDiff::new),
+        size: TerminalSize::watch(),
+        controls: if opts.paced { Controls::start() } else { None },
+        audio: None,
+        seek_step: SEEK_STEP.div_f64(opts.speed),
+        stats: (opts.stats && escapes).then(|| Stats::new(opts)),
+        subtitles: opts.subtitles.clone().filter(|_| !opts.graphics),
+        timecode: opts
+            .timecode
+            .filter(|_| !opts.graphics)
+            .map(|corner| Timecode { corner, length }),
+        seek: opts.seek,
+        skipped: Duration::ZERO,
+        timing: Timing::default(),
+        speed: opts.speed,
+        graphics: opts.graphics,
+        escapes,
+        paced: opts.paced,
+        step: opts.step,
+        quit: false,
+    };
+    let mut result = Ok(());
+    let mut played = 0;
+
+    while result.is_ok() && !session.quit && opts.plays.is_none_or(|plays| played < plays) {
+        if played > 0 {
+            session.linger(opts.loop_delay);
+            if session.quit {
+                break;
+            }
+        }
+        // restart the sound along with the video
+        stop_audio(&mut session.audio);
+        session.audio = opts
+            .audio
+            .as_deref()
+            .and_then(|audio| play_audio(audio, session.seek));
+
+        result = play(&mut FrameClock::new(opts), &mut session);
+        played += 1;
+    }
+
+    stop_audio(&mut session.audio);
+    restore_terminal();
+    if result.is_ok() && session.paced && session.timing.too_slow() {
+        let Timing { shown, late } = session.timing;
+        let message = format!(
+            "{} of {} frames were late, the terminal can't keep up with {:.2} fps; a smaller \
+             playback size or playing fewer frames may help",
+            late,
+            shown + late,
+            target_frame_rate(opts)
+        );
+        if opts.strict {
+            bail!(message);
+        }
+        warn!("{}", message);
+    }
+    result
+}
+
+/// How many frames were on time and how many weren't, over all loop iterations.
+#[derive(Clone, Copy, Default)]
+struct Timing {
+    /// Frames written out before their slot was over
+    shown: usize,
+    /// Frames written out after their slot, or dropped to catch up with the sound
+    late: usize,
+}
+
+impl Timing {
+    fn too_slow(self) -> bool {
+        let frames = self.shown + self.late;
+        frames > 0 && self.late as f64 / frames as f64 > LATE_SHARE
+    }
+}
+
+/// Frames per second playback aims for, the average of the delays for timed frames.
+fn target_frame_rate(opts: &PlaybackOptions) -> f64 {
+    let frame_rate = match &opts.frame_delays {
+        Some(delays) if !delays.is_empty() => {
+            delays.len() as f64 / delays.iter().sum::<Duration>().as_secs_f64()
+        }
+        _ => opts.frame_rate,
+    };
+    frame_rate * opts.speed
+}
+
+fn stop_audio(audio_player: &mut Option<Child>) {
+    if let Some(mut player) = audio_player.take() {
+        // ffplay exits on its own at the end of the track, this only matters if video ended early
+        let _ = player.kill();
+        let _ = player.wait();
+    }
+}
+
+fn decode_frame(frame_path: &Path) -> Result<DynamicImage> {
+    let decode = || -> Result<DynamicImage> { Ok(Reader::open(frame_path)?.decode()?) };
+    decode().with_context(|| format!("could not decode {}", frame_path.display()))
+}
+
+fn play_audio(path: &str, start: Duration) -> Option<Child> {
+    let start = start.as_secs_f64().to_string();
+    Command::new("ffplay")
+        .args(vec![
+            "-nodisp",
+            "-autoexit",
+            "-loglevel",
+            "quiet",
+            "-ss",
+            &start,
+            path,
+        ])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| warn!("Failed to execute ffplay, playing without sound: {}", e))
+        .ok()
+}
+
+/// Hides the cursor and turns off line wrapping, and unless frames `scroll` switches to a
+/// cleared alternate screen.
+fn setup_terminal(scroll: bool) {
+    TERMINAL_ALTERED.store(true, Ordering::SeqCst);
+    if !scroll {
+        ALTERNATE_SCREEN.store(true, Ordering::SeqCst);
+        // play on the alternate screen, leaving the shell's screen and scrollback untouched
+        print!("\x1B[?1049h");
+        // 清空屏幕并移动到左上角
+        print!("\x1B[2J\x1B[H");
+    }
+    // 隐藏光标
+    print!("\x1B[?25l");
+    // 禁用行包装
+    print!("\x1B[?7l");
+}
+
+/// Undoes the terminal setup done by [`display_loop`]. Only the first call after setup has
+/// any effect, so both an interrupt handler and the regular exit path can call it.
+pub fn restore_terminal() {
+    // raw mode is entered for the keyboard controls even without any escapes
+    let _ = crossterm::terminal::disable_raw_mode();
+    if TERMINAL_ALTERED.swap(false, Ordering::SeqCst) {
+        // 恢复终端设置
+        print!("\x1B[?7h"); // 重新启用行包装
+        print!("\x1B[?25h"); // 显示光标
+        if let Some(cleanup) = kitty::cleanup() {
+            print!("{}", cleanup);
+        }
+        if ALTERNATE_SCREEN.swap(false, Ordering::SeqCst) {
+            // back to the main screen as it was before playback
+            print!("\x1B[?1049l");
+        }
+        let _ = io::stdout().flush();
+    }
+}
+
+/// Slowest frame rate the clock schedules, a frame every 1000 seconds. Rates below it, like
+/// `1e-300`, would have periods that don't fit in a `Duration`.
+pub const MIN_FRAME_RATE: f64 = 0.001;
+
+/// `duration` times `factor`, saturating at `Duration::MAX` where `mul_f64` would panic.
+pub fn scale_duration(duration: Duration, factor: f64) -> Duration {
+    Duration::try_from_secs_f64(duration.as_secs_f64() * factor).unwrap_or(Duration::MAX)
+}
+
+/// Schedules frames against a fixed start time instead of sleeping a fixed period after each
+/// one, so time spent printing never accumulates: frame `n` is due at `start + n * period`,
+/// or at the sum of the preceding frames' delays for sources with per-frame timing.
+pub struct FrameClock {
+    start: Instant,
+    period: Duration,
+    // presentation offset of every frame plus the end of the last one
+    offsets: Option<Vec<Duration>>,
+}
+
+impl FrameClock {
+    pub fn new(opts: &PlaybackOptions) -> Self {
+        match &opts.frame_delays {
+            Some(delays) => {
+                let mut delays: Vec<_> = delays
+                    .iter()
+                    .map(|&d| scale_duration(d, 1.0 / opts.speed))
+                    .collect();
+                if opts.bounce {
+                    delays = bounce_order(delays.len()).map(|i| delays[i]).collect();
+                } else if opts.reverse {
+                    delays.reverse();
+                }
+                Self::with_delays(Instant::now(), &delays)
+            }
+            None => Self::starting_at(Instant::now(), opts.frame_rate * opts.speed),
+        }
+    }
+
+    pub fn starting_at(start: Instant, frame_rate: f64) -> Self {
+        FrameClock {
+            start,
+            period: Duration::try_from_secs_f64(1.0 / frame_rate).unwrap_or(Duration::MAX),
+            offsets: None,
+        }
+    }
+
+    pub fn with_delays(start: Instant, delays: &[Duration]) -> Self {
+        let offsets = std::iter::once(Duration::ZERO)
+            .chain(delays.iter().scan(Duration::ZERO, |offset, &delay| {
+                *offset = offset.saturating_add(delay);
+                Some(*offset)
+            }))
+            .collect();
+        FrameClock {
+            start,
+            period: delays.last().copied().unwrap_or_default(),
+            offsets: Some(offsets),
+        }
+    }
+
+    pub fn due(&self, index: usize) -> Instant {
+        self.start + self.offset(index)
+    }
+
+    /// When frame `index` is due relative to the start of playback.
+    pub fn offset(&self, index: usize) -> Duration {
+        match &self.offsets {
+            Some(offsets) => {
+                // frames past the end of the schedule keep the last frame's delay
+                let last = offsets.len() - 1;
+                let extra = index.saturating_sub(last) as u32;
+                offsets[index.min(last)].saturating_add(self.period.saturating_mul(extra))
+            }
+            None => scale_duration(self.period, index as f64),
+        }
+    }
+
+    /// The frame on screen `offset` after the start of playback.
+    pub fn frame_at(&self, offset: Duration) -> usize {
+        let frames_after = |base: Duration| {
+            if self.period.is_zero() {
+                0
+            } else {
+                (offset.saturating_sub(base).as_secs_f64() / self.period.as_secs_f64()) as usize
+            }
+        };
+        match &self.offsets {
+            Some(offsets) => {
+                let last = offsets.len() - 1;
+                match offsets.partition_point(|&o| o <= offset) {
+                    n if n > last => last + frames_after(offsets[last]),
+                    n => n.saturating_sub(1),
+                }
+            }
+            None => frames_after(Duration::ZERO),
+        }
+    }
+
+    /// Moves the schedule so that frame `index` is due at `now`, after pausing or seeking.
+    pub fn resync(&mut self, index: usize, now: Instant) {
+        if let Some(start) = now.checked_sub(self.offset(index)) {
+            self.start = start;
+        }
+    }
+
+    /// Time left until frame `index` is due, zero if it is already late.
+    pub fn delay(&self, index: usize, now: Instant) -> Duration {
+        self.offset(index)
+            .saturating_sub(now.saturating_duration_since(self.start))
+    }
+
+    /// Whether frame `index` is late by more than its whole slot and should be dropped.
+    pub fn is_overdue(&self, index: usize, now: Instant) -> bool {
+        now.saturating_duration_since(self.start) > self.offset(index + 1)
+    }
+}
+
+/// Everything about playback that outlives a single loop iteration: what is on screen, the
+/// terminal size, keyboard input and the sound.
+struct Session {
+    diff: Option<FrameDiff>,
+    size: TerminalSize,
+    controls: Option<Controls>,
+    audio: Option<Child>,
+    /// Playback time the arrow keys jump by
+    seek_step: Duration,
+    stats: Option<Stats>,
+    subtitles: Option<Subtitles>,
+    timecode: Option<Timecode>,
+    /// Where the next play starts, `--seek` until the first one has jumped there
+    seek: Duration,
+    /// Video time the frames of the current play start at, for streams that skipped ahead
+    skipped: Duration,
+    timing: Timing,
+    speed: f64,
+    graphics: bool,
+    escapes: bool,
+    paced: bool,
+    step: bool,
+    quit: bool,
+}
+
+impl Session {
+    /// Turns a frame into the output to print after moving the cursor home: clipped to the
+    /// terminal, and with a diff only the cells that changed.
+    fn prepare<'a>(&mut self, frame: &'a str) -> Cow<'a, str> {
+        let resized = self.size.changed();
+        if resized {
+            self.forget_screen();
+        }
+
+        let frame = match self.size.get() {
+            Some((columns, rows)) if !self.graphics => clip_frame(frame, columns, rows),
+            _ => Cow::Borrowed(frame),
+        };
+        let mut output = match self.diff.as_mut().and_then(|diff| diff.update(&frame)) {
+            Some(update) => Cow::Owned(update),
+            None => frame,
+        };
+
+        if resized && self.escapes {
+            // 清除调整大小后残留的内容
+            output = Cow::Owned(format!("\x1B[2J{}", output));
+        }
+        if self.controls.is_some() && output.contains('\n') {
+            // raw mode doesn't turn newlines into carriage return plus line feed
+            output = Cow::Owned(output.replace('\n', "\r\n"));
+        }
+        output
+    }
+
+    /// The frame with the captions and the timecode burned in that show `position` into
+    /// playback.
+    fn caption<'a>(&self, frame: &'a str, position: Duration) -> Cow<'a, str> {
+        let time = scale_duration(position, self.speed).saturating_add(self.skipped);
+        let (captioned, captions) = match &self.subtitles {
+            Some(subtitles) => (subtitles.burn_in(frame, time), subtitles.at(time).len()),
+            None => (Cow::Borrowed(frame), 0),
+        };
+        let Some(timecode) = &self.timecode else {
+            return captioned;
+        };
+        let rows = captioned.lines().count();
+        let rows = match self.size.get() {
+            Some((_, terminal_rows)) => rows.min(terminal_rows),
+            None => rows,
+        };
+        // the stats and the step label take up the top row
+        let top = usize::from(self.stats.is_some() || (self.step && self.escapes));
+        let free = top..rows.saturating_sub(captions);
+        let width = self.width(&captioned);
+        Cow::Owned(timecode.burn_in(&captioned, time, width, free))
+    }
+
+    /// Makes the next frame draw in full, after a prepared frame was never shown.
+    fn forget_screen(&mut self) {
+        if let Some(diff) = &mut self.diff {
+            diff.reset();
+        }
+    }
+
+    /// The `--stats` overlay for frame `index`, right-aligned to the frame or the terminal,
+    /// whichever is narrower.
+    fn overlay(&mut self, frame: &str, index: usize) -> Option<String> {
+        let width = self.width(frame);
+        Some(self.stats.as_mut()?.overlay(index, width))
+    }
+
+    /// Columns `frame` takes up on screen, at most as many as the terminal has.
+    fn width(&self, frame: &str) -> usize {
+        let width = visible_width(frame.lines().next().unwrap_or_default());
+        match self.size.get() {
+            Some((columns, _)) => width.min(columns),
+            None => width,
+        }
+    }
+
+    /// Leaves the frame on screen for `delay` before the video plays again, or as long as
+    /// it takes to quit. Other keys are ignored meanwhile.
+    fn linger(&mut self, delay: Duration) {
+        if !self.paced || delay.is_zero() {
+            return;
+        }
+        if self.controls.is_none() {
+            thread::sleep(delay);
+            return;
+        }
+        // a delay too long to have an end is waited out until quitting
+        let end = Instant::now().checked_add(delay);
+        loop {
+            let left = match end {
+                Some(end) => match end.checked_duration_since(Instant::now()) {
+                    Some(left) if !left.is_zero() => Some(left),
+                    _ => return,
+                },
+                None => None,
+            };
+            if self.action(left) == Some(Action::Quit) {
+                self.quit = true;
+                return;
+            }
+        }
+    }
+
+    /// The next key action, waiting up to `timeout` for it.
+    fn action(&self, timeout: Option<Duration>) -> Option<Action> {
+        let controls = self.controls.as_ref()?;
+        match timeout {
+            Some(Duration::ZERO) => controls.poll(),
+            timeout => controls.wait(timeout),
+        }
+    }
+}
+
+/// The `--timecode` overlay.
+struct Timecode {
+    corner: Corner,
+    /// Length of the video, to show next to the position if it's known
+    length: Option<Duration>,
+}
+
+impl Timecode {
+    /// Writes `time` into the corner of the first `width` columns of `frame`, on the first or
+    /// last of the rows in `free`, which other overlays leave alone. Nothing is written
+    /// without a free row.
+    fn burn_in(&self, frame: &str, time: Duration, width: usize, free: Range<usize>) -> String {
+        let text = match self.length {
+            Some(length) => format!(
+                " {} / {} ",
+                format_clock(time.min(length)),
+                format_clock(length)
+            ),
+            None => format!(" {} ", format_clock(time)),
+        };
+        let text: String = text.chars().take(width).collect();
+        let row = match self.corner {
+            _ if free.is_empty() => return frame.to_owned(),
+            Corner::TopLeft | Corner::TopRight => free.start,
+            Corner::BottomLeft | Corner::BottomRight => free.end - 1,
+        };
+        let column = match self.corner {
+            Corner::TopLeft | Corner::BottomLeft => 0,
+            Corner::TopRight | Corner::BottomRight => width - text.chars().count(),
+        };
+
+        let mut burned: Vec<Cow<str>> = frame.split_terminator('\n').map(Cow::Borrowed).collect();
+        if let Some(line) = burned.get_mut(row) {
+            *line = Cow::Owned(overwrite_cells(line, column, &text));
+        }
+        let mut burned = burned.join("\n");
+        if frame.ends_with('\n') {
+            burned.push('\n');
+        }
+        burned
+    }
+}
+
+/// Measurements behind the `--stats` overlay.
+struct Stats {
+    target_fps: f64,
+    last_shown: Option<Instant>,
+    // moving average of the time between two frames being written out
+    interval: Option<Duration>,
+    dropped: usize,
+    // the overlay never shrinks, the frame underneath isn't redrawn with --diff
+    overlay_width: usize,
+}
+
+impl Stats {
+    fn new(opts: &PlaybackOptions) -> Self {
+        Stats {
+            target_fps: target_frame_rate(opts),
+            last_shown: None,
+            interval: None,
+            dropped: 0,
+            overlay_width: 0,
+        }
+    }
+
+    /// Records that a frame has just been printed and flushed.
+    fn shown(&mut self, now: Instant) {
+        if let Some(last) = self.last_shown {
+            let interval = now - last;
+            // 指数移动平均, 让数字不会每帧都跳动
+            self.interval = Some(match self.interval {
+                Some(average) => average.mul_f64(0.9) + interval.mul_f64(0.1),
+                None => interval,
+            });
+        }
+        self.last_shown = Some(now);
+    }
+
+    /// Forgets the last frame time, so a pause doesn't count as one very slow frame.
+    fn pause(&mut self) {
+        self.last_shown = None;
+    }
+
+    /// The overlay text with cursor positioning, ending at column `width` of the top row.
+    fn overlay(&mut self, index: usize, width: usize) -> String {
+        let measured = self.interval.map_or(0.0, |interval| {
+            1.0 / interval.as_secs_f64().max(f64::EPSILON)
+        });
+        let text = format!(
+            " {:.1}/{:.1} fps  frame {}  dropped {} ",
+            measured,
+            self.target_fps,
+            index + 1,
+            self.dropped
+        );
+        self.overlay_width = self.overlay_width.max(text.len());
+        let column = width.saturating_sub(self.overlay_width) + 1;
+        format!(
+            "{}\x1B[1;{}H\x1B[7m{:>width$}{}",
+            RESET,
+            column,
+            text,
+            RESET,
+            width = self.overlay_width
+        )
+    }
+}
+
+/// Frames to present, looked up by index.
+trait FrameSource {
+    fn get(&mut self, index: usize) -> Option<&str>;
+    /// Whether frames before the current one can still be looked up, for seeking back.
+    fn rewindable(&self) -> bool;
+}
+
+impl FrameSource for &[String] {
+    fn get(&mut self, index: usize) -> Option<&str> {
+        <[String]>::get(self, index).map(String::as_str)
+    }
+
+    fn rewindable(&self) -> bool {
+        true
+    }
+}
+
+/// Buffered frames looked up from the last one.
+struct Reversed<'a>(&'a [String]);
+
+impl FrameSource for Reversed<'_> {
+    fn get(&mut self, index: usize) -> Option<&str> {
+        let index = self.0.len().checked_sub(index + 1)?;
+        self.0.get(index).map(String::as_str)
+    }
+
+    fn rewindable(&self) -> bool {
+        true
+    }
+}
+
+/// Buffered frames played forward and back, see [`bounce_order`].
+struct Bounced<'a>(&'a [String]);
+
+impl FrameSource for Bounced<'_> {
+    fn get(&mut self, index: usize) -> Option<&str> {
+        let len = self.0.len();
+        let index = if index < len {
+            index
+        } else {
+            // back from the second to last frame, stopping short of the first
+            (2 * len).checked_sub(index + 2).filter(|&back| back > 0)?
+        };
+        self.0.get(index).map(String::as_str)
+    }
+
+    fn rewindable(&self) -> bool {
+        true
+    }
+}
+
+/// Frames coming out of an iterator, which can only skip ahead.
+struct Sequential<I> {
+    frames: I,
+    next: usize,
+    current: Option<String>,
+}
+
+impl<I: Iterator<Item = String>> FrameSource for Sequential<I> {
+    fn get(&mut self, index: usize) -> Option<&str> {
+        if index >= self.next {
+            self.current = self.frames.nth(index - self.next);
+            self.next = index + 1;
+        } else if index + 1 != self.next {
+            return None;
+        }
+        self.current.as_deref()
+    }
+
+    fn rewindable(&self) -> bool {
+        false
+    }
+}
+
+/// Presents frames produced on a background thread, keeping at most `depth` of them in memory.
+fn present_prefetched(
+    frames: impl Iterator<Item = String> + Send,
+    depth: usize,
+    clock: &mut FrameClock,
+    session: &mut Session,
+) -> Result<()> {
+    // 后台线程按需解码, 有界通道限制内存占用
+    thread::scope(|s| {
+        let (tx, rx) = mpsc::sync_channel(depth);
+        s.spawn(move || {
+            for frame in frames {
+                // the receiver is gone once playback stops, so stop decoding too
+                if tx.send(frame).is_err() {
+                    break;
+                }
+            }
+        });
+        let mut frames = Sequential {
+            frames: rx.into_iter(),
+            next: 0,
+            current: None,
+        };
+        present(&mut frames, clock, session)
+    })
+}
+
+/// Prints frames in order on the frame clock, following the keyboard controls. While the
+/// audio track plays, frames that are overdue get dropped to stay aligned with it; pausing or
+/// seeking stops the sound as it can't follow.
+fn present(
+    frames: &mut impl FrameSource,
+    clock: &mut FrameClock,
+    session: &mut Session,
+) -> Result<()> {
+    // the clock runs at the playback speed, the seek position is in video time
+    let mut index = clock.frame_at(std::mem::take(&mut session.seek).div_f64(session.speed));
+    if session.step {
+        return present_steps(frames, index, clock, session);
+    }
+    clock.resync(index, Instant::now());
+    let mut pending = None;
+
+    loop {
+        if let Some(action) = pending
+            .take()
+            .or_else(|| session.action(Some(Duration::ZERO)))
+        {
+            match action {
+                Action::Quit => {
+                    session.quit = true;
+                    break;
+                }
+                Action::TogglePause => {
+                    stop_audio(&mut session.audio);
+                    // 暂停: 等待下一个按键, 除了再次按空格以外的按键也会被执行
+                    let next = session.action(None);
+                    if next != Some(Action::TogglePause) {
+                        pending = next;
+                    }
+                }
+                Action::Seek(direction) => {
+                    stop_audio(&mut session.audio);
+                    let position = clock.offset(index);
+                    let position = if direction < 0 {
+                        position.saturating_sub(session.seek_step)
+                    } else {
+                        position + session.seek_step
+                    };
+                    let target = clock.frame_at(position);
+                    index = if frames.rewindable() {
+                        target
+                    } else {
+                        target.max(index)
+                    };
+                }
+            }
+            // the clock is stopped while keys are handled and resumes from the current frame
+            clock.resync(index, Instant::now());
+            if let Some(stats) = &mut session.stats {
+                stats.pause();
+            }
+            continue;
+        }
+
+        if session.audio.is_some() && clock.is_overdue(index, Instant::now()) {
+            if let Some(stats) = &mut session.stats {
+                stats.dropped += 1;
+            }
+            session.timing.late += 1;
+            index += 1;
+            continue;
+        }
+        let Some(frame) = frames.get(index) else {
+            break;
+        };
+        let captioned = session.caption(frame, clock.offset(index));
+        let mut output = session.prepare(&captioned);
+        if let Some(overlay) = session.overlay(frame, index) {
+            output.to_mut().push_str(&overlay);
+        }
+
+        let delay = clock.delay(index, Instant::now());
+        if !session.paced {
+            // written out as fast as possible
+        } else if session.controls.is_some() {
+            // keep listening for keys while waiting for the frame to be due
+            if let Some(action) = session.action(Some(delay)) {
+                session.forget_screen();
+                pending = Some(action);
+                continue;
+            }
+        } else if !delay.is_zero() {
+            thread::sleep(delay);
+        }
+        show_frame(&output, session.escapes).context("could not write to the terminal")?;
+        let now = Instant::now();
+        if clock.is_overdue(index, now) {
+            session.timing.late += 1;
+        } else {
+            session.timing.shown += 1;
+        }
+        if let Some(stats) = &mut session.stats {
+            stats.shown(now);
+        }
+        index += 1;
+    }
+
+    Ok(())
+}
+
+/// Shows the frames one at a time starting at `index`, with its number in the top-right
+/// corner, leaving the clock stopped: space or the right arrow key moves on to the next frame
+/// and the left arrow key back to the previous one, until the user quits.
+fn present_steps(
+    frames: &mut impl FrameSource,
+    mut index: usize,
+    clock: &FrameClock,
+    session: &mut Session,
+) -> Result<()> {
+    if session.controls.is_none() {
+        bail!("stepping through frames needs a terminal to read keys from");
+    }
+    // the label never shrinks, the frame underneath isn't redrawn with --diff
+    let mut label_width = 0;
+
+    while let Some(frame) = frames.get(index) {
+        let captioned = session.caption(frame, clock.offset(index));
+        let mut output = session.prepare(&captioned).into_owned();
+        let label = format!(" frame {} ", index + 1);
+        if session.escapes {
+            label_width = label_width.max(label.len());
+            let column = session.width(frame).saturating_sub(label_width) + 1;
+            output.push_str(&format!(
+                "{}\x1B[1;{}H\x1B[7m{:>width$}{}",
+                RESET,
+                column,
+                label,
+                RESET,
+                width = label_width
+            ));
+        } else {
+            output.push_str(&label);
+            output.push('\n');
+        }
+        show_frame(&output, session.escapes).context("could not write to the terminal")?;
+
+        match session.action(None) {
+            Some(Action::TogglePause) | Some(Action::Seek(1..)) => {
+                // the last frame stays on screen
+                if frames.get(index + 1).is_some() {
+                    index += 1;
+                }
+            }
+            Some(Action::Seek(_)) if frames.rewindable() => index = index.saturating_sub(1),
+            Some(Action::Seek(_)) => {}
+            Some(Action::Quit) | None => {
+                session.quit = true;
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn show_frame(frame: &str, home: bool) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    if home {
+        // 仅移动光标到起始位置
+        write!(stdout, "\x1B[H")?;
+    }
+    // 使用单次输出
+    write!(stdout, "{}", frame)?;
+    // 立即刷新输出
+    stdout.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_clock_late_frames_do_not_accumulate_sleep_debt() {
+        let start = Instant::now();
+        let clock = FrameClock::starting_at(start, 100.0);
+
+        // 55ms in, frames 0 through 5 are due already and must not sleep at all
+        let now = start + Duration::from_millis(55);
+        for index in 0..=5 {
+            assert_eq!(clock.delay(index, now), Duration::ZERO);
+        }
+        // the next frame only waits for the remainder of its own slot
+        assert_eq!(clock.delay(6, now), Duration::from_millis(5));
+    }
+
+    #[test]
+    fn frame_clock_overdue_after_a_full_period() {
+        let start = Instant::now();
+        let clock = FrameClock::starting_at(start, 100.0);
+
+        assert!(!clock.is_overdue(2, start + Duration::from_millis(25)));
+        assert!(clock.is_overdue(2, start + Duration::from_millis(31)));
+    }
+
+    #[test]
+    fn frame_clock_seeks_to_the_frame_on_screen() {
+        let start = Instant::now();
+        let clock = FrameClock::starting_at(start, 100.0);
+        assert_eq!(clock.frame_at(Duration::from_millis(55)), 5);
+
+        let delays = [10, 50, 20].map(Duration::from_millis);
+        let clock = FrameClock::with_delays(start, &delays);
+        assert_eq!(clock.frame_at(Duration::from_millis(30)), 1);
+        assert_eq!(clock.frame_at(Duration::from_millis(60)), 2);
+        // past the end, frames keep the last delay
+        assert_eq!(clock.frame_at(Duration::from_millis(125)), 5);
+    }
+
+    #[test]
+    fn frame_clock_reverses_per_frame_delays() {
+        let opts = PlaybackOptions {
+            frame_delays: Some(vec![Duration::from_millis(10), Duration::from_millis(30)]),
+            reverse: true,
+            ..PlaybackOptions::default()
+        };
+        let clock = FrameClock::new(&opts);
+        assert_eq!(clock.offset(1), Duration::from_millis(30));
+        assert_eq!(clock.offset(2), Duration::from_millis(40));
+    }
+
+    #[test]
+    fn timecode_goes_in_the_corner_of_the_free_rows() {
+        let frame = "..........\n..........\n..........\n";
+        let time = Duration::from_secs(75);
+        let timecode = |corner, length| Timecode { corner, length };
+
+        let burned = timecode(Corner::BottomRight, None).burn_in(frame, time, 10, 0..3);
+        assert_eq!(burned, "..........\n..........\n... 01:15 \n");
+        let burned = timecode(Corner::TopLeft, None).burn_in(frame, time, 10, 1..2);
+        assert_eq!(burned, "..........\n 01:15 ...\n..........\n");
+        // too narrow for the length, cut off rather than spilling over the edge
+        let length = Some(Duration::from_secs(600));
+        let burned = timecode(Corner::BottomLeft, length).burn_in(frame, time, 10, 0..2);
+        assert_eq!(burned, "..........\n 01:15 / 1\n..........\n");
+        assert_eq!(
+            timecode(Corner::TopRight, length).burn_in(frame, time, 10, 2..2),
+            frame
+        );
+    }
+
+    #[test]
+    fn undecodable_frames_repeat_the_one_before() {
+        let frame = |s: &str| Some(s.to_string());
+        let frames = vec![None, None, frame("a"), None, None, frame("b"), None];
+        assert_eq!(
+            repeat_previous(frames),
+            vec!["a", "a", "a", "a", "a", "b", "b"]
+        );
+        assert!(repeat_previous(vec![None, None]).is_empty());
+    }
+
+    #[test]
+    fn bounce_turns_at_the_ends() {
+        let frames: Vec<String> = ["a", "b", "c", "d"].map(String::from).to_vec();
+        let mut bounced = Bounced(&frames);
+        let cycle: Vec<String> = (0..7)
+            .map_while(|i| bounced.get(i).map(String::from))
+            .collect();
+        assert_eq!(cycle, ["a", "b", "c", "d", "c", "b"]);
+        assert_eq!(bounce_order(4).collect::<Vec<_>>(), [0, 1, 2, 3, 2, 1]);
+        assert_eq!(bounce_order(1).collect::<Vec<_>>(), [0]);
+
+        // each frame keeps its delay on the way back
+        let opts = PlaybackOptions {
+            frame_delays: Some([10, 20, 40].map(Duration::from_millis).to_vec()),
+            bounce: true,
+            ..PlaybackOptions::default()
+        };
+        let clock = FrameClock::new(&opts);
+        assert_eq!(clock.offset(3), Duration::from_millis(70));
+        assert_eq!(clock.offset(4), Duration::from_millis(90));
+    }
+
+    #[test]
+    fn frame_clock_follows_per_frame_delays() {
+        let start = Instant::now();
+        let delays = [10, 50, 20].map(Duration::from_millis);
+        let clock = FrameClock::with_delays(start, &delays);
+
+        assert_eq!(clock.due(0), start);
+        assert_eq!(clock.due(1), start + Duration::from_millis(10));
+        assert_eq!(clock.due(2), start + Duration::from_millis(60));
+        assert_eq!(clock.due(3), start + Duration::from_millis(80));
+        assert!(!clock.is_overdue(1, start + Duration::from_millis(55)));
+        assert!(clock.is_overdue(1, start + Duration::from_millis(61)));
+    }
+}
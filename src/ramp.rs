@@ -0,0 +1,308 @@
+/*
+   This file is part of term-video.
+
+   term-video is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   term-video is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with term-video.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Mapping luminosity onto a character ramp, either in equal bands or by how much of a cell
+//! each character covers.
+//!
+//! The ink of a character isn't spread evenly along a ramp: `@` covers far more of its cell
+//! than `.`, and letters in the middle of a ramp bunch up. Weighting the ramp by coverage
+//! gives every character the brightness it actually has on screen, so evenly spaced
+//! luminosity comes out as evenly spaced brightness.
+
+use clap::ValueEnum;
+
+/// Share of its cell every printable ASCII character covers, measured on DejaVu Sans Mono,
+/// plus the shade blocks at their nominal density.
+pub const COVERAGE: &[(char, f32)] = &[
+    (' ', 0.000),
+    ('!', 0.085),
+    ('"', 0.066),
+    ('#', 0.242),
+    ('$', 0.209),
+    ('%', 0.185),
+    ('&', 0.229),
+    ('\'', 0.033),
+    ('(', 0.113),
+    (')', 0.113),
+    ('*', 0.096),
+    ('+', 0.112),
+    (',', 0.044),
+    ('-', 0.029),
+    ('.', 0.026),
+    ('/', 0.109),
+    ('0', 0.244),
+    ('1', 0.159),
+    ('2', 0.183),
+    ('3', 0.191),
+    ('4', 0.198),
+    ('5', 0.199),
+    ('6', 0.231),
+    ('7', 0.145),
+    ('8', 0.250),
+    ('9', 0.231),
+    (':', 0.052),
+    (';', 0.070),
+    ('<', 0.118),
+    ('=', 0.123),
+    ('>', 0.118),
+    ('?', 0.124),
+    ('@', 0.276),
+    ('A', 0.218),
+    ('B', 0.274),
+    ('C', 0.164),
+    ('D', 0.244),
+    ('E', 0.221),
+    ('F', 0.178),
+    ('G', 0.216),
+    ('H', 0.238),
+    ('I', 0.175),
+    ('J', 0.158),
+    ('K', 0.231),
+    ('L', 0.145),
+    ('M', 0.275),
+    ('N', 0.274),
+    ('O', 0.236),
+    ('P', 0.210),
+    ('Q', 0.253),
+    ('R', 0.251),
+    ('S', 0.199),
+    ('T', 0.157),
+    ('U', 0.220),
+    ('V', 0.189),
+    ('W', 0.269),
+    ('X', 0.200),
+    ('Y', 0.155),
+    ('Z', 0.197),
+    ('[', 0.138),
+    ('\\', 0.109),
+    (']', 0.138),
+    ('^', 0.071),
+    ('_', 0.034),
+    ('`', 0.022),
+    ('a', 0.192),
+    ('b', 0.218),
+    ('c', 0.129),
+    ('d', 0.218),
+    ('e', 0.186),
+    ('f', 0.145),
+    ('g', 0.239),
+    ('h', 0.191),
+    ('i', 0.134),
+    ('j', 0.143),
+    ('k', 0.191),
+    ('l', 0.127),
+    ('m', 0.216),
+    ('n', 0.164),
+    ('o', 0.178),
+    ('p', 0.218),
+    ('q', 0.218),
+    ('r', 0.107),
+    ('s', 0.150),
+    ('t', 0.141),
+    ('u', 0.163),
+    ('v', 0.138),
+    ('w', 0.188),
+    ('x', 0.146),
+    ('y', 0.172),
+    ('z', 0.142),
+    ('{', 0.153),
+    ('|', 0.120),
+    ('}', 0.151),
+    ('~', 0.060),
+    ('░', 0.25),
+    ('▒', 0.5),
+    ('▓', 0.75),
+    ('█', 1.0),
+];
+
+/// How luminosity picks characters off the ramp.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum RampMode {
+    /// Equal bands of luminosity per character, in the order given
+    Linear,
+    /// Characters ordered by their coverage, each standing for the brightness it covers
+    Coverage,
+}
+
+/// Luminosity levels of the characters of a ramp, along with the thresholds picking them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ramp {
+    /// Brightness each character stands for, ascending from 0 to 255
+    levels: Vec<f32>,
+    /// Luminosity from which on every character but the first is picked
+    thresholds: Vec<f32>,
+}
+
+impl Ramp {
+    /// `steps` equal bands of luminosity, the way [`get_pixel_char`] picks characters.
+    ///
+    /// [`get_pixel_char`]: crate::render::get_pixel_char
+    pub fn linear(steps: usize) -> Ramp {
+        let steps = steps.max(1);
+        let max_level = steps.saturating_sub(1).max(1) as f32;
+        Ramp {
+            levels: (0..steps).map(|i| i as f32 * 255.0 / max_level).collect(),
+            thresholds: (1..steps)
+                .map(|i| (i * 256) as f32 / steps as f32)
+                .collect(),
+        }
+        .validated()
+    }
+
+    /// A ramp of characters covering the given shares of their cells, which must be sorted
+    /// ascending. Coverage is stretched so the sparsest character stands for black and the
+    /// densest for white, and every luminosity picks the character closest to it.
+    pub fn weighted(coverage: &[f32]) -> Ramp {
+        let (sparsest, densest) = match coverage {
+            [first, .., last] if last > first => (*first, *last),
+            _ => return Ramp::linear(coverage.len()),
+        };
+        let levels: Vec<f32> = coverage
+            .iter()
+            .map(|share| (share - sparsest) / (densest - sparsest) * 255.0)
+            .collect();
+        let thresholds = levels
+            .windows(2)
+            .map(|pair| (pair[0] + pair[1]) / 2.0)
+            .collect();
+        Ramp { levels, thresholds }.validated()
+    }
+
+    /// Checks in debug builds that the bands of the characters follow each other from 0 to
+    /// 255: one threshold between every two levels, in ascending order, so every luminosity
+    /// falls into exactly one band. Bands may be empty when there are more than 256 levels.
+    fn validated(self) -> Ramp {
+        debug_assert_eq!(self.thresholds.len() + 1, self.levels.len());
+        debug_assert!(self.thresholds.windows(2).all(|pair| pair[0] <= pair[1]));
+        debug_assert!(self
+            .thresholds
+            .iter()
+            .all(|threshold| (0.0..=256.0).contains(threshold)));
+        self
+    }
+
+    /// Index of the character for `luminosity`, found by binary search over the thresholds.
+    pub fn index(&self, luminosity: u8) -> usize {
+        self.thresholds
+            .partition_point(|&threshold| threshold <= luminosity as f32)
+    }
+
+    /// Brightness every character stands for, for error diffusion to aim at.
+    pub fn levels(&self) -> &[f32] {
+        &self.levels
+    }
+}
+
+/// How much of its cell `ch` covers, from `custom` first and [`COVERAGE`] otherwise.
+pub fn coverage_of(ch: char, custom: &[(char, f32)]) -> Option<f32> {
+    custom
+        .iter()
+        .chain(COVERAGE)
+        .find(|&&(known, _)| known == ch)
+        .map(|&(_, share)| share)
+}
+
+/// `chars` sorted from the sparsest to the densest along with the ramp weighted by their
+/// coverage. Characters without a known coverage are left out.
+pub fn weighted_ramp(chars: &[char], custom: &[(char, f32)]) -> (Vec<char>, Ramp) {
+    let mut covered: Vec<(char, f32)> = chars
+        .iter()
+        .filter_map(|&ch| Some((ch, coverage_of(ch, custom)?)))
+        .collect();
+    // a stable sort keeps the order given for characters covering the same share
+    covered.sort_by(|a, b| a.1.total_cmp(&b.1));
+    let shares: Vec<f32> = covered.iter().map(|&(_, share)| share).collect();
+    (
+        covered.into_iter().map(|(ch, _)| ch).collect(),
+        Ramp::weighted(&shares),
+    )
+}
+
+/// Parses a `char=coverage` pair, like `@=0.28`, with the coverage between 0 and 1.
+pub fn parse_coverage(s: &str) -> Option<(char, f32)> {
+    let mut chars = s.chars();
+    let ch = chars.next()?;
+    let share: f32 = chars.as_str().strip_prefix('=')?.trim().parse().ok()?;
+    (0.0..=1.0).contains(&share).then_some((ch, share))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::{get_pixel_char, ASCII_CHARSET};
+
+    #[test]
+    fn linear_ramp_matches_equal_bands() {
+        let charset: Vec<char> = ASCII_CHARSET.chars().collect();
+        let ramp = Ramp::linear(charset.len());
+        for luminosity in 0..=255 {
+            assert_eq!(
+                charset[ramp.index(luminosity)],
+                get_pixel_char(luminosity, &charset)
+            );
+        }
+    }
+
+    #[test]
+    fn linear_bands_cover_every_luminosity_once() {
+        for steps in 1..=300 {
+            let charset: Vec<char> = (0..steps as u32)
+                .map(|i| char::from_u32(0x100 + i).unwrap())
+                .collect();
+            let ramp = Ramp::linear(steps);
+            let indices: Vec<usize> = (0..=255).map(|l| ramp.index(l)).collect();
+            // the bands run from the first character to the last without going back
+            assert_eq!(indices[0], 0, "{} steps", steps);
+            assert_eq!(indices[255], 255 * steps / 256, "{} steps", steps);
+            assert!(indices.windows(2).all(|pair| pair[0] <= pair[1]));
+            for (luminosity, &index) in indices.iter().enumerate() {
+                assert_eq!(charset[index], get_pixel_char(luminosity as u8, &charset));
+            }
+            // up to one character per luminosity, none of them is left out
+            if steps <= 256 {
+                assert!(indices.windows(2).all(|pair| pair[1] - pair[0] <= 1));
+            }
+        }
+    }
+
+    #[test]
+    fn weighted_ramp_picks_the_nearest_coverage() {
+        let (chars, ramp) = weighted_ramp(&['@', ' ', '.', '#'], &[('#', 0.2)]);
+        assert_eq!(chars, vec![' ', '.', '#', '@']);
+        assert_eq!(ramp.levels()[0], 0.0);
+        assert_eq!(ramp.levels()[3], 255.0);
+        // '.' stands for 24 and '#' for 185, so the thresholds are at 12, 104 and 220
+        assert_eq!(ramp.index(12), 0);
+        assert_eq!(ramp.index(13), 1);
+        assert_eq!(ramp.index(104), 1);
+        assert_eq!(ramp.index(105), 2);
+        assert_eq!(ramp.index(219), 2);
+        assert_eq!(ramp.index(220), 3);
+        // luminosity only ever moves up the ramp
+        assert!((0..255).all(|l| ramp.index(l) <= ramp.index(l + 1)));
+    }
+
+    #[test]
+    fn coverage_pairs_parse() {
+        assert_eq!(parse_coverage("@=0.28"), Some(('@', 0.28)));
+        assert_eq!(parse_coverage("==0.12"), Some(('=', 0.12)));
+        assert_eq!(parse_coverage(",=0"), Some((',', 0.0)));
+        for invalid in ["@", "@0.3", "@=", "@=1.5", "ab=0.1", ""] {
+            assert_eq!(parse_coverage(invalid), None, "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,213 @@
+/*
+   This file is part of term-video.
+
+   term-video is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   term-video is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with term-video.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Rendered animations saved to a file, so they can be replayed without the source video.
+//!
+//! The format is plain text: a `term-video recording 1` header line, an `fps <rate>` line,
+//! optionally a `size <columns>x<rows>` line with the playback area the frames were rendered
+//! for, then every frame as a `frame <length> [<delay>]` line followed by exactly `length` bytes
+//! of rendered text. The delay, in microseconds, is only present for sources with per-frame
+//! timing and then given for every frame.
+
+use crate::{parse::parse_dimensions, player::MIN_FRAME_RATE};
+use anyhow::{bail, Context, Result};
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+    time::Duration,
+};
+
+const HEADER: &str = "term-video recording 1";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recording {
+    pub frame_rate: f64,
+    /// Columns and rows the frames were rendered for, unknown in older recordings
+    pub size: Option<(u32, u32)>,
+    pub frames: Vec<String>,
+    /// How long each frame is shown, overriding `frame_rate` when set
+    pub frame_delays: Option<Vec<Duration>>,
+}
+
+impl Recording {
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let save = || -> Result<()> {
+            let mut out = BufWriter::new(File::create(path)?);
+            self.write(&mut out)?;
+            out.flush()?;
+            Ok(())
+        };
+        save().with_context(|| format!("could not write recording {}", path.display()))
+    }
+
+    /// Writes every frame to a text file of its own in `dir`, numbered from
+    /// `frame-0000001.txt` like the extracted frames they were rendered from. Frames left
+    /// over from a longer dump into the same folder are removed.
+    pub fn dump_frames(&self, dir: &Path) -> Result<()> {
+        let path = |number: usize| dir.join(format!("frame-{:07}.txt", number));
+        fs::create_dir_all(dir)
+            .with_context(|| format!("could not create folder {}", dir.display()))?;
+        for (index, frame) in self.frames.iter().enumerate() {
+            let path = path(index + 1);
+            fs::write(&path, frame)
+                .with_context(|| format!("could not write frame {}", path.display()))?;
+        }
+        let mut number = self.frames.len() + 1;
+        while fs::remove_file(path(number)).is_ok() {
+            number += 1;
+        }
+        Ok(())
+    }
+
+    /// Writes the recording in the file format to `out`.
+    pub fn write(&self, mut out: impl Write) -> io::Result<()> {
+        writeln!(out, "{}", HEADER)?;
+        writeln!(out, "fps {}", self.frame_rate)?;
+        if let Some((columns, rows)) = self.size {
+            writeln!(out, "size {}x{}", columns, rows)?;
+        }
+        for (index, frame) in self.frames.iter().enumerate() {
+            write!(out, "frame {}", frame.len())?;
+            if let Some(delay) = self.frame_delays.as_ref().and_then(|d| d.get(index)) {
+                write!(out, " {}", delay.as_micros())?;
+            }
+            writeln!(out)?;
+            out.write_all(frame.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Recording> {
+        let data = fs::read_to_string(path)
+            .with_context(|| format!("could not read recording {}", path.display()))?;
+        Self::parse(&data).with_context(|| format!("{} is not a valid recording", path.display()))
+    }
+
+    /// Reads a recording from the contents of a recording file.
+    pub fn parse(data: &str) -> Result<Recording> {
+        let mut rest = data;
+        if next_line(&mut rest) != Some(HEADER) {
+            bail!("missing header");
+        }
+        let frame_rate = next_line(&mut rest)
+            .and_then(|line| line.strip_prefix("fps "))
+            .and_then(|fps| fps.parse().ok())
+            .filter(|fps: &f64| fps.is_finite() && *fps >= MIN_FRAME_RATE)
+            .context("missing frame rate")?;
+        let mut size = None;
+        if rest.starts_with("size ") {
+            size = next_line(&mut rest)
+                .and_then(|line| line.strip_prefix("size "))
+                .and_then(parse_dimensions);
+            if size.is_none() {
+                bail!("bad size");
+            }
+        }
+
+        let mut frames = Vec::new();
+        let mut delays = Vec::new();
+        while !rest.is_empty() {
+            let header = next_line(&mut rest).context("truncated frame header")?;
+            let mut fields = header
+                .strip_prefix("frame ")
+                .context("bad frame header")?
+                .split(' ');
+            let len: usize = fields
+                .next()
+                .and_then(|len| len.parse().ok())
+                .context("bad frame length")?;
+            if let Some(delay) = fields.next() {
+                let delay = delay.parse().context("bad frame delay")?;
+                delays.push(Duration::from_micros(delay));
+            }
+            let frame = rest
+                .get(..len)
+                .with_context(|| format!("frame {} is truncated", frames.len() + 1))?;
+            frames.push(frame.to_owned());
+            rest = &rest[len..];
+        }
+
+        let frame_delays = match delays.len() {
+            0 => None,
+            n if n == frames.len() => Some(delays),
+            _ => bail!("only some frames have a delay"),
+        };
+        Ok(Recording {
+            frame_rate,
+            size,
+            frames,
+            frame_delays,
+        })
+    }
+}
+
+/// Splits the first line off `rest`, without its newline.
+fn next_line<'a>(rest: &mut &'a str) -> Option<&'a str> {
+    let (line, tail) = rest.split_once('\n')?;
+    *rest = tail;
+    Some(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recording_round_trips() {
+        let recording = Recording {
+            frame_rate: 24000.0 / 1001.0,
+            size: Some((2, 2)),
+            frames: vec![String::from(" .\n@\x1B[0m\n"), String::from("frame 2\n\n")],
+            frame_delays: Some(vec![Duration::from_millis(40), Duration::from_millis(100)]),
+        };
+        let path = std::env::temp_dir().join(format!("term-video-test-{}.rec", std::process::id()));
+        recording.save(&path).unwrap();
+        let loaded = Recording::load(&path);
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(loaded.unwrap(), recording);
+    }
+
+    #[test]
+    fn frames_are_dumped_one_per_file() {
+        let dir = std::env::temp_dir().join(format!("term-video-test-{}-dump", std::process::id()));
+        let recording = |frames: &[&str]| Recording {
+            frame_rate: 30.0,
+            size: None,
+            frames: frames.iter().map(|frame| frame.to_string()).collect(),
+            frame_delays: None,
+        };
+        recording(&["a\n", "b\n", "c\n"]).dump_frames(&dir).unwrap();
+        recording(&["d\n", "e\n"]).dump_frames(&dir).unwrap();
+        let second = fs::read_to_string(dir.join("frame-0000002.txt"));
+        let leftover = dir.join("frame-0000003.txt").exists();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(second.unwrap(), "e\n");
+        assert!(!leftover);
+    }
+
+    #[test]
+    fn recording_rejects_truncated_frames() {
+        assert!(Recording::parse("term-video recording 1\nfps 30\nframe 10\nabc").is_err());
+        assert!(Recording::parse("term-video recording 1\nfps 30\nsize 80\nframe 1\na").is_err());
+        // the size is optional
+        let recording = Recording::parse("term-video recording 1\nfps 30\nframe 1\na").unwrap();
+        assert_eq!(recording.size, None);
+    }
+}
@@ -0,0 +1,1305 @@
+/*
+   This file is part of term-video.
+
+   term-video is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   term-video is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with term-video.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::{
+    color::{luminance, ColorMode, LumaMode, MapChannel, RESET},
+    dither::dither_to,
+    edges::sobel,
+    kitty,
+    palette::Palette,
+    ramp::{weighted_ramp, Ramp, RampMode},
+    sixel,
+    smooth::Hysteresis,
+    tone::{stretch, ToneCurve},
+};
+use clap::ValueEnum;
+use image::{DynamicImage, GenericImageView, Rgb, RgbImage, Rgba, RgbaImage};
+use std::borrow::Cow;
+
+pub const ASCII_CHARSET: &str = " .,-~:;=!*#$@";
+pub const BLOCKS_CHARSET: &str = " ░▒▓█";
+pub const DETAILED_CHARSET: &str =
+    " .'`^\",:;Il!i><~+_-?][}{1)(|\\/tfjrxnuvczXYUJCLQ0OZmwqpdbkhao*#MW&8%B@$";
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum CharsetPreset {
+    /// The classic 13 character ramp
+    Ascii,
+    /// Unicode shade blocks
+    Blocks,
+    /// 70 character ramp for large resolutions
+    Detailed,
+}
+
+impl CharsetPreset {
+    pub fn chars(self) -> &'static str {
+        match self {
+            CharsetPreset::Ascii => ASCII_CHARSET,
+            CharsetPreset::Blocks => BLOCKS_CHARSET,
+            CharsetPreset::Detailed => DETAILED_CHARSET,
+        }
+    }
+}
+
+const UPPER_HALF: char = '▀';
+const LOWER_HALF: char = '▄';
+const FULL_BLOCK: char = '█';
+
+// Braille patterns start at U+2800, every dot is one bit of the offset from it
+const BRAILLE_BLANK: u32 = 0x2800;
+// dots numbered column by column, 1 to 3 and 4 to 6 from the top, the later added bottom
+// row 7 and 8 takes the high bits
+const BRAILLE_DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+// shades of gray in the Sixel palette of mono mode
+const SIXEL_GRAYS: u32 = 32;
+
+/// How source pixels are turned into character cells.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RenderMode {
+    /// One pixel per cell, mapped onto the character ramp
+    Ascii,
+    /// Two vertically stacked pixels per cell, drawn as an upper half block with the top
+    /// pixel as foreground and the bottom pixel as background color
+    HalfBlock,
+    /// One pixel per cell, drawn as a space with the pixel as background color. Without
+    /// color there is nothing to fill cells with, so they fall back to the character ramp
+    Blocks,
+    /// A 2x4 block of pixels per cell, drawn as the Unicode Braille pattern with a dot for
+    /// every pixel at least as bright as the threshold
+    Braille { threshold: u8 },
+    /// The frame as an actual image in the Sixel graphics format, with every character cell
+    /// assumed to be `cell_width`x`cell_height` pixels
+    Sixel { cell_width: u32, cell_height: u32 },
+    /// The frame as a PNG image sent with the Kitty graphics protocol, scaled by the terminal
+    /// to cover the cells of `cell_width`x`cell_height` pixel blocks it is extracted at
+    Kitty { cell_width: u32, cell_height: u32 },
+}
+
+impl RenderMode {
+    /// Number of source pixels covered by one character cell, horizontally and vertically.
+    pub fn cell_pixels(self) -> (u32, u32) {
+        match self {
+            RenderMode::Ascii | RenderMode::Blocks => (1, 1),
+            RenderMode::HalfBlock => (1, 2),
+            RenderMode::Braille { .. } => (2, 4),
+            RenderMode::Sixel {
+                cell_width,
+                cell_height,
+            }
+            | RenderMode::Kitty {
+                cell_width,
+                cell_height,
+            } => (cell_width, cell_height),
+        }
+    }
+
+    /// Whether frames are terminal graphics rather than lines of characters.
+    pub fn is_graphics(self) -> bool {
+        matches!(self, RenderMode::Sixel { .. } | RenderMode::Kitty { .. })
+    }
+}
+
+/// How the luminosity range of frames is stretched to the full range before rendering.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AutoLevels {
+    /// Every frame by its own darkest and brightest pixel
+    PerFrame,
+    /// Every frame by the same luminosity range, `lo..=hi`
+    Range(u8, u8),
+}
+
+/// What fills the background of character cells in the color modes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Background {
+    /// A fixed color behind every cell, padding included
+    Color([u8; 3]),
+    /// A darkened version of the cell's own color
+    Darkened,
+}
+
+impl Background {
+    /// Share of the cell's color a darkened background keeps, out of 256.
+    const DARKENED: u32 = 80;
+
+    fn behind(self, rgb: [u8; 3]) -> [u8; 3] {
+        match self {
+            Background::Color(color) => color,
+            Background::Darkened => rgb.map(|c| (c as u32 * Self::DARKENED / 256) as u8),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RenderOptions {
+    /// Playback area in character cells
+    pub width: u32,
+    pub height: u32,
+    pub mode: RenderMode,
+    pub color_mode: ColorMode,
+    /// Weighting of the color channels in the luminosity that picks characters
+    pub luma_mode: LumaMode,
+    /// The property of the pixels that picks characters, luminosity or one of saturation and
+    /// hue for stylized output
+    pub map_channel: MapChannel,
+    /// Character ramp ordered from darkest to brightest, must not be empty
+    pub charset: Vec<char>,
+    /// How luminosity picks characters off the ramp. With `RampMode::Coverage` the ramp is
+    /// reordered by coverage and characters whose coverage isn't known are left out
+    pub ramp: RampMode,
+    /// Coverage of characters missing from [`COVERAGE`](crate::ramp::COVERAGE), or to use
+    /// instead of the measured one, as shares of the cell between 0 and 1
+    pub coverage: Vec<(char, f32)>,
+    /// Added to every pixel value, positive values brighten the picture
+    pub brightness: f32,
+    /// Scales pixel values around the midpoint, 1.0 leaves them unchanged
+    pub contrast: f32,
+    /// Gamma correction, values above 1.0 bring out shadow detail, must be positive
+    pub gamma: f32,
+    /// Flip luminosity so bright pixels get the sparsest characters, for light terminals
+    pub invert: bool,
+    /// Number of distinct luminosity levels, fewer than 256 give a deliberately banded look
+    pub levels: u16,
+    /// Spread the error of mapping luminosity onto the character ramp over neighbouring
+    /// pixels with Floyd–Steinberg dithering, smoothing out banding in gradients
+    pub dither: bool,
+    /// Map the Sobel edge magnitude instead of the brightness onto the ramp, for a line art
+    /// look
+    pub edges: bool,
+    /// Edge magnitudes below this are treated as flat, only used with `edges`
+    pub edge_threshold: u8,
+    /// Keep the character of a cell while its luminosity stays within `smooth_threshold`
+    /// of the one it was drawn with, against flicker. Only frames rendered in order with
+    /// [`Renderer::render_next_frame`] are smoothed
+    pub smooth: bool,
+    /// How far luminosity has to move for a cell to change with `smooth`
+    pub smooth_threshold: u8,
+    /// Stretch the luminosity range of frames to the full range first, for footage that
+    /// leaves part of it unused
+    pub auto_levels: Option<AutoLevels>,
+    /// Background of the cells in the color modes, the terminal's own without one. Half
+    /// blocks already use the background for their bottom pixel and ignore it
+    pub background: Option<Background>,
+    /// Foreground color every line of mono output is drawn in, ignored in the color modes
+    pub tint: Option<[u8; 3]>,
+    /// Number of colors in the fixed palette the color modes snap every cell to, built from
+    /// a sample of the frames with [`Renderer::with_palette`]. Text modes only
+    pub palette_size: Option<u16>,
+    /// Frames come in this many times larger on both axes than the pixel grid of `mode`, and
+    /// every block of that many pixels per side is averaged into one pixel of the grid,
+    /// against aliasing. 1 takes frames as they are
+    pub supersample: u32,
+    /// Pixels less opaque than this are see-through, blank like the padding around the
+    /// frame, instead of drawn in whatever color they hide. 0 draws every pixel however
+    /// transparent it is. Frames without an alpha channel are always drawn in full
+    pub alpha_threshold: u8,
+    /// Number of frames over which the picture fades in from an empty screen at the start
+    /// of the video and out again at its end. Only frames rendered with
+    /// [`Renderer::render_frame_at`] are faded
+    pub fade: u32,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            width: 80,
+            height: 24,
+            mode: RenderMode::Ascii,
+            color_mode: ColorMode::Mono,
+            luma_mode: LumaMode::Rec709,
+            map_channel: MapChannel::Luma,
+            charset: ASCII_CHARSET.chars().collect(),
+            ramp: RampMode::Linear,
+            coverage: Vec::new(),
+            brightness: 0.0,
+            contrast: 1.0,
+            gamma: 1.0,
+            invert: false,
+            levels: 256,
+            dither: false,
+            edges: false,
+            edge_threshold: 0,
+            smooth: false,
+            smooth_threshold: 8,
+            auto_levels: None,
+            background: None,
+            tint: None,
+            palette_size: None,
+            supersample: 1,
+            alpha_threshold: 1,
+            fade: 0,
+        }
+    }
+}
+
+impl RenderOptions {
+    /// Size in pixels of the frames to render: the pixel grid of `mode` over the playback
+    /// area, `supersample` times larger.
+    pub fn frame_size(&self) -> (u32, u32) {
+        let (cell_width, cell_height) = self.mode.cell_pixels();
+        let factor = self.supersample.max(1);
+        (
+            self.width * cell_width * factor,
+            self.height * cell_height * factor,
+        )
+    }
+}
+
+/// Turns decoded frames into printable text.
+#[derive(Clone)]
+pub struct Renderer {
+    options: RenderOptions,
+    tone: ToneCurve,
+    /// The characters of the ramp in the order `ramp` indexes them
+    chars: Vec<char>,
+    ramp: Ramp,
+    palette: Option<Palette>,
+}
+
+impl Renderer {
+    pub fn new(options: RenderOptions) -> Self {
+        let tone = ToneCurve::new(&options);
+        let (chars, ramp) = match options.ramp {
+            RampMode::Coverage => match weighted_ramp(&options.charset, &options.coverage) {
+                (chars, ramp) if !chars.is_empty() => (chars, ramp),
+                // none of the characters has a known coverage
+                _ => (options.charset.clone(), Ramp::linear(options.charset.len())),
+            },
+            RampMode::Linear => (options.charset.clone(), Ramp::linear(options.charset.len())),
+        };
+        Renderer {
+            options,
+            tone,
+            chars,
+            ramp,
+            palette: None,
+        }
+    }
+
+    pub fn options(&self) -> &RenderOptions {
+        &self.options
+    }
+
+    /// Columns and rows of the rendered frames.
+    pub fn size(&self) -> (u32, u32) {
+        (self.options.width, self.options.height)
+    }
+
+    /// The renderer snapping colors to a median cut palette of `palette_size` colors that
+    /// represents `frames`, as far as it's set.
+    pub fn with_palette(self, frames: &[DynamicImage]) -> Renderer {
+        let Some(size) = self.options.palette_size else {
+            return self;
+        };
+        let pixels = frames
+            .iter()
+            .flat_map(|frame| self.adjusted_colors(frame))
+            .collect();
+        Renderer {
+            palette: Some(Palette::median_cut(pixels, size as usize)),
+            ..self
+        }
+    }
+
+    pub fn palette(&self) -> Option<&Palette> {
+        self.palette.as_ref()
+    }
+
+    /// The colors of the pixels of `frame` after the tonal adjustments, what a palette has to
+    /// represent.
+    fn adjusted_colors(&self, frame: &DynamicImage) -> Vec<[u8; 3]> {
+        let frame = &*self.downsampled(frame);
+        let pixels = match self.options.auto_levels {
+            Some(levels) => self.stretch_levels(frame, levels).to_rgb8(),
+            None => frame.to_rgb8(),
+        };
+        pixels
+            .pixels()
+            .map(|pixel| self.tone.apply_rgb(pixel.0))
+            .collect()
+    }
+
+    /// The color a cell of pixel color `rgb` is drawn in: with the tonal adjustments and,
+    /// with a palette, the closest color in it.
+    fn color(&self, rgb: [u8; 3]) -> [u8; 3] {
+        let rgb = self.tone.apply_rgb(rgb);
+        match &self.palette {
+            Some(palette) => palette.nearest(rgb),
+            None => rgb,
+        }
+    }
+
+    /// Converts a single decoded frame into its printable text representation.
+    ///
+    /// Pixel values go through these adjustments, in order:
+    ///
+    /// 1. with `auto_levels`, the luminosity range of the frame, or the given one, stretched
+    ///    to `0..=255` on all channels, see [`stretch`](crate::tone::stretch)
+    /// 2. brightness and contrast, as `(value - 128) * contrast + 128 + brightness` clamped
+    ///    to `0..=255`
+    /// 3. gamma correction, `255 * (value / 255)^(1 / gamma)`
+    /// 4. with `edges`, the Sobel gradient magnitude replaces the luminosity, magnitudes
+    ///    below `edge_threshold` dropped to zero
+    /// 5. inversion, `255 - value`
+    /// 6. with `fade`, scaling towards 0 over the first and last frames, see
+    ///    [`render_frame_at`](Self::render_frame_at)
+    /// 7. posterization into `levels` buckets, see [`quantize`](crate::tone::quantize)
+    ///
+    /// Color channels get the first three steps and the fade, the luminosity driving the
+    /// character choice gets all of them. With `dither` set the adjusted luminosity is then
+    /// error diffused across the frame before characters, or lit half blocks in mono mode,
+    /// are picked.
+    pub fn render_frame(&self, frame: &DynamicImage) -> String {
+        self.render(frame, None)
+    }
+
+    /// Like [`render_frame`](Self::render_frame), for frames rendered one after the other
+    /// in playback order with the same `history`. With `smooth` the luminosity picking the
+    /// characters is held by [`Hysteresis`] before any dithering.
+    pub fn render_next_frame(&self, frame: &DynamicImage, history: &mut Hysteresis) -> String {
+        self.render(frame, Some(history).filter(|_| self.options.smooth))
+    }
+
+    /// Like [`render_next_frame`](Self::render_next_frame) for frame `index` of a video of
+    /// `count` frames, faded in or out as far as it is within `fade` frames of either end.
+    pub fn render_frame_at(
+        &self,
+        frame: &DynamicImage,
+        index: usize,
+        count: usize,
+        history: &mut Hysteresis,
+    ) -> String {
+        match self.fade_factor(index, count) {
+            factor if factor < 1.0 => Renderer {
+                tone: ToneCurve::faded(&self.options, factor),
+                ..self.clone()
+            }
+            .render_next_frame(frame, history),
+            _ => self.render_next_frame(frame, history),
+        }
+    }
+
+    /// How bright frame `index` of `count` is drawn with `fade`, rising linearly from 0 for
+    /// the first frame to 1 for frame `fade` on, and falling the same way towards the last.
+    pub fn fade_factor(&self, index: usize, count: usize) -> f32 {
+        let fade = self.options.fade as usize;
+        if fade == 0 {
+            return 1.0;
+        }
+        let from_end = index.min(count.saturating_sub(index + 1));
+        (from_end as f32 / fade as f32).min(1.0)
+    }
+
+    /// Whether frames with luminosity weighted like `weighting` can be drawn from it alone
+    /// with [`render_luma`](Self::render_luma): one character without color per pixel,
+    /// nothing that needs the color channels, and the weighting `luma_mode` asks for. Gray
+    /// frames from ffmpeg are the Y plane of the video, weighted by its color matrix, so an
+    /// unknown `weighting` of `None` needs RGB.
+    pub fn renders_luma(&self, weighting: Option<LumaMode>) -> bool {
+        weighting == Some(self.options.luma_mode) && self.draws_luma_alone()
+    }
+
+    fn draws_luma_alone(&self) -> bool {
+        let options = &self.options;
+        options.mode == RenderMode::Ascii
+            && options.color_mode == ColorMode::Mono
+            && options.map_channel == MapChannel::Luma
+            && options.auto_levels.is_none()
+            && options.supersample == 1
+    }
+
+    /// Like [`render_next_frame`](Self::render_next_frame) for a `width`x`height` frame
+    /// given as the luminosity of its pixels row by row, like the Y plane ffmpeg decodes
+    /// video to, which spares building an image and weighting its channels. Only for
+    /// renderers that [`renders_luma`](Self::renders_luma).
+    pub fn render_luma(
+        &self,
+        luma: &[u8],
+        width: u32,
+        height: u32,
+        history: &mut Hysteresis,
+    ) -> String {
+        debug_assert!(
+            self.draws_luma_alone(),
+            "render_luma needs a mono ascii renderer"
+        );
+        let mut luma = self.adjust_luma(luma.to_vec(), width as usize, height as usize);
+        if self.options.smooth {
+            history.apply(&mut luma, self.options.smooth_threshold);
+        }
+        let levels: Vec<usize> = if self.options.dither {
+            dither_to(&luma, width as usize, height as usize, self.ramp.levels())
+        } else {
+            luma.iter().map(|&value| self.ramp.index(value)).collect()
+        };
+
+        // centered in the playback area like in render()
+        let left = self.options.width.saturating_sub(width) / 2;
+        let top = self.options.height.saturating_sub(height) / 2;
+        let mut frame_buffer = String::with_capacity(self.frame_capacity());
+        for y in 0..self.options.height {
+            if let Some(tint) = self.options.tint {
+                ColorMode::Truecolor.write_fg(&mut frame_buffer, tint);
+            }
+            for x in 0..self.options.width {
+                let inside = x >= left && y >= top && x - left < width && y - top < height;
+                frame_buffer.push(if inside {
+                    self.chars[levels[((y - top) * width + x - left) as usize]]
+                } else {
+                    ' '
+                });
+            }
+            if self.options.tint.is_some() {
+                frame_buffer.push_str(RESET);
+            }
+            frame_buffer.push('\n');
+        }
+        frame_buffer
+    }
+
+    fn render(&self, frame: &DynamicImage, history: Option<&mut Hysteresis>) -> String {
+        let frame = &*self.downsampled(frame);
+        // taken before the adjustments, which drop the alpha channel
+        let transparent = self.transparency(frame);
+        let transparent = transparent.as_deref();
+        let stretched;
+        let frame = match self.options.auto_levels {
+            Some(levels) => {
+                stretched = self.stretch_levels(frame, levels);
+                &stretched
+            }
+            None => frame,
+        };
+        let RenderOptions {
+            width,
+            height,
+            mode,
+            color_mode,
+            ..
+        } = self.options;
+        let colored = color_mode != ColorMode::Mono;
+        let tint = self.options.tint.filter(|_| !colored);
+
+        let mut frame_buffer = String::with_capacity(self.frame_capacity());
+
+        // frames scaled with --keep-aspect may be smaller than the playback area, center them
+        let (cell_width, cell_height) = mode.cell_pixels();
+        let (frame_width, frame_height) = frame.dimensions();
+        let columns = frame_width.div_ceil(cell_width);
+        let rows = frame_height.div_ceil(cell_height);
+        let left = width.saturating_sub(columns) / 2;
+        let top = height.saturating_sub(rows) / 2;
+
+        if mode.is_graphics() {
+            // 图像从光标位置开始绘制
+            let mut frame_buffer = format!("\x1B[{};{}H", top + 1, left + 1);
+            match mode {
+                RenderMode::Kitty { .. } => frame_buffer.push_str(&kitty::encode(
+                    &self.graphics_pixels(frame),
+                    columns,
+                    rows,
+                )),
+                _ => frame_buffer.push_str(&self.render_sixel(frame)),
+            }
+            return frame_buffer;
+        }
+
+        // edge detection looks at neighbouring pixels and error diffusion has to visit them
+        // in order, so both run over the whole frame before any cell is written
+        let lit = Ramp::linear(2);
+        let ramp = match mode {
+            RenderMode::HalfBlock => &lit,
+            _ => &self.ramp,
+        };
+        let whole_frame = self.options.dither || self.options.edges || history.is_some();
+        let luma = |history: Option<&mut Hysteresis>| {
+            let mut luma = self.adjusted_luma(frame);
+            if let Some(history) = history {
+                history.apply(&mut luma, self.options.smooth_threshold);
+            }
+            // see-through pixels stay dark, and don't spread any error around when dithered
+            for (luma, _) in luma
+                .iter_mut()
+                .zip(transparent.unwrap_or_default())
+                .filter(|(_, &transparent)| transparent)
+            {
+                *luma = 0;
+            }
+            luma
+        };
+        let quantized = match mode {
+            // the dots are always picked over the whole frame, as it's only two levels
+            RenderMode::Braille { threshold } => {
+                Some(self.threshold_frame(luma(history), frame, threshold))
+            }
+            _ => (whole_frame && (mode == RenderMode::Ascii || !colored))
+                .then(|| self.quantize_frame(luma(history), frame, ramp)),
+        };
+        let quantized = quantized.as_deref();
+
+        for y in 0..height {
+            // every line sets the tint of its own, as frame diffs redraw single lines
+            if let Some(tint) = tint {
+                ColorMode::Truecolor.write_fg(&mut frame_buffer, tint);
+            }
+            // whether the last cell written set a color that padding would inherit
+            let mut painted = false;
+            for x in 0..width {
+                let cell = (x >= left && y >= top && x - left < columns && y - top < rows)
+                    .then(|| ((x - left) * cell_width, (y - top) * cell_height))
+                    .filter(|&(px, py)| {
+                        !transparent.is_some_and(|transparent| {
+                            see_through(transparent, frame, px, py, cell_width, cell_height)
+                        })
+                    });
+                let Some((px, py)) = cell else {
+                    match self.options.background {
+                        Some(Background::Color(color)) if colored => {
+                            color_mode.write_bg(&mut frame_buffer, color);
+                            painted = true;
+                        }
+                        _ if painted => {
+                            frame_buffer.push_str(RESET);
+                            painted = false;
+                        }
+                        _ => {}
+                    }
+                    frame_buffer.push(' ');
+                    continue;
+                };
+                match mode {
+                    RenderMode::HalfBlock => self.push_half_block_cell(
+                        &mut frame_buffer,
+                        frame,
+                        px,
+                        py,
+                        quantized,
+                        transparent,
+                    ),
+                    RenderMode::Blocks if colored => {
+                        self.push_block_cell(&mut frame_buffer, frame, px, py)
+                    }
+                    RenderMode::Braille { .. } => self.push_braille_cell(
+                        &mut frame_buffer,
+                        frame,
+                        px,
+                        py,
+                        quantized.unwrap_or_default(),
+                        transparent,
+                    ),
+                    _ => self.push_ascii_cell(&mut frame_buffer, frame, px, py, quantized),
+                }
+                painted = colored;
+            }
+            if colored || tint.is_some() {
+                frame_buffer.push_str(RESET);
+            }
+            frame_buffer.push('\n');
+        }
+
+        frame_buffer
+    }
+
+    /// Roughly how many bytes a rendered frame takes up in memory: the space reserved for a
+    /// frame of text, or about the size of its pixels in base64 for graphics, which is what
+    /// Kitty frames of busy footage come to.
+    pub fn frame_len(&self) -> usize {
+        let RenderOptions {
+            width,
+            height,
+            mode,
+            ..
+        } = self.options;
+        if mode.is_graphics() {
+            let (cell_width, cell_height) = mode.cell_pixels();
+            (width * cell_width) as usize * (height * cell_height) as usize * 4
+        } else {
+            self.frame_capacity()
+        }
+    }
+
+    /// Upper bound of a rendered frame's length in bytes.
+    fn frame_capacity(&self) -> usize {
+        let RenderOptions {
+            width,
+            height,
+            mode,
+            color_mode,
+            ref charset,
+            ..
+        } = self.options;
+
+        // every cell may carry color escapes, and every line starts with the tint and ends in
+        // a reset when colorized
+        let background = usize::from(self.options.background.is_some());
+        let (escapes, char_len) = match mode {
+            RenderMode::HalfBlock => (2, UPPER_HALF.len_utf8()),
+            // every Braille pattern is three bytes long in UTF-8
+            RenderMode::Braille { .. } => (1 + background, 3),
+            _ => (
+                1 + background,
+                charset.iter().map(|c| c.len_utf8()).max().unwrap_or(1),
+            ),
+        };
+        let cell_len = escapes * color_mode.escape_len() as usize + RESET.len() + char_len;
+        let line_len = ColorMode::Truecolor.escape_len() as usize + RESET.len() + 1;
+        height as usize * (line_len + width as usize * cell_len)
+    }
+
+    /// Which pixels of `frame` are less opaque than `alpha_threshold`, row by row. None if
+    /// all of them are drawn.
+    fn transparency(&self, frame: &DynamicImage) -> Option<Vec<bool>> {
+        let threshold = self.options.alpha_threshold;
+        if threshold == 0 || !frame.color().has_alpha() {
+            return None;
+        }
+        let transparent: Vec<bool> = frame
+            .to_rgba8()
+            .pixels()
+            .map(|pixel| pixel.0[3] < threshold)
+            .collect();
+        transparent.contains(&true).then_some(transparent)
+    }
+
+    /// `frame` scaled down to the pixel grid with `supersample`.
+    fn downsampled<'a>(&self, frame: &'a DynamicImage) -> Cow<'a, DynamicImage> {
+        match self.options.supersample {
+            0 | 1 => Cow::Borrowed(frame),
+            factor => Cow::Owned(box_downsample(frame, factor)),
+        }
+    }
+
+    /// `frame` with every channel stretched from the luminosity range `levels` stands for to
+    /// the full range.
+    fn stretch_levels(&self, frame: &DynamicImage, levels: AutoLevels) -> DynamicImage {
+        let mut pixels = frame.to_rgb8();
+        let (lo, hi) = match levels {
+            AutoLevels::PerFrame => luma_range(&pixels, self.options.luma_mode),
+            AutoLevels::Range(lo, hi) => (lo, hi),
+        };
+        for pixel in pixels.pixels_mut() {
+            pixel.0 = pixel.0.map(|channel| stretch(channel, lo, hi));
+        }
+        DynamicImage::ImageRgb8(pixels)
+    }
+
+    /// The pixels of `frame` with the tonal adjustments applied, as shades of gray in mono
+    /// mode, for the graphics protocols.
+    fn graphics_pixels(&self, frame: &DynamicImage) -> RgbImage {
+        let mut pixels = frame.to_rgb8();
+        for pixel in pixels.pixels_mut() {
+            *pixel = Rgb(match self.options.color_mode {
+                ColorMode::Mono => {
+                    [self.tone
+                        .apply_luma(luminance(pixel.0, self.options.luma_mode));
+                        3]
+                }
+                _ => self.tone.apply_rgb(pixel.0),
+            });
+        }
+        pixels
+    }
+
+    /// Encodes `frame` as a Sixel image. Colors are quantized onto a 6x6x6 color cube, or in
+    /// mono mode the luminosity onto 32 shades of gray.
+    fn render_sixel(&self, frame: &DynamicImage) -> String {
+        let (width, height) = frame.dimensions();
+        let pixels = self.graphics_pixels(frame);
+        let (palette, indices): (Vec<[u8; 3]>, Vec<u8>) = match self.options.color_mode {
+            ColorMode::Mono => {
+                let palette = (0..SIXEL_GRAYS).map(|i| [(i * 255 / (SIXEL_GRAYS - 1)) as u8; 3]);
+                let indices = pixels
+                    .pixels()
+                    .map(|pixel| ((pixel.0[0] as u32 * (SIXEL_GRAYS - 1) + 127) / 255) as u8);
+                (palette.collect(), indices.collect())
+            }
+            _ => {
+                let level = |i: u32| (i * 255 / 5) as u8;
+                let palette = (0..216).map(|i| [level(i / 36), level(i / 6 % 6), level(i % 6)]);
+                let indices = pixels.pixels().map(|pixel| {
+                    let [r, g, b] = pixel.0.map(|c| (c as u32 * 5 + 127) / 255);
+                    (r * 36 + g * 6 + b) as u8
+                });
+                (palette.collect(), indices.collect())
+            }
+        };
+        sixel::encode(width as usize, height as usize, &palette, &indices)
+    }
+
+    /// The level of `ramp` every pixel of `frame` maps to by its adjusted luminosity `luma`,
+    /// dithered with `dither`.
+    fn quantize_frame(&self, luma: Vec<u8>, frame: &DynamicImage, ramp: &Ramp) -> Vec<usize> {
+        let (width, height) = (frame.width() as usize, frame.height() as usize);
+        if self.options.dither {
+            dither_to(&luma, width, height, ramp.levels())
+        } else {
+            luma.iter().map(|&value| ramp.index(value)).collect()
+        }
+    }
+
+    /// Like [`quantize_frame`](Self::quantize_frame) with two levels, but without dithering
+    /// every pixel at least as bright as `threshold` is 1.
+    fn threshold_frame(&self, luma: Vec<u8>, frame: &DynamicImage, threshold: u8) -> Vec<usize> {
+        if self.options.dither {
+            return self.quantize_frame(luma, frame, &Ramp::linear(2));
+        }
+        luma.iter()
+            .map(|&value| usize::from(value >= threshold))
+            .collect()
+    }
+
+    /// The luminosity, or other mapped channel, of every pixel with all adjustments applied,
+    /// the edge magnitude with `edges`.
+    fn adjusted_luma(&self, frame: &DynamicImage) -> Vec<u8> {
+        let luma = frame
+            .to_rgb8()
+            .pixels()
+            .map(|pixel| {
+                self.options
+                    .map_channel
+                    .value(pixel.0, self.options.luma_mode)
+            })
+            .collect();
+        self.adjust_luma(luma, frame.width() as usize, frame.height() as usize)
+    }
+
+    /// Applies the adjustments to the luminosity of a `width`x`height` frame, for
+    /// [`adjusted_luma`](Self::adjusted_luma).
+    fn adjust_luma(&self, mut luma: Vec<u8>, width: usize, height: usize) -> Vec<u8> {
+        luma.iter_mut()
+            .for_each(|value| *value = self.tone.apply_luma(*value));
+        if self.options.edges {
+            // inverting doesn't change the gradient, so flip the magnitude instead
+            luma = sobel(&luma, width, height, self.options.edge_threshold);
+            if self.options.invert {
+                luma.iter_mut().for_each(|value| *value = 255 - *value);
+            }
+        }
+        luma
+    }
+
+    fn push_ascii_cell(
+        &self,
+        buf: &mut String,
+        frame: &DynamicImage,
+        x: u32,
+        y: u32,
+        quantized: Option<&[usize]>,
+    ) {
+        let pixel = frame.get_pixel(x, y);
+        let color = self.color(rgb(pixel));
+        self.write_background(buf, color);
+        self.options.color_mode.write_fg(buf, color);
+        let index = match quantized {
+            Some(levels) => levels[(y * frame.width() + x) as usize],
+            None => self.ramp.index(self.tone.apply_luma(self.luma(pixel))),
+        };
+        buf.push(self.chars[index]);
+    }
+
+    fn push_block_cell(&self, buf: &mut String, frame: &DynamicImage, x: u32, y: u32) {
+        let color = self.color(rgb(frame.get_pixel(x, y)));
+        self.options.color_mode.write_bg(buf, color);
+        buf.push(' ');
+    }
+
+    fn push_half_block_cell(
+        &self,
+        buf: &mut String,
+        frame: &DynamicImage,
+        x: u32,
+        y: u32,
+        quantized: Option<&[usize]>,
+        transparent: Option<&[bool]>,
+    ) {
+        let top = frame.get_pixel(x, y);
+        // the last row of an odd-height frame has no bottom pixel, treat it as black
+        let bottom = if y + 1 < frame.height() {
+            frame.get_pixel(x, y + 1)
+        } else {
+            Rgba([0, 0, 0, 255])
+        };
+        let see_through = |y: u32| {
+            transparent.is_some_and(|transparent| {
+                y < frame.height() && transparent[(y * frame.width() + x) as usize]
+            })
+        };
+
+        match self.options.color_mode {
+            ColorMode::Mono => {
+                let lit = |pixel: Rgba<u8>, y: u32| match quantized {
+                    _ if see_through(y) => false,
+                    Some(levels) if y < frame.height() => {
+                        levels[(y * frame.width() + x) as usize] == 1
+                    }
+                    _ => self.tone.apply_luma(self.luma(pixel)) >= 128,
+                };
+                buf.push(match (lit(top, y), lit(bottom, y + 1)) {
+                    (false, false) => ' ',
+                    (true, false) => UPPER_HALF,
+                    (false, true) => LOWER_HALF,
+                    (true, true) => FULL_BLOCK,
+                });
+            }
+            // one half is see-through, the other is drawn over the background
+            color_mode if see_through(y) || see_through(y + 1) => {
+                let (pixel, half) = if see_through(y) {
+                    (bottom, LOWER_HALF)
+                } else {
+                    (top, UPPER_HALF)
+                };
+                buf.push_str(RESET);
+                if let Some(Background::Color(color)) = self.options.background {
+                    color_mode.write_bg(buf, color);
+                }
+                color_mode.write_fg(buf, self.color(rgb(pixel)));
+                buf.push(half);
+            }
+            color_mode => {
+                color_mode.write_fg(buf, self.color(rgb(top)));
+                color_mode.write_bg(buf, self.color(rgb(bottom)));
+                buf.push(UPPER_HALF);
+            }
+        }
+    }
+
+    /// Writes the Braille pattern of the 2x4 pixels from `x`, `y` on, with a dot for every
+    /// one that is lit in `lit`. Colored modes draw it in the average color of the lit
+    /// pixels.
+    fn push_braille_cell(
+        &self,
+        buf: &mut String,
+        frame: &DynamicImage,
+        x: u32,
+        y: u32,
+        lit: &[usize],
+        transparent: Option<&[bool]>,
+    ) {
+        let mut pattern = 0;
+        let (mut sum, mut count) = ([0u32; 3], 0);
+        // of all pixels in the cell, for the background
+        let (mut cell_sum, mut cell_count) = ([0u32; 3], 0);
+        for (dy, row) in BRAILLE_DOTS.iter().enumerate() {
+            for (dx, dot) in row.iter().enumerate() {
+                let (px, py) = (x + dx as u32, y + dy as u32);
+                // the blocks on the right and bottom edge may stick out of the frame
+                if px >= frame.width() || py >= frame.height() {
+                    continue;
+                }
+                let index = (py * frame.width() + px) as usize;
+                if transparent.is_some_and(|transparent| transparent[index]) {
+                    continue;
+                }
+                let pixel = rgb(frame.get_pixel(px, py));
+                for (sum, channel) in cell_sum.iter_mut().zip(pixel) {
+                    *sum += channel as u32;
+                }
+                cell_count += 1;
+                if lit[index] == 1 {
+                    pattern |= dot;
+                    for (sum, channel) in sum.iter_mut().zip(pixel) {
+                        *sum += channel as u32;
+                    }
+                    count += 1;
+                }
+            }
+        }
+
+        if cell_count > 0 {
+            let average = cell_sum.map(|sum| (sum / cell_count) as u8);
+            self.write_background(buf, self.color(average));
+        }
+        if count > 0 {
+            let average = sum.map(|sum| (sum / count) as u8);
+            self.options.color_mode.write_fg(buf, self.color(average));
+        }
+        buf.push(char::from_u32(BRAILLE_BLANK + pattern).unwrap_or(' '));
+    }
+
+    /// The value of the mapped channel of `pixel`, its luminosity by default.
+    fn luma(&self, pixel: Rgba<u8>) -> u8 {
+        self.options
+            .map_channel
+            .value(rgb(pixel), self.options.luma_mode)
+    }
+
+    /// Appends the escape filling the background behind a cell of the adjusted color `rgb`,
+    /// nothing without a background or in mono mode.
+    fn write_background(&self, buf: &mut String, rgb: [u8; 3]) {
+        if let Some(background) = self.options.background {
+            self.options
+                .color_mode
+                .write_bg(buf, background.behind(rgb));
+        }
+    }
+}
+
+/// Whether every pixel of the `cell_width` by `cell_height` cell from `x`, `y` on that lies
+/// within `frame` is see-through in `transparent`.
+fn see_through(
+    transparent: &[bool],
+    frame: &DynamicImage,
+    x: u32,
+    y: u32,
+    cell_width: u32,
+    cell_height: u32,
+) -> bool {
+    let (width, height) = frame.dimensions();
+    (y..(y + cell_height).min(height)).all(|py| {
+        (x..(x + cell_width).min(width)).all(|px| transparent[(py * width + px) as usize])
+    })
+}
+
+/// Scales `frame` down by `factor` on both axes, every pixel the average of a `factor` by
+/// `factor` block. Blocks cut off by the right and bottom edge average the pixels they have.
+/// Colors are weighted by opacity, so see-through pixels don't darken the ones next to them,
+/// and the alpha channel is kept if there is one.
+pub fn box_downsample(frame: &DynamicImage, factor: u32) -> DynamicImage {
+    let pixels = frame.to_rgba8();
+    let (width, height) = pixels.dimensions();
+    let factor = factor.max(1);
+    let downsampled =
+        RgbaImage::from_fn(width.div_ceil(factor), height.div_ceil(factor), |x, y| {
+            let mut sum = [0u32; 3];
+            let (mut alpha, mut count) = (0, 0);
+            for py in y * factor..((y + 1) * factor).min(height) {
+                for px in x * factor..((x + 1) * factor).min(width) {
+                    let [r, g, b, a] = pixels.get_pixel(px, py).0;
+                    for (sum, channel) in sum.iter_mut().zip([r, g, b]) {
+                        *sum += channel as u32 * a as u32;
+                    }
+                    alpha += a as u32;
+                    count += 1;
+                }
+            }
+            let [r, g, b] = sum.map(|sum| ((sum + alpha / 2) / alpha.max(1)) as u8);
+            Rgba([r, g, b, ((alpha + count / 2) / count) as u8])
+        });
+    if frame.color().has_alpha() {
+        DynamicImage::ImageRgba8(downsampled)
+    } else {
+        DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(downsampled).into_rgb8())
+    }
+}
+
+/// The darkest and brightest luminosity among `pixels`.
+pub fn luma_range(pixels: &RgbImage, luma_mode: LumaMode) -> (u8, u8) {
+    pixels
+        .pixels()
+        .map(|pixel| luminance(pixel.0, luma_mode))
+        .fold((u8::MAX, u8::MIN), |(lo, hi), luma| {
+            (lo.min(luma), hi.max(luma))
+        })
+}
+
+fn rgb(pixel: Rgba<u8>) -> [u8; 3] {
+    [pixel.0[0], pixel.0[1], pixel.0[2]]
+}
+
+/// Linearly maps a luminosity value across the given character ramp, which must not be empty.
+pub fn get_pixel_char(luminosity: u8, charset: &[char]) -> char {
+    debug_assert!(!charset.is_empty(), "the character ramp is empty");
+    // every character gets a band of 256/len luminosities, the last one ending at 255
+    charset[luminosity as usize * charset.len() / 256]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    #[test]
+    fn render_frame_maps_black_and_white_to_ramp_ends() {
+        let mut image = RgbImage::new(2, 1);
+        image.put_pixel(1, 0, Rgb([255, 255, 255]));
+        let renderer = Renderer::new(RenderOptions {
+            width: 2,
+            height: 1,
+            ..RenderOptions::default()
+        });
+
+        assert_eq!(
+            renderer.render_frame(&DynamicImage::ImageRgb8(image)),
+            " @\n"
+        );
+    }
+
+    #[test]
+    fn render_frame_paints_blocks_as_backgrounds() {
+        let mut image = RgbImage::new(2, 1);
+        image.put_pixel(0, 0, Rgb([200, 100, 0]));
+        let frame = DynamicImage::ImageRgb8(image);
+        let render = |color_mode| {
+            Renderer::new(RenderOptions {
+                width: 2,
+                height: 1,
+                mode: RenderMode::Blocks,
+                color_mode,
+                ..RenderOptions::default()
+            })
+            .render_frame(&frame)
+        };
+
+        assert_eq!(
+            render(ColorMode::Truecolor),
+            "\x1B[48;2;200;100;0m \x1B[48;2;0;0;0m \x1B[0m\n"
+        );
+        // without color the ramp stands in
+        assert_eq!(render(ColorMode::Mono), ": \n");
+    }
+
+    #[test]
+    fn luma_renders_like_the_gray_image() {
+        let luma: Vec<u8> = (0..12).map(|v| v * 23).collect();
+        let image = RgbImage::from_fn(4, 3, |x, y| {
+            let v = luma[(y * 4 + x) as usize];
+            Rgb([v, v, v])
+        });
+        for (dither, edges) in [(false, false), (true, false), (false, true)] {
+            let renderer = Renderer::new(RenderOptions {
+                width: 6,
+                height: 5,
+                contrast: 1.5,
+                dither,
+                edges,
+                tint: Some([0, 255, 0]),
+                ..RenderOptions::default()
+            });
+            assert!(renderer.renders_luma(Some(LumaMode::Rec709)));
+            assert_eq!(
+                renderer.render_luma(&luma, 4, 3, &mut Hysteresis::default()),
+                renderer.render_frame(&DynamicImage::ImageRgb8(image.clone())),
+            );
+        }
+        let colored = Renderer::new(RenderOptions {
+            color_mode: ColorMode::Truecolor,
+            ..RenderOptions::default()
+        });
+        assert!(!colored.renders_luma(Some(LumaMode::Rec709)));
+    }
+
+    #[test]
+    fn luma_is_only_rendered_with_the_weighting_of_the_source() {
+        let rec709 = Renderer::new(RenderOptions::default());
+        assert!(rec709.renders_luma(Some(LumaMode::Rec709)));
+        assert!(!rec709.renders_luma(Some(LumaMode::Rec601)));
+        assert!(!rec709.renders_luma(None));
+        let rec601 = Renderer::new(RenderOptions {
+            luma_mode: LumaMode::Rec601,
+            ..RenderOptions::default()
+        });
+        assert!(rec601.renders_luma(Some(LumaMode::Rec601)));
+        assert!(!rec601.renders_luma(Some(LumaMode::Rec709)));
+        let average = Renderer::new(RenderOptions {
+            luma_mode: LumaMode::Average,
+            ..RenderOptions::default()
+        });
+        assert!(!average.renders_luma(Some(LumaMode::Rec709)));
+    }
+
+    #[test]
+    fn tint_wraps_every_line_of_mono_output() {
+        let mut image = RgbImage::new(2, 2);
+        image.put_pixel(1, 0, Rgb([255, 255, 255]));
+        let frame = DynamicImage::ImageRgb8(image);
+        let render = |color_mode| {
+            Renderer::new(RenderOptions {
+                width: 2,
+                height: 2,
+                color_mode,
+                tint: Some([255, 176, 0]),
+                ..RenderOptions::default()
+            })
+            .render_frame(&frame)
+        };
+
+        assert_eq!(
+            render(ColorMode::Mono),
+            "\x1B[38;2;255;176;0m @\x1B[0m\n\x1B[38;2;255;176;0m  \x1B[0m\n"
+        );
+        // the colors of the picture win over the tint
+        assert!(!render(ColorMode::Truecolor).contains("255;176;0"));
+    }
+
+    #[test]
+    fn half_blocks_show_two_pixels_a_cell() {
+        // two columns of opposite pixels, four high, make two rows of cells
+        let image = RgbImage::from_fn(2, 4, |x, y| {
+            let lit = (x == 0) != (y == 1);
+            Rgb([if lit { 255 } else { 0 }; 3])
+        });
+        let frame = DynamicImage::ImageRgb8(image);
+        let render = |color_mode| {
+            Renderer::new(RenderOptions {
+                width: 2,
+                height: 2,
+                mode: RenderMode::HalfBlock,
+                color_mode,
+                ..RenderOptions::default()
+            })
+            .render_frame(&frame)
+        };
+
+        assert_eq!(render(ColorMode::Mono), "\u{2580}\u{2584}\n\u{2588} \n");
+        // in color the top pixel is the foreground and the bottom one the background
+        let colored = render(ColorMode::Truecolor);
+        assert_eq!(colored.lines().count(), 2);
+        assert!(colored.starts_with(
+            "\x1B[38;2;255;255;255m\x1B[48;2;0;0;0m\u{2580}\x1B[38;2;0;0;0m\x1B[48;2;255;255;255m\u{2580}"
+        ));
+    }
+
+    #[test]
+    fn render_frame_sets_braille_dots() {
+        // lit pixels at the top left, the second row on the right and the bottom left
+        let mut image = RgbImage::new(2, 4);
+        for (x, y) in [(0, 0), (1, 1), (0, 3)] {
+            image.put_pixel(x, y, Rgb([255, 255, 255]));
+        }
+        let renderer = Renderer::new(RenderOptions {
+            width: 1,
+            height: 1,
+            mode: RenderMode::Braille { threshold: 128 },
+            ..RenderOptions::default()
+        });
+
+        // dots 1, 5 and 7
+        assert_eq!(
+            renderer.render_frame(&DynamicImage::ImageRgb8(image)),
+            "\u{2851}\n"
+        );
+    }
+
+    #[test]
+    fn render_frame_fills_cell_backgrounds() {
+        let mut image = RgbImage::new(1, 1);
+        image.put_pixel(0, 0, Rgb([200, 100, 0]));
+        let frame = DynamicImage::ImageRgb8(image);
+        let render = |background| {
+            Renderer::new(RenderOptions {
+                width: 1,
+                height: 1,
+                color_mode: ColorMode::Truecolor,
+                charset: vec!['#'],
+                background: Some(background),
+                ..RenderOptions::default()
+            })
+            .render_frame(&frame)
+        };
+
+        assert_eq!(
+            render(Background::Color([0, 0, 40])),
+            "\x1B[48;2;0;0;40m\x1B[38;2;200;100;0m#\x1B[0m\n"
+        );
+        assert_eq!(
+            render(Background::Darkened),
+            "\x1B[48;2;62;31;0m\x1B[38;2;200;100;0m#\x1B[0m\n"
+        );
+    }
+
+    #[test]
+    fn supersample_averages_blocks_of_pixels() {
+        // a checkerboard of 2x2 blocks, each half black and half white, with a white column
+        // cut off by the right edge
+        let image = RgbImage::from_fn(5, 2, |x, y| {
+            if x == 4 || (x + y) % 2 == 0 {
+                Rgb([255, 255, 255])
+            } else {
+                Rgb([0, 0, 0])
+            }
+        });
+        let frame = DynamicImage::ImageRgb8(image);
+        let downsampled = box_downsample(&frame, 2).to_rgb8();
+        assert_eq!(downsampled.dimensions(), (3, 1));
+        assert_eq!(downsampled.get_pixel(0, 0), &Rgb([128, 128, 128]));
+        assert_eq!(downsampled.get_pixel(2, 0), &Rgb([255, 255, 255]));
+
+        let renderer = Renderer::new(RenderOptions {
+            width: 3,
+            height: 1,
+            charset: vec![' ', '+', '@'],
+            supersample: 2,
+            ..RenderOptions::default()
+        });
+        assert_eq!(renderer.options().frame_size(), (6, 2));
+        assert_eq!(renderer.render_frame(&frame), "++@\n");
+    }
+
+    #[test]
+    fn fade_ramps_up_and_down_at_the_ends() {
+        let frame = DynamicImage::ImageRgb8(RgbImage::from_pixel(1, 1, Rgb([255, 255, 255])));
+        let renderer = Renderer::new(RenderOptions {
+            width: 1,
+            height: 1,
+            charset: vec![' ', '+', '@'],
+            fade: 2,
+            ..RenderOptions::default()
+        });
+        let factors: Vec<f32> = (0..6).map(|index| renderer.fade_factor(index, 6)).collect();
+        assert_eq!(factors, [0.0, 0.5, 1.0, 1.0, 0.5, 0.0]);
+        let frames: String = (0..6)
+            .map(|index| renderer.render_frame_at(&frame, index, 6, &mut Hysteresis::default()))
+            .collect();
+        assert_eq!(frames, " \n+\n@\n@\n+\n \n");
+
+        // without a fade every frame is drawn in full
+        let renderer = Renderer::new(RenderOptions::default());
+        assert_eq!(renderer.fade_factor(0, 6), 1.0);
+    }
+
+    #[test]
+    fn transparent_pixels_are_left_blank() {
+        // white, a see-through white pixel, and a half transparent one
+        let mut image = RgbaImage::new(3, 1);
+        image.put_pixel(0, 0, Rgba([255, 255, 255, 255]));
+        image.put_pixel(1, 0, Rgba([255, 255, 255, 0]));
+        image.put_pixel(2, 0, Rgba([255, 255, 255, 100]));
+        let frame = DynamicImage::ImageRgba8(image);
+        let render = |alpha_threshold, background| {
+            Renderer::new(RenderOptions {
+                width: 3,
+                height: 1,
+                charset: vec![' ', '@'],
+                alpha_threshold,
+                background,
+                ..RenderOptions::default()
+            })
+            .render_frame(&frame)
+        };
+
+        assert_eq!(render(0, None), "@@@\n");
+        assert_eq!(render(1, None), "@ @\n");
+        assert_eq!(render(128, None), "@  \n");
+
+        // a see-through top half leaves only the bottom one drawn over the background
+        let mut image = RgbaImage::new(1, 2);
+        image.put_pixel(0, 0, Rgba([0, 0, 0, 0]));
+        image.put_pixel(0, 1, Rgba([200, 100, 0, 255]));
+        let renderer = Renderer::new(RenderOptions {
+            width: 1,
+            height: 1,
+            mode: RenderMode::HalfBlock,
+            color_mode: ColorMode::Truecolor,
+            background: Some(Background::Color([0, 0, 40])),
+            ..RenderOptions::default()
+        });
+        assert_eq!(
+            renderer.render_frame(&DynamicImage::ImageRgba8(image)),
+            "\x1B[0m\x1B[48;2;0;0;40m\x1B[38;2;200;100;0m\u{2584}\x1B[0m\n"
+        );
+    }
+}
@@ -0,0 +1,155 @@
+/*
+   This file is part of term-video.
+
+   term-video is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   term-video is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with term-video.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Rendered frames kept across runs, so playing the same video with the same settings again
+//! skips ffmpeg and rendering altogether.
+//!
+//! Every entry is a zstd compressed [`Recording`] under `$XDG_CACHE_HOME/term-video/rendered`,
+//! or `~/.cache/term-video/rendered`, named after a hash of a key describing the inputs and
+//! settings. The key is stored in front of the recording as well and compared on load, so a
+//! hash collision can't play the wrong frames.
+
+use crate::recording::Recording;
+use anyhow::{Context, Result};
+use std::{
+    collections::hash_map::DefaultHasher,
+    env,
+    fs::{self, File},
+    hash::{Hash, Hasher},
+    io::{BufRead, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    process,
+};
+
+// zstd's default, a good tradeoff for text this repetitive
+const COMPRESSION_LEVEL: i32 = 3;
+
+/// The cache entry for one combination of inputs and settings.
+#[derive(Clone, Debug)]
+pub struct RenderCache {
+    key: String,
+    path: PathBuf,
+}
+
+impl RenderCache {
+    /// The entry for `key`, which has to change whenever the rendered frames would. None if
+    /// there is no home directory to keep the cache in.
+    pub fn new(key: String) -> Option<RenderCache> {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let path = cache_dir()?.join(format!("{:016x}.rec.zst", hasher.finish()));
+        Some(RenderCache { key, path })
+    }
+
+    /// The frames stored for this entry by an earlier run, if there are any.
+    pub fn load(&self) -> Option<Recording> {
+        let file = File::open(&self.path).ok()?;
+        let mut data = BufReader::new(zstd::Decoder::new(file).ok()?);
+        let mut key = String::new();
+        for _ in 0..self.key.lines().count() {
+            data.read_line(&mut key).ok()?;
+        }
+        if key.trim_end_matches('\n') != self.key {
+            return None;
+        }
+        let mut recording = String::new();
+        data.read_to_string(&mut recording).ok()?;
+        Recording::parse(&recording).ok()
+    }
+
+    /// Stores `recording` for later runs, replacing what was there.
+    pub fn store(&self, recording: &Recording) -> Result<()> {
+        let dir = self
+            .path
+            .parent()
+            .context("render cache has no directory")?;
+        fs::create_dir_all(dir)
+            .with_context(|| format!("could not create directory {}", dir.display()))?;
+
+        // written next to the entry and moved over it, so a run that is interrupted or
+        // loading the entry at the same time never sees half of it
+        let partial = self
+            .path
+            .with_extension(format!("{}.partial", process::id()));
+        let write = || -> Result<()> {
+            let mut out = zstd::Encoder::new(File::create(&partial)?, COMPRESSION_LEVEL)?;
+            writeln!(out, "{}", self.key)?;
+            let mut buffered = BufWriter::new(&mut out);
+            recording.write(&mut buffered)?;
+            buffered.flush()?;
+            drop(buffered);
+            out.finish()?;
+            fs::rename(&partial, &self.path)?;
+            Ok(())
+        };
+        write().map_err(|e| {
+            let _ = fs::remove_file(&partial);
+            e.context(format!("could not write {}", self.path.display()))
+        })
+    }
+}
+
+/// Where rendered frames are kept.
+pub fn cache_dir() -> Option<PathBuf> {
+    let cache_home = env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))?;
+    Some(cache_home.join("term-video").join("rendered"))
+}
+
+/// Deletes all rendered frames, returning the directory they were kept in if there was one.
+pub fn clear() -> Result<Option<PathBuf>> {
+    match cache_dir() {
+        Some(dir) if dir.exists() => {
+            fs::remove_dir_all(&dir)
+                .with_context(|| format!("could not delete directory {}", dir.display()))?;
+            Ok(Some(dir))
+        }
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn render_cache_round_trips_and_checks_the_key() {
+        let dir = env::temp_dir().join(format!("term-video-test-{}-render-cache", process::id()));
+        let entry = |key: &str| RenderCache {
+            key: key.to_owned(),
+            path: dir.join("entry.rec.zst"),
+        };
+        let recording = Recording {
+            frame_rate: 25.0,
+            size: Some((2, 2)),
+            frames: vec![String::from("@@\n..\n"), String::from("..\n@@\n")],
+            frame_delays: Some(vec![Duration::from_millis(40), Duration::from_millis(80)]),
+        };
+
+        entry("video.mp4\nsettings").store(&recording).unwrap();
+        let loaded = entry("video.mp4\nsettings").load();
+        // same file, different settings
+        let mismatched = entry("video.mp4\nother settings").load();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(loaded, Some(recording));
+        assert_eq!(mismatched, None);
+    }
+}
@@ -0,0 +1,167 @@
+/*
+   This file is part of term-video.
+
+   term-video is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   term-video is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with term-video.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Contact sheets: thumbnails of frames sampled across a video, tiled into a grid of text
+//! to preview it at a glance.
+
+use crate::{player::scale_duration, terminal::visible_width};
+use std::time::Duration;
+
+/// Spaces between the thumbnails of a contact sheet.
+pub const GAP: u32 = 2;
+
+/// Rows of thumbnails a contact sheet has unless `--sheet-frames` says how many frames to show.
+pub const ROWS: u32 = 3;
+
+/// Width of every one of `columns` thumbnails sharing `terminal_width` columns, [`GAP`] apart.
+pub fn thumbnail_width(terminal_width: u32, columns: u32) -> u32 {
+    ((terminal_width + GAP) / columns.max(1))
+        .saturating_sub(GAP)
+        .max(1)
+}
+
+/// Where frame `index` starts in playback at `speed`, once the frames before it have been
+/// shown for their `frame_delays` or at `fps`.
+pub fn playback_position(
+    index: usize,
+    frame_delays: Option<&[Duration]>,
+    fps: f64,
+    speed: f64,
+) -> Duration {
+    let position = match frame_delays {
+        Some(delays) => delays[..index.min(delays.len())]
+            .iter()
+            .fold(Duration::ZERO, |sum, &delay| sum.saturating_add(delay)),
+        None => Duration::from_secs_f64(index as f64 / fps),
+    };
+    scale_duration(position, 1.0 / speed)
+}
+
+/// Indices of `samples` frames spread evenly across `count` of them, each from the middle of
+/// its share of the video. At most `count` are picked.
+pub fn sample_indices(count: usize, samples: usize) -> Vec<usize> {
+    let samples = samples.min(count);
+    (0..samples)
+        .map(|sample| (2 * sample + 1) * count / (2 * samples))
+        .collect()
+}
+
+/// Tiles rendered frames into rows of `columns` thumbnails, `gap` spaces apart, with every
+/// thumbnail's caption on the line below it and an empty line between the rows. Captions
+/// longer than a thumbnail is wide are cut off.
+pub fn tile(thumbnails: &[(String, String)], columns: usize, gap: usize) -> String {
+    let spacer = " ".repeat(gap);
+    let mut sheet = String::new();
+    for (row, thumbnails) in thumbnails.chunks(columns.max(1)).enumerate() {
+        if row > 0 {
+            sheet.push('\n');
+        }
+        let frames: Vec<Vec<&str>> = thumbnails
+            .iter()
+            .map(|(frame, _)| frame.lines().collect())
+            .collect();
+        let width = |lines: &[&str]| lines.first().map_or(0, |line| visible_width(line));
+        let height = frames.iter().map(Vec::len).max().unwrap_or_default();
+        for line in 0..height {
+            let cells: Vec<String> = frames
+                .iter()
+                .map(|lines| match lines.get(line) {
+                    Some(&text) => text.to_owned(),
+                    None => " ".repeat(width(lines)),
+                })
+                .collect();
+            sheet.push_str(cells.join(&spacer).trim_end_matches(' '));
+            sheet.push('\n');
+        }
+        let captions: Vec<String> = frames
+            .iter()
+            .zip(thumbnails)
+            .map(|(lines, (_, caption))| {
+                let width = width(lines);
+                let caption: String = caption.chars().take(width).collect();
+                format!("{:width$}", caption, width = width)
+            })
+            .collect();
+        sheet.push_str(captions.join(&spacer).trim_end());
+        sheet.push('\n');
+    }
+    sheet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_come_from_the_middle_of_their_share() {
+        assert_eq!(sample_indices(100, 4), [12, 37, 62, 87]);
+        assert_eq!(sample_indices(3, 3), [0, 1, 2]);
+        assert_eq!(sample_indices(2, 5), [0, 1]);
+        assert!(sample_indices(0, 5).is_empty());
+    }
+
+    #[test]
+    fn thumbnails_share_the_terminal_width() {
+        // 80 columns hold 4 thumbnails of 18 with 3 gaps of 2 between them
+        assert_eq!(thumbnail_width(80, 4), 18);
+        assert_eq!(thumbnail_width(80, 1), 80);
+        assert_eq!(thumbnail_width(3, 10), 1);
+        assert_eq!(thumbnail_width(80, 0), 80);
+    }
+
+    #[test]
+    fn positions_follow_the_frame_rate_or_delays() {
+        assert_eq!(playback_position(0, None, 25.0, 1.0), Duration::ZERO);
+        assert_eq!(
+            playback_position(50, None, 25.0, 1.0),
+            Duration::from_secs(2)
+        );
+        assert_eq!(
+            playback_position(50, None, 25.0, 2.0),
+            Duration::from_secs(1)
+        );
+        let delays = [100, 200, 300].map(Duration::from_millis);
+        assert_eq!(
+            playback_position(2, Some(&delays), 25.0, 1.0),
+            Duration::from_millis(300)
+        );
+        // frames past the delays start where the delays end
+        assert_eq!(
+            playback_position(5, Some(&delays), 25.0, 0.5),
+            Duration::from_millis(1200)
+        );
+        let endless = [Duration::MAX; 2];
+        assert_eq!(
+            playback_position(2, Some(&endless), 25.0, 0.01),
+            Duration::MAX
+        );
+    }
+
+    #[test]
+    fn thumbnails_are_tiled_with_their_captions() {
+        let thumbnail = |text: &str, caption: &str| (text.to_owned(), caption.to_owned());
+        let thumbnails = [
+            thumbnail("ab\ncd\n", "0:01"),
+            thumbnail("\x1B[31mef\x1B[0m\ngh\n", "x"),
+            thumbnail("ij\nkl\n", "3"),
+        ];
+        assert_eq!(
+            tile(&thumbnails, 2, 1),
+            "ab \x1B[31mef\x1B[0m\ncd gh\n0: x\n\nij\nkl\n3\n"
+        );
+    }
+}
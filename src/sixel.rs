@@ -0,0 +1,110 @@
+/*
+   This file is part of term-video.
+
+   term-video is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   term-video is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with term-video.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! A small Sixel encoder, for terminals that can show the actual video instead of characters.
+
+use std::fmt::Write as _;
+
+/// Encodes a `width`x`height` image, given as indices into `palette` in row-major order, as
+/// a Sixel sequence drawn at the cursor position.
+///
+/// Sixel data comes in bands six pixels tall. Each band is sent as one pass per palette
+/// color used in it, every pass overprinting the previous one, with each column's six bits
+/// packed into a single character and repeated characters run-length encoded.
+pub fn encode(width: usize, height: usize, palette: &[[u8; 3]], indices: &[u8]) -> String {
+    let mut out = String::new();
+    // raster attributes: square pixels and the image size
+    write!(out, "\x1BPq\"1;1;{};{}", width, height).unwrap();
+    for (index, rgb) in palette.iter().enumerate() {
+        // palette channels are percentages
+        let [r, g, b] = rgb.map(|c| (c as u32 * 100 + 127) / 255);
+        write!(out, "#{};2;{};{};{}", index, r, g, b).unwrap();
+    }
+
+    let mut bits = vec![0u8; palette.len() * width];
+    let mut used = vec![false; palette.len()];
+    for band in (0..height).step_by(6) {
+        bits.fill(0);
+        used.fill(false);
+        for dy in 0..6.min(height - band) {
+            let row = &indices[(band + dy) * width..(band + dy + 1) * width];
+            for (x, &color) in row.iter().enumerate() {
+                bits[color as usize * width + x] |= 1 << dy;
+                used[color as usize] = true;
+            }
+        }
+
+        if band > 0 {
+            // next band
+            out.push('-');
+        }
+        let mut first = true;
+        for color in (0..palette.len()).filter(|&color| used[color]) {
+            if !first {
+                // back to the start of the band for the next color
+                out.push('$');
+            }
+            first = false;
+            write!(out, "#{}", color).unwrap();
+            push_runs(&mut out, &bits[color * width..(color + 1) * width]);
+        }
+    }
+
+    out.push_str("\x1B\\");
+    out
+}
+
+/// Appends one color pass of a band, leaving out the empty columns at its end.
+fn push_runs(out: &mut String, columns: &[u8]) {
+    let end = columns
+        .iter()
+        .rposition(|&bits| bits != 0)
+        .map_or(0, |i| i + 1);
+    let mut columns = columns[..end].iter().peekable();
+    while let Some(&bits) = columns.next() {
+        let mut run = 1;
+        while columns.next_if_eq(&&bits).is_some() {
+            run += 1;
+        }
+        let sixel = (63 + bits) as char;
+        if run > 3 {
+            write!(out, "!{}{}", run, sixel).unwrap();
+        } else {
+            (0..run).for_each(|_| out.push(sixel));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_bands_per_color() {
+        // 5x7: black with a white left column, which spills into the second band
+        let mut indices = vec![0; 5 * 7];
+        for y in 0..7 {
+            indices[y * 5] = 1;
+        }
+        let sixel = encode(5, 7, &[[0, 0, 0], [255, 255, 255]], &indices);
+        assert_eq!(
+            sixel,
+            "\x1BPq\"1;1;5;7#0;2;0;0;0#1;2;100;100;100\
+             #0?!4~$#1~-#0?!4@$#1@\x1B\\"
+        );
+    }
+}
@@ -0,0 +1,74 @@
+/*
+   This file is part of term-video.
+
+   term-video is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   term-video is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with term-video.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Temporal hysteresis, keeping cells from flickering between neighbouring characters over
+//! noise in otherwise still footage.
+
+/// The luminosity every pixel was last drawn with, carried from one frame to the next.
+#[derive(Clone, Debug, Default)]
+pub struct Hysteresis {
+    drawn: Vec<u8>,
+}
+
+impl Hysteresis {
+    /// Holds every pixel of `luma` at the value it was drawn with in the previous frame
+    /// unless it moved at least `threshold` away from it, which it then is drawn with from
+    /// now on. Changes that large come through right away, so motion doesn't leave a trail;
+    /// slow drifts come through in steps of `threshold`. The first frame, and every frame of
+    /// a different size than the one before, is taken as it is.
+    pub fn apply(&mut self, luma: &mut [u8], threshold: u8) {
+        if self.drawn.len() != luma.len() {
+            self.drawn = luma.to_vec();
+            return;
+        }
+        for (value, drawn) in luma.iter_mut().zip(&mut self.drawn) {
+            if value.abs_diff(*drawn) >= threshold {
+                *drawn = *value;
+            } else {
+                *value = *drawn;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_changes_are_held_and_large_ones_pass() {
+        let mut hysteresis = Hysteresis::default();
+        let mut first = [100, 100, 100];
+        hysteresis.apply(&mut first, 10);
+        assert_eq!(first, [100, 100, 100]);
+
+        // noise is held, a jump like from motion comes through in the same frame
+        let mut second = [104, 96, 250];
+        hysteresis.apply(&mut second, 10);
+        assert_eq!(second, [100, 100, 250]);
+
+        // the moved pixel settles at its new value, no ghost of the old one stays behind
+        let mut third = [109, 91, 248];
+        hysteresis.apply(&mut third, 10);
+        assert_eq!(third, [100, 100, 250]);
+
+        // a slow drift accumulates until it crosses the threshold
+        let mut fourth = [110, 100, 0];
+        hysteresis.apply(&mut fourth, 10);
+        assert_eq!(fourth, [110, 100, 0]);
+    }
+}
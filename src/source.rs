@@ -0,0 +1,209 @@
+/*
+   This file is part of term-video.
+
+   term-video is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   term-video is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with term-video.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::ffmpeg::STDIN;
+use anyhow::{bail, Context, Result};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{self, Command, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+// numbers the temporary files of several downloads in the same run
+static DOWNLOADS: AtomicUsize = AtomicUsize::new(0);
+
+/// ffmpeg's capture device format for cameras on this system, and the first camera there.
+const CAMERA: (&str, &str) = if cfg!(target_os = "macos") {
+    ("avfoundation", "0")
+} else if cfg!(windows) {
+    ("dshow", "")
+} else {
+    ("v4l2", "/dev/video0")
+};
+
+/// A video input resolved into something ffmpeg can open directly.
+///
+/// Local files are passed through as they are. Remote `http(s)://` URLs are either handed to
+/// ffmpeg as well or downloaded to a temporary file first, which is deleted again when the
+/// `Input` is dropped. YouTube-style links are resolved with `yt-dlp`. `-` stands for video
+/// piped into stdin, which ffmpeg reads itself. Cameras are opened by [`Input::camera`].
+#[derive(Debug)]
+pub struct Input {
+    /// What ffmpeg and ffprobe should be pointed at
+    pub path: String,
+    /// Whether the video is fetched over the network
+    pub remote: bool,
+    /// Whether the video is read from stdin, which can only be done once and without seeking
+    pub stdin: bool,
+    /// ffmpeg input format of a capture device like a camera, which ffmpeg can't tell from
+    /// its name, and which has no end and can't be probed or seeked
+    pub format: Option<&'static str>,
+    downloaded: Option<PathBuf>,
+}
+
+impl Input {
+    pub fn resolve(input: &str, download: bool) -> Result<Input> {
+        if input == "-" {
+            return Ok(Input {
+                path: STDIN.to_owned(),
+                remote: false,
+                stdin: true,
+                format: None,
+                downloaded: None,
+            });
+        }
+        if !is_url(input) {
+            if !Path::new(input).exists() {
+                bail!("input file {} does not exist", input);
+            }
+            return Ok(Input {
+                path: input.to_owned(),
+                remote: false,
+                stdin: false,
+                format: None,
+                downloaded: None,
+            });
+        }
+
+        let host = input
+            .split_once("://")
+            .and_then(|(_, rest)| rest.split(['/', '?', '#']).next())
+            .unwrap_or_default();
+        if host.is_empty() {
+            bail!("{} is not a valid URL", input);
+        }
+
+        let youtube = is_youtube(host);
+        if !download {
+            let path = if youtube {
+                yt_dlp(&["-f", "best", "-g", input], input)?
+                    .lines()
+                    .next()
+                    .unwrap_or_default()
+                    .to_owned()
+            } else {
+                input.to_owned()
+            };
+            return Ok(Input {
+                path,
+                remote: true,
+                stdin: false,
+                format: None,
+                downloaded: None,
+            });
+        }
+
+        let target = env::temp_dir().join(format!(
+            "term-video-{}-download-{}",
+            process::id(),
+            DOWNLOADS.fetch_add(1, Ordering::SeqCst)
+        ));
+        let target_str = target.to_string_lossy();
+        if youtube {
+            yt_dlp(&["-f", "best", "-o", &target_str, input], input)?;
+        } else {
+            // curl -fL -sS -o <target> <url>
+            let curl = Command::new("curl")
+                .args(["-fL", "-sS", "-o", &target_str, input])
+                .stdout(Stdio::null())
+                .output()
+                .context("failed to execute curl to download the video")?;
+            if !curl.status.success() {
+                let _ = fs::remove_file(&target);
+                bail!(
+                    "could not download {}: {}",
+                    input,
+                    String::from_utf8_lossy(&curl.stderr).trim()
+                );
+            }
+        }
+
+        Ok(Input {
+            path: target_str.into_owned(),
+            remote: true,
+            stdin: false,
+            format: None,
+            downloaded: Some(target),
+        })
+    }
+
+    /// A camera, named the way the capture device format of the system wants it: a
+    /// `/dev/video*` path for v4l2 on Linux, an index or name for avfoundation on macOS and
+    /// `video=<name>` for dshow on Windows. An empty name picks the first camera, except on
+    /// Windows, which has no such default.
+    pub fn camera(device: &str) -> Result<Input> {
+        let (format, default) = CAMERA;
+        let device = match device {
+            "" if default.is_empty() => bail!(
+                "there is no default camera here, name one like --camera \"video=Integrated Camera\""
+            ),
+            "" => default,
+            device => device,
+        };
+        if format == "v4l2" && !Path::new(device).exists() {
+            bail!(
+                "there is no camera at {}, is it plugged in? Another one can be picked with --camera <device>",
+                device
+            );
+        }
+        Ok(Input {
+            path: device.to_owned(),
+            remote: false,
+            stdin: false,
+            format: Some(format),
+            downloaded: None,
+        })
+    }
+
+    /// Temporary file holding a downloaded video, if there is one.
+    pub fn downloaded(&self) -> Option<&Path> {
+        self.downloaded.as_deref()
+    }
+}
+
+impl Drop for Input {
+    fn drop(&mut self) {
+        if let Some(path) = &self.downloaded {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+pub fn is_url(input: &str) -> bool {
+    input.starts_with("http://") || input.starts_with("https://")
+}
+
+fn is_youtube(host: &str) -> bool {
+    let host = host.strip_prefix("www.").unwrap_or(host);
+    host == "youtu.be" || host == "youtube.com" || host.ends_with(".youtube.com")
+}
+
+fn yt_dlp(args: &[&str], url: &str) -> Result<String> {
+    let out = Command::new("yt-dlp")
+        .args(args)
+        .output()
+        .context("YouTube links need yt-dlp, install it or pass a direct video URL")?;
+    if !out.status.success() {
+        bail!(
+            "yt-dlp could not fetch {}: {}",
+            url,
+            String::from_utf8_lossy(&out.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+}
@@ -0,0 +1,231 @@
+/*
+   This file is part of term-video.
+
+   term-video is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   term-video is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with term-video.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Captions read from SRT files and burned into rendered frames.
+
+use crate::{
+    parse::parse_timestamp,
+    terminal::{overwrite_cells, visible_width},
+};
+use anyhow::{Context, Result};
+use std::{borrow::Cow, fs, path::Path, time::Duration};
+
+/// A caption and when it is shown.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cue {
+    pub start: Duration,
+    pub end: Duration,
+    pub lines: Vec<String>,
+}
+
+/// The cues of a subtitle file, sorted by when they start.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Subtitles {
+    cues: Vec<Cue>,
+    // the latest end of any cue up to the same index, which unlike the ends themselves
+    // never decreases and can be searched even if cues overlap
+    ends: Vec<Duration>,
+}
+
+impl Subtitles {
+    pub fn load(path: &Path) -> Result<Subtitles> {
+        let srt = fs::read_to_string(path)
+            .with_context(|| format!("could not read subtitles {}", path.display()))?;
+        Self::parse(&srt).with_context(|| format!("{} is not a valid SRT file", path.display()))
+    }
+
+    /// Parses SRT: blocks of an optional counter line, a `00:00:01,000 --> 00:00:02,500`
+    /// timing line and the caption lines, separated by blank lines. Formatting tags like
+    /// `<i>` are dropped, cues without any text or time on screen are left out.
+    pub fn parse(srt: &str) -> Result<Subtitles> {
+        let srt = srt.trim_start_matches('\u{feff}').replace("\r\n", "\n");
+        let mut cues = Vec::new();
+        for (index, block) in srt.split("\n\n").enumerate() {
+            let mut lines = block.lines().skip_while(|line| line.trim().is_empty());
+            let Some(mut timing) = lines.next() else {
+                continue;
+            };
+            if !timing.contains("-->") {
+                timing = lines.next().unwrap_or_default();
+            }
+            let (start, end) = parse_timing(timing)
+                .with_context(|| format!("cue {} has no valid timing", index + 1))?;
+
+            let lines: Vec<String> = lines
+                .map(strip_tags)
+                .map(|line| line.trim().to_owned())
+                .filter(|line| !line.is_empty())
+                .collect();
+            if !lines.is_empty() && end > start {
+                cues.push(Cue { start, end, lines });
+            }
+        }
+        Ok(Self::from_cues(cues))
+    }
+
+    pub fn from_cues(mut cues: Vec<Cue>) -> Subtitles {
+        cues.sort_by_key(|cue| cue.start);
+        let ends = cues
+            .iter()
+            .scan(Duration::ZERO, |latest, cue| {
+                *latest = (*latest).max(cue.end);
+                Some(*latest)
+            })
+            .collect();
+        Subtitles { cues, ends }
+    }
+
+    /// The same cues for a video that starts `offset` into the one they were written for,
+    /// like with `--start`.
+    pub fn shifted(self, offset: Duration) -> Subtitles {
+        let cues = self
+            .cues
+            .into_iter()
+            .filter(|cue| cue.end > offset)
+            .map(|cue| Cue {
+                start: cue.start.saturating_sub(offset),
+                end: cue.end - offset,
+                ..cue
+            })
+            .collect();
+        Self::from_cues(cues)
+    }
+
+    /// The caption lines showing at `time`, those of overlapping cues one after the other in
+    /// the order they started.
+    pub fn at(&self, time: Duration) -> Vec<&str> {
+        let started = self.cues.partition_point(|cue| cue.start <= time);
+        // everything up to the last cue ending by then is over
+        let first = self.ends[..started].partition_point(|&end| end <= time);
+        self.cues[first..started]
+            .iter()
+            .filter(|cue| cue.end > time)
+            .flat_map(|cue| cue.lines.iter().map(String::as_str))
+            .collect()
+    }
+
+    /// Writes the captions showing at `time` over the bottom rows of a rendered text frame,
+    /// centered.
+    pub fn burn_in<'a>(&self, frame: &'a str, time: Duration) -> Cow<'a, str> {
+        let captions = self.at(time);
+        if captions.is_empty() {
+            return Cow::Borrowed(frame);
+        }
+
+        let mut rows: Vec<Cow<str>> = frame.split_terminator('\n').map(Cow::Borrowed).collect();
+        let first = rows.len().saturating_sub(captions.len());
+        for (row, caption) in rows[first..].iter_mut().zip(captions) {
+            let width = visible_width(row);
+            // a space on either side sets the text apart from the picture
+            let caption: String = format!(" {} ", caption).chars().take(width).collect();
+            let column = (width - visible_width(&caption)) / 2;
+            *row = Cow::Owned(overwrite_cells(row, column, &caption));
+        }
+
+        let mut burned = rows.join("\n");
+        if frame.ends_with('\n') {
+            burned.push('\n');
+        }
+        Cow::Owned(burned)
+    }
+}
+
+/// Reads a `00:00:01,000 --> 00:00:02,500` timing line, ignoring position hints after it.
+fn parse_timing(line: &str) -> Option<(Duration, Duration)> {
+    let (start, rest) = line.split_once("-->")?;
+    let end = rest.split_whitespace().next()?;
+    let time = |s: &str| parse_timestamp(&s.trim().replace(',', "."));
+    Some((time(start)?, time(end)?))
+}
+
+/// Drops `<i>`-style HTML tags and `{\an8}`-style positioning codes from a caption line.
+fn strip_tags(line: &str) -> String {
+    let mut text = String::with_capacity(line.len());
+    let mut closing = None;
+    for ch in line.chars() {
+        match (closing, ch) {
+            (None, '<') => closing = Some('>'),
+            (None, '{') => closing = Some('}'),
+            (None, ch) => text.push(ch),
+            (Some(close), ch) if ch == close => closing = None,
+            _ => {}
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SRT: &str = "\u{feff}1\r\n00:00:01,000 --> 00:00:03,000\r\n<i>Hello</i> there\r\nSecond line\r\n\r\n2\r\n00:00:02,500 --> 00:00:04,000 X1:100\r\n{\\an8}Overlap\r\n\r\n3\r\n00:00:05,000 --> 00:00:06,000\r\n\r\n";
+
+    #[test]
+    fn subtitles_parse_srt() {
+        let subtitles = Subtitles::parse(SRT).unwrap();
+        assert_eq!(
+            subtitles.cues,
+            vec![
+                Cue {
+                    start: Duration::from_secs(1),
+                    end: Duration::from_secs(3),
+                    lines: vec![String::from("Hello there"), String::from("Second line")],
+                },
+                Cue {
+                    start: Duration::from_millis(2500),
+                    end: Duration::from_secs(4),
+                    lines: vec![String::from("Overlap")],
+                },
+            ]
+        );
+        assert!(Subtitles::parse("1\n00:00:01 -> 00:00:02\nText\n").is_err());
+    }
+
+    #[test]
+    fn subtitles_find_overlapping_cues() {
+        let subtitles = Subtitles::parse(SRT).unwrap();
+        assert!(subtitles.at(Duration::from_millis(500)).is_empty());
+        assert_eq!(
+            subtitles.at(Duration::from_millis(2700)),
+            ["Hello there", "Second line", "Overlap"]
+        );
+        assert_eq!(subtitles.at(Duration::from_millis(3500)), ["Overlap"]);
+        assert!(subtitles.at(Duration::from_secs(4)).is_empty());
+        assert_eq!(
+            subtitles
+                .clone()
+                .shifted(Duration::from_secs(2))
+                .at(Duration::from_millis(1500)),
+            ["Overlap"]
+        );
+    }
+
+    #[test]
+    fn subtitles_burn_into_the_bottom_rows() {
+        let subtitles = Subtitles::from_cues(vec![Cue {
+            start: Duration::ZERO,
+            end: Duration::from_secs(1),
+            lines: vec![String::from("hi")],
+        }]);
+        let frame = "@@@@@@@@\n@@@@@@@@\n";
+        assert_eq!(
+            subtitles.burn_in(frame, Duration::ZERO),
+            "@@@@@@@@\n@@ hi @@\n"
+        );
+        assert_eq!(subtitles.burn_in(frame, Duration::from_secs(1)), frame);
+    }
+}
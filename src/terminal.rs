@@ -0,0 +1,229 @@
+/*
+   This file is part of term-video.
+
+   term-video is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   term-video is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with term-video.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Keeping frames inside the terminal, whose size can change during playback.
+
+use crate::color::RESET;
+use std::{
+    borrow::Cow,
+    env,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+/// Tracks the size of the terminal, picking up changes when it receives SIGWINCH.
+pub struct TerminalSize {
+    size: Option<(usize, usize)>,
+    resized: Arc<AtomicBool>,
+}
+
+impl TerminalSize {
+    pub fn watch() -> Self {
+        let resized = Arc::new(AtomicBool::new(false));
+        #[cfg(unix)]
+        {
+            // without the handler the size is simply never updated
+            let _ = signal_hook::flag::register(signal_hook::consts::SIGWINCH, resized.clone());
+        }
+        TerminalSize {
+            size: term_size::dimensions(),
+            resized,
+        }
+    }
+
+    /// Columns and rows of the terminal, None if stdout isn't one.
+    pub fn get(&self) -> Option<(usize, usize)> {
+        self.size
+    }
+
+    /// Whether the terminal changed size since the last call.
+    pub fn changed(&mut self) -> bool {
+        if !self.resized.swap(false, Ordering::SeqCst) {
+            return false;
+        }
+        let size = term_size::dimensions();
+        let changed = size != self.size;
+        self.size = size;
+        changed
+    }
+}
+
+/// Size of a character cell in pixels, if the terminal reports it.
+pub fn cell_size() -> Option<(u32, u32)> {
+    let size = crossterm::terminal::window_size().ok()?;
+    if size.columns == 0 || size.rows == 0 || size.width == 0 || size.height == 0 {
+        return None;
+    }
+    Some((
+        (size.width / size.columns) as u32,
+        (size.height / size.rows) as u32,
+    ))
+}
+
+/// Whether the terminal is known to display Sixel graphics. Asking the terminal itself
+/// takes a round trip through stdin, so this is a best guess from the environment.
+pub fn likely_supports_sixel() -> bool {
+    const TERMS: [&str; 5] = ["mlterm", "foot", "yaft", "contour", "wezterm"];
+    const PROGRAMS: [&str; 4] = ["WezTerm", "iTerm.app", "mintty", "contour"];
+
+    let term = env::var("TERM").unwrap_or_default();
+    let program = env::var("TERM_PROGRAM").unwrap_or_default();
+    // xterm only draws Sixel when started as a VT340, but it is the reference implementation
+    TERMS.iter().any(|name| term.starts_with(name))
+        || PROGRAMS.contains(&program.as_str())
+        || env::var_os("XTERM_VERSION").is_some()
+}
+
+/// Whether the terminal speaks the Kitty graphics protocol, judging by the environment.
+pub fn likely_supports_kitty() -> bool {
+    let program = env::var("TERM_PROGRAM").unwrap_or_default();
+    env::var("TERM").is_ok_and(|term| term == "xterm-kitty" || term == "xterm-ghostty")
+        || env::var_os("KITTY_WINDOW_ID").is_some()
+        || ["WezTerm", "ghostty"].contains(&program.as_str())
+}
+
+/// Cuts a rendered frame down to `columns`x`rows` cells so it never wraps or scrolls the
+/// terminal. A frame exactly as tall as the terminal loses its final newline, which would
+/// scroll the first line off screen.
+pub fn clip_frame(frame: &str, columns: usize, rows: usize) -> Cow<'_, str> {
+    let lines = frame.matches('\n').count();
+    let width = frame.split('\n').map(visible_width).max().unwrap_or(0);
+    if width <= columns && lines <= rows {
+        return match frame.strip_suffix('\n') {
+            Some(trimmed) if lines == rows => Cow::Borrowed(trimmed),
+            _ => Cow::Borrowed(frame),
+        };
+    }
+
+    let mut clipped = String::with_capacity(frame.len());
+    for (index, line) in frame.split_terminator('\n').take(rows).enumerate() {
+        if index > 0 {
+            clipped.push('\n');
+        }
+        let (kept, cut) = split_at_column(line, columns);
+        clipped.push_str(kept);
+        // colors set in the kept part must not leak into the rest of the screen
+        if cut && kept.contains('\x1B') {
+            clipped.push_str(RESET);
+        }
+    }
+    if lines < rows {
+        clipped.push('\n');
+    }
+    Cow::Owned(clipped)
+}
+
+/// Splits `line` in front of its visible character number `columns`, returning the first part
+/// and whether anything was cut off.
+fn split_at_column(line: &str, columns: usize) -> (&str, bool) {
+    match visible_chars(line).nth(columns) {
+        Some((i, _)) => (&line[..i], true),
+        None => (line, false),
+    }
+}
+
+/// Writes `text` over the cells of `line` from visible column `column` on. The cells on
+/// either side keep their colors, `text` itself is drawn in the default colors.
+pub fn overwrite_cells(line: &str, column: usize, text: &str) -> String {
+    // every cell carries its own color escapes in front of it, so cutting right behind a
+    // cell leaves the colors of the next one with it
+    let end_of = |cells: usize| match cells.checked_sub(1) {
+        None => 0,
+        Some(last) => visible_chars(line)
+            .nth(last)
+            .map_or(line.len(), |(i, ch)| i + ch.len_utf8()),
+    };
+    let (before, after) = (end_of(column), end_of(column + visible_width(text)));
+
+    let mut line_out = String::with_capacity(line.len() + text.len() + RESET.len());
+    line_out.push_str(&line[..before]);
+    if line.contains('\x1B') {
+        line_out.push_str(RESET);
+    }
+    line_out.push_str(text);
+    line_out.push_str(&line[after..]);
+    line_out
+}
+
+/// Number of cells `line` takes up, not counting escape sequences.
+pub fn visible_width(line: &str) -> usize {
+    visible_chars(line).count()
+}
+
+/// The characters of `line` that take up a cell, with their byte offsets, skipping escape
+/// sequences.
+fn visible_chars(line: &str) -> impl Iterator<Item = (usize, char)> + '_ {
+    let mut escape = false;
+    line.char_indices().filter(move |&(_, ch)| {
+        if escape {
+            // CSI sequences end in a byte from '@' to '~', the opening '[' excluded
+            escape = ch == '[' || !('@'..='~').contains(&ch);
+            false
+        } else if ch == '\x1B' {
+            escape = true;
+            false
+        } else {
+            true
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clip_frame_cuts_lines_and_rows() {
+        assert_eq!(clip_frame("abcd\nefgh\nijkl\n", 2, 2), "ab\nef");
+        assert_eq!(clip_frame("abcd\nefgh\n", 2, 5), "ab\nef\n");
+    }
+
+    #[test]
+    fn clip_frame_keeps_fitting_frames() {
+        assert!(matches!(
+            clip_frame("ab\ncd\n", 2, 3),
+            Cow::Borrowed("ab\ncd\n")
+        ));
+        // the final newline of a full-height frame would scroll the terminal
+        assert!(matches!(
+            clip_frame("ab\ncd\n", 2, 2),
+            Cow::Borrowed("ab\ncd")
+        ));
+    }
+
+    #[test]
+    fn overwrite_cells_keeps_colors_around_the_text() {
+        assert_eq!(overwrite_cells("abcdef", 2, "XY"), "abXYef");
+        assert_eq!(overwrite_cells("abc", 2, "XYZ"), "abXYZ");
+        let (red, blue) = ("\x1B[38;5;196m", "\x1B[38;5;21m");
+        assert_eq!(
+            overwrite_cells(&format!("{red}a{red}b{blue}c{RESET}"), 1, "X"),
+            format!("{red}a{RESET}X{blue}c{RESET}")
+        );
+    }
+
+    #[test]
+    fn clip_frame_resets_cut_colors() {
+        let red = "\x1B[38;5;196m";
+        assert_eq!(
+            clip_frame(&format!("{red}ab{RESET}\n"), 1, 1),
+            format!("{red}a{RESET}")
+        );
+    }
+}
@@ -0,0 +1,72 @@
+/*
+   This file is part of term-video.
+
+   term-video is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   term-video is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with term-video.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::time::Duration;
+
+/// Formats a duration as `MM:SS.ss`, or `H:MM:SS.ss` from an hour on, the way
+/// [`parse_timestamp`](crate::parse::parse_timestamp) reads it back.
+pub fn format_timestamp(duration: Duration) -> String {
+    let centis = (duration.as_secs_f64() * 100.0).round() as u64;
+    let (seconds, centis) = (centis / 100, centis % 100);
+    let (minutes, seconds) = (seconds / 60, seconds % 60);
+    if minutes >= 60 {
+        format!(
+            "{}:{:02}:{:02}.{:02}",
+            minutes / 60,
+            minutes % 60,
+            seconds,
+            centis
+        )
+    } else {
+        format!("{:02}:{:02}.{:02}", minutes, seconds, centis)
+    }
+}
+
+/// Formats a duration in whole seconds as `MM:SS`, or `H:MM:SS` from an hour on, the way
+/// players show the position in a video.
+pub fn format_clock(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    let (minutes, seconds) = (seconds / 60, seconds % 60);
+    if minutes >= 60 {
+        format!("{}:{:02}:{:02}", minutes / 60, minutes % 60, seconds)
+    } else {
+        format!("{:02}:{:02}", minutes, seconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse_timestamp;
+
+    #[test]
+    fn format_timestamp_round_trips() {
+        assert_eq!(format_timestamp(Duration::from_millis(90_500)), "01:30.50");
+        assert_eq!(format_timestamp(Duration::from_secs(3723)), "1:02:03.00");
+        assert_eq!(
+            parse_timestamp(&format_timestamp(Duration::from_millis(3_723_250))),
+            Some(Duration::from_millis(3_723_250))
+        );
+    }
+
+    #[test]
+    fn format_clock_counts_whole_seconds() {
+        assert_eq!(format_clock(Duration::from_millis(90_999)), "01:30");
+        assert_eq!(format_clock(Duration::ZERO), "00:00");
+        assert_eq!(format_clock(Duration::from_secs(3723)), "1:02:03");
+    }
+}
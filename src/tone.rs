@@ -0,0 +1,160 @@
+/*
+   This file is part of term-video.
+
+   term-video is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   term-video is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with term-video.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Tonal adjustments applied to pixel values before they are mapped to characters or colors.
+
+use crate::render::RenderOptions;
+
+/// Lookup tables mapping every input level to its adjusted value, built once per renderer.
+#[derive(Clone, Debug)]
+pub struct ToneCurve {
+    lut: [u8; 256],
+    // the same with the adjustments that only concern character selection
+    luma_lut: [u8; 256],
+}
+
+impl ToneCurve {
+    pub fn new(options: &RenderOptions) -> Self {
+        Self::faded(options, 1.0)
+    }
+
+    /// The curve of `options` with the result scaled by `factor` between 0 and 1, dimming
+    /// colors towards black and luminosity towards the sparsest characters. Inversion comes
+    /// before that and posterization after it, so the picture fades to an empty screen
+    /// either way.
+    pub fn faded(options: &RenderOptions, factor: f32) -> Self {
+        let fade = |value: u8| (value as f32 * factor).round() as u8;
+        let mut lut = [0; 256];
+        for (level, out) in lut.iter_mut().enumerate() {
+            let value = (level as f32 - 128.0) * options.contrast + 128.0 + options.brightness;
+            let value = value.round().clamp(0.0, 255.0) as u8;
+            *out = apply_gamma(value, options.gamma);
+        }
+        let luma_lut = lut.map(|value| {
+            let value = if options.invert { 255 - value } else { value };
+            quantize(fade(value), options.levels)
+        });
+        ToneCurve {
+            lut: lut.map(fade),
+            luma_lut,
+        }
+    }
+
+    /// Adjusts a color channel.
+    pub fn apply(&self, level: u8) -> u8 {
+        self.lut[level as usize]
+    }
+
+    /// Adjusts a luminosity value about to be mapped to a character.
+    pub fn apply_luma(&self, level: u8) -> u8 {
+        self.luma_lut[level as usize]
+    }
+
+    pub fn apply_rgb(&self, [r, g, b]: [u8; 3]) -> [u8; 3] {
+        [self.apply(r), self.apply(g), self.apply(b)]
+    }
+}
+
+/// Linearly maps `lo..=hi` onto the full `0..=255` range, clamping values outside of it.
+/// A flat range, with `lo` not below `hi`, leaves values unchanged.
+pub fn stretch(value: u8, lo: u8, hi: u8) -> u8 {
+    if lo >= hi {
+        return value;
+    }
+    let scaled = (value.clamp(lo, hi) - lo) as u32 * 255 / (hi - lo) as u32;
+    scaled as u8
+}
+
+/// Gamma corrects a pixel value as `255 * (value / 255)^(1 / gamma)`, values above 1.0 lift
+/// the shadows. `gamma` must be positive.
+pub fn apply_gamma(value: u8, gamma: f32) -> u8 {
+    let normalized = value as f32 / 255.0;
+    (255.0 * normalized.powf(1.0 / gamma)).round() as u8
+}
+
+/// Posterizes a value into `levels` buckets as `(value / step) * step` with
+/// `step = 256 / levels`, every value mapping to the start of its bucket. 256 levels leave
+/// values unchanged. `levels` must be in `1..=256`.
+pub fn quantize(value: u8, levels: u16) -> u8 {
+    let step = 256 / levels.clamp(1, 256);
+    (value as u16 / step * step) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gamma_one_is_identity() {
+        for value in 0..=255 {
+            assert_eq!(apply_gamma(value, 1.0), value);
+        }
+    }
+
+    #[test]
+    fn gamma_table_keeps_endpoints() {
+        for gamma in [0.4, 1.0, 2.2] {
+            let curve = ToneCurve::new(&RenderOptions {
+                gamma,
+                ..RenderOptions::default()
+            });
+            assert_eq!(curve.apply_luma(0), 0);
+            assert_eq!(curve.apply_luma(255), 255);
+        }
+        // and lifts the midtones for gamma above 1
+        assert!(apply_gamma(64, 2.2) > 64);
+    }
+
+    #[test]
+    fn fading_dims_towards_an_empty_screen() {
+        let options = RenderOptions {
+            invert: true,
+            ..RenderOptions::default()
+        };
+        let half = ToneCurve::faded(&options, 0.5);
+        assert_eq!(half.apply(200), 100);
+        // inverted black is at full luminosity, halved by the fade
+        assert_eq!(half.apply_luma(0), 128);
+        let black = ToneCurve::faded(&options, 0.0);
+        assert!((0..=255).all(|level| black.apply(level) == 0 && black.apply_luma(level) == 0));
+    }
+
+    #[test]
+    fn stretch_fills_the_whole_range() {
+        assert_eq!(stretch(60, 60, 180), 0);
+        assert_eq!(stretch(120, 60, 180), 127);
+        assert_eq!(stretch(180, 60, 180), 255);
+        // values outside the range are clamped
+        assert_eq!(stretch(10, 60, 180), 0);
+        assert_eq!(stretch(250, 60, 180), 255);
+        // flat frames have no range to stretch
+        assert_eq!(stretch(90, 90, 90), 90);
+        assert_eq!(stretch(90, 200, 100), 90);
+    }
+
+    #[test]
+    fn quantize_bucket_boundaries() {
+        assert_eq!(quantize(63, 4), 0);
+        assert_eq!(quantize(64, 4), 64);
+        assert_eq!(quantize(191, 4), 128);
+        assert_eq!(quantize(255, 4), 192);
+        assert_eq!(quantize(255, 1), 0);
+        for value in 0..=255 {
+            assert_eq!(quantize(value, 256), value);
+        }
+    }
+}
@@ -0,0 +1,155 @@
+/*
+   This file is part of term-video.
+
+   term-video is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   term-video is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with term-video.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! End-to-end tests of extracting frames with ffmpeg and rendering them, on clips ffmpeg
+//! generates from its test sources. They are skipped where ffmpeg isn't installed.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{self, Command, Stdio},
+};
+
+use term_video::{
+    extract_frames,
+    ffmpeg::{probe_video, FfmpegArgs, ScaleFilter, Transform},
+    ExtractOptions, RenderOptions, Renderer,
+};
+
+// size of the frames extracted, one pixel per character in the ascii mode
+const WIDTH: u32 = 8;
+const HEIGHT: u32 = 4;
+
+/// A directory of its own for every test, removed again when the test is done.
+struct Scratch(PathBuf);
+
+impl Scratch {
+    fn new(test: &str) -> Scratch {
+        let dir = env::temp_dir().join(format!("term-video-{}-{}", test, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        Scratch(dir)
+    }
+
+    fn path(&self, name: &str) -> String {
+        self.0.join(name).to_str().unwrap().to_string()
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Whether `tool` runs, noting why the test is skipped if it doesn't.
+fn available(tool: &str) -> bool {
+    let runs = Command::new(tool)
+        .arg("-version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success());
+    if !runs {
+        eprintln!("{} isn't installed, skipping", tool);
+    }
+    runs
+}
+
+/// Encodes the output of the lavfi filter graph `source` losslessly into `video`.
+fn generate(source: &str, video: &str) {
+    let status = Command::new("ffmpeg")
+        .args(["-v", "error", "-y", "-f", "lavfi", "-i", source])
+        .args(["-c:v", "ffv1", video])
+        .status()
+        .unwrap();
+    assert!(status.success(), "ffmpeg couldn't generate {}", video);
+}
+
+fn extract_options() -> ExtractOptions {
+    ExtractOptions {
+        ffmpeg: PathBuf::from("ffmpeg"),
+        width: WIDTH,
+        height: HEIGHT,
+        cell_aspect: None,
+        start: None,
+        end: None,
+        crop: None,
+        sample_rate: 1,
+        transform: Transform::default(),
+        scale_filter: ScaleFilter::Bicubic,
+        ffmpeg_args: FfmpegArgs::default(),
+        interpolation: None,
+        tonemap: None,
+    }
+}
+
+fn render(frame: &Path) -> String {
+    let renderer = Renderer::new(RenderOptions {
+        width: WIDTH,
+        height: HEIGHT,
+        ..RenderOptions::default()
+    });
+    renderer.render_frame(&image::open(frame).unwrap())
+}
+
+#[test]
+fn solid_frames_render_as_the_ends_of_the_ramp() {
+    if !available("ffmpeg") {
+        return;
+    }
+    let scratch = Scratch::new("solid");
+    let video = scratch.path("solid.mkv");
+    // a second of white followed by a second of black, at 10 frames per second
+    generate(
+        "color=c=white:s=64x32:r=10:d=1[white];\
+         color=c=black:s=64x32:r=10:d=1[black];\
+         [white][black]concat=n=2",
+        &video,
+    );
+
+    let frames = extract_frames(&video, &scratch.path("cache"), &extract_options()).unwrap();
+    assert_eq!(frames.len(), 20);
+    assert_eq!(
+        image::image_dimensions(&frames[0]).unwrap(),
+        (WIDTH, HEIGHT)
+    );
+    assert_eq!(render(&frames[0]), "@@@@@@@@\n".repeat(HEIGHT as usize));
+    assert_eq!(render(&frames[19]), "        \n".repeat(HEIGHT as usize));
+}
+
+#[test]
+fn test_pattern_is_probed_and_extracted_in_full() {
+    if !available("ffmpeg") || !available("ffprobe") {
+        return;
+    }
+    let scratch = Scratch::new("pattern");
+    let video = scratch.path("pattern.mkv");
+    generate("testsrc=s=64x32:r=10:d=2", &video);
+
+    let info = probe_video(Path::new("ffprobe"), &video);
+    assert_eq!(info.dimensions, Some((64, 32)));
+    assert_eq!(info.frame_rate, Some(10.0));
+
+    let frames = extract_frames(&video, &scratch.path("cache"), &extract_options()).unwrap();
+    assert_eq!(frames.len(), 20);
+    // the pattern has bright and dark parts, unlike a solid frame
+    let text = render(&frames[0]);
+    assert_eq!(text.lines().count(), HEIGHT as usize);
+    assert!(text.chars().filter(|&c| c != '\n').any(|c| c != ' '));
+    assert!(text.chars().filter(|&c| c != '\n').any(|c| c != '@'));
+}